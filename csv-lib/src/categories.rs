@@ -0,0 +1,150 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::constants::MAX_CATEGORIES;
+
+/// Category table of a STRING or BOOLEAN column.
+///
+/// Once a column has more than `MAX_CATEGORIES` distinct values it is not considered categorical:
+/// the table is dropped and only `total` is updated (see `is_truncated`).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CategoryProfile {
+    counts: HashMap<String, usize>,
+    /// Number of non empty values
+    pub total: usize,
+    truncated: bool,
+}
+
+/// Key of categories which differ only in case or whitespace.
+fn normalized(category: &str) -> String {
+    category
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+impl CategoryProfile {
+    /// Register a non empty raw value of the column.
+    pub fn push(&mut self, value: &str) {
+        self.total += 1;
+
+        if self.truncated {
+            return;
+        }
+
+        *self.counts.entry(value.to_owned()).or_default() += 1;
+
+        if self.counts.len() > MAX_CATEGORIES {
+            self.counts = HashMap::default();
+            self.truncated = true;
+        }
+    }
+
+    /// Whether the column has too many distinct values to keep the category table.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Number of distinct categories.
+    pub fn cardinality(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn count(&self, category: &str) -> usize {
+        self.counts.get(category).copied().unwrap_or_default()
+    }
+
+    /// Share of the non empty values of the column which belong to the category.
+    pub fn proportion(&self, category: &str) -> f64 {
+        if self.total == 0 {
+            return 0.;
+        }
+
+        self.count(category) as f64 / self.total as f64
+    }
+
+    /// Categories with their counts and proportions, the most frequent first.
+    pub fn table(&self) -> Vec<(String, usize, f64)> {
+        let mut table: Vec<(String, usize, f64)> = self
+            .counts
+            .iter()
+            .map(|(category, count)| (category.clone(), *count, self.proportion(category)))
+            .collect();
+
+        table.sort_by(|(a_category, a_count, _), (b_category, b_count, _)| {
+            b_count
+                .cmp(a_count)
+                .then_with(|| a_category.cmp(b_category))
+        });
+        table
+    }
+
+    /// Categories whose proportion is below `min_proportion` (e.g. `0.01`), in alphabetical order.
+    pub fn rare(&self, min_proportion: f64) -> Vec<String> {
+        let mut rare: Vec<String> = self
+            .counts
+            .keys()
+            .filter(|category| self.proportion(category) < min_proportion)
+            .cloned()
+            .collect();
+
+        rare.sort();
+        rare
+    }
+
+    /// Groups of categories which differ only in case or whitespace, e.g. `Male`, `male` and `male `.
+    pub fn near_duplicates(&self) -> Vec<Vec<String>> {
+        let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for category in self.counts.keys() {
+            groups
+                .entry(normalized(category))
+                .or_default()
+                .push(category.clone());
+        }
+
+        groups
+            .into_values()
+            .filter(|group| group.len() > 1)
+            .map(|mut group| {
+                group.sort();
+                group
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CategoryProfile;
+    use crate::constants::MAX_CATEGORIES;
+
+    #[test]
+    fn test_category_profile() {
+        let mut profile = CategoryProfile::default();
+        for value in [
+            "Male", "Female", "Male", "male", "Other", "Male", "FEMALE", "Male",
+        ] {
+            profile.push(value);
+        }
+
+        assert_eq!(profile.cardinality(), 5);
+        assert_eq!(profile.table()[0], ("Male".to_owned(), 4, 0.5));
+        assert_eq!(profile.rare(0.2), vec!["FEMALE", "Female", "Other", "male"]);
+        assert_eq!(
+            profile.near_duplicates(),
+            vec![vec!["FEMALE", "Female"], vec!["Male", "male"]]
+        );
+    }
+
+    #[test]
+    fn test_high_cardinality() {
+        let mut profile = CategoryProfile::default();
+        for i in 0..=MAX_CATEGORIES {
+            profile.push(&i.to_string());
+        }
+
+        assert!(profile.is_truncated());
+        assert_eq!(profile.cardinality(), 0);
+        assert_eq!(profile.total, MAX_CATEGORIES + 1);
+    }
+}
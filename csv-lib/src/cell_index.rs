@@ -0,0 +1,472 @@
+//! Compact index of flagged cells (gaps, outliers), one row bitmap per column.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::error::{Error, Result};
+
+/// Rows of a container share the high bits of their index: `row >> CONTAINER_BITS`.
+const CONTAINER_BITS: u32 = 16;
+/// An array container with more rows is turned into a bitmap (both take 8 KiB at this size).
+const ARRAY_MAX_LEN: usize = 4096;
+const BITMAP_WORDS: usize = (1 << CONTAINER_BITS) / 64;
+const FILE_MAGIC: &[u8; 4] = b"CIDX";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Container {
+    /// Sorted low bits of sparse rows
+    Array(Vec<u16>),
+    /// One bit per row of dense containers
+    Bitmap(Box<[u64; BITMAP_WORDS]>),
+}
+
+impl Container {
+    fn insert(&mut self, low: u16) -> bool {
+        match self {
+            Container::Array(values) => {
+                // Rows are usually flagged in increasing order
+                if values.last().is_some_and(|last| *last < low) {
+                    values.push(low);
+                } else {
+                    match values.binary_search(&low) {
+                        Ok(_) => return false,
+                        Err(pos) => values.insert(pos, low),
+                    }
+                }
+
+                if values.len() > ARRAY_MAX_LEN {
+                    let mut words = Box::new([0_u64; BITMAP_WORDS]);
+                    for value in values.iter() {
+                        words[*value as usize / 64] |= 1 << (value % 64);
+                    }
+                    *self = Container::Bitmap(words);
+                }
+                true
+            }
+            Container::Bitmap(words) => {
+                let (word, bit) = (low as usize / 64, 1 << (low % 64));
+                let inserted = words[word] & bit == 0;
+                words[word] |= bit;
+                inserted
+            }
+        }
+    }
+
+    fn contains(&self, low: u16) -> bool {
+        match self {
+            Container::Array(values) => values.binary_search(&low).is_ok(),
+            Container::Bitmap(words) => words[low as usize / 64] & (1 << (low % 64)) != 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Container::Array(values) => values.len(),
+            Container::Bitmap(words) => words.iter().map(|w| w.count_ones() as usize).sum(),
+        }
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = u16> + '_> {
+        match self {
+            Container::Array(values) => Box::new(values.iter().copied()),
+            Container::Bitmap(words) => Box::new(words.iter().enumerate().flat_map(|(i, word)| {
+                (0..64)
+                    .filter(move |bit| word & (1 << bit) != 0)
+                    .map(move |bit| (i * 64 + bit) as u16)
+            })),
+        }
+    }
+}
+
+/// Set of row indexes, stored as sorted arrays for sparse ranges and bitmaps for dense ones (as
+/// roaring bitmaps): a few bytes per row at most, 1 bit per row in dense ranges.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RowSet {
+    containers: BTreeMap<usize, Container>,
+}
+
+impl RowSet {
+    /// # Return
+    /// Whether the row was not in the set.
+    pub fn insert(&mut self, row: usize) -> bool {
+        self.containers
+            .entry(row >> CONTAINER_BITS)
+            .or_insert_with(|| Container::Array(Vec::new()))
+            .insert(row as u16)
+    }
+
+    pub fn contains(&self, row: usize) -> bool {
+        self.containers
+            .get(&(row >> CONTAINER_BITS))
+            .is_some_and(|container| container.contains(row as u16))
+    }
+
+    pub fn len(&self) -> usize {
+        self.containers.values().map(Container::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.containers.is_empty()
+    }
+
+    /// Rows in increasing order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.containers.iter().flat_map(|(high, container)| {
+            container
+                .iter()
+                .map(move |low| (high << CONTAINER_BITS) | low as usize)
+        })
+    }
+
+    /// At most `limit` rows, skipping the first `offset` ones. Whole containers are skipped
+    /// without being iterated.
+    pub fn page(&self, mut offset: usize, limit: usize) -> Vec<usize> {
+        let mut page = Vec::with_capacity(limit.min(ARRAY_MAX_LEN));
+
+        for (high, container) in self.containers.iter() {
+            if page.len() == limit {
+                break;
+            }
+
+            let len = container.len();
+            if offset >= len {
+                offset -= len;
+                continue;
+            }
+
+            page.extend(
+                container
+                    .iter()
+                    .skip(offset)
+                    .take(limit - page.len())
+                    .map(|low| (high << CONTAINER_BITS) | low as usize),
+            );
+            offset = 0;
+        }
+
+        page
+    }
+}
+
+/// Cells of containers of the same rows (one per column) as `(low, col_id)` pairs, in row-major
+/// order.
+fn merge_containers<'a>(
+    containers: &'a [(usize, &'a Container)],
+) -> impl Iterator<Item = (u16, usize)> + 'a {
+    let mut iters: Vec<_> = containers
+        .iter()
+        .map(|(_, container)| container.iter())
+        .collect();
+    // Next cell of every container, the smallest row first and the smallest column among ties
+    let mut heads: BinaryHeap<Reverse<(u16, usize, usize)>> = iters
+        .iter_mut()
+        .enumerate()
+        .filter_map(|(i, iter)| Some(Reverse((iter.next()?, containers[i].0, i))))
+        .collect();
+
+    std::iter::from_fn(move || {
+        let Reverse((low, col_id, i)) = heads.pop()?;
+        if let Some(next) = iters[i].next() {
+            heads.push(Reverse((next, col_id, i)));
+        }
+        Some((low, col_id))
+    })
+}
+
+/// Flagged cells of a csv file (see `CsvToolkit::gaps` and `CsvToolkit::outliers`).
+///
+/// Only `(row, column)` pairs are kept, flagged values are read back from the data when needed.
+/// Use `save` and `load` to spill the index of a huge file to disk.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CellIndex {
+    columns: BTreeMap<usize, RowSet>,
+}
+
+impl CellIndex {
+    /// # Return
+    /// Whether the cell was not flagged yet.
+    pub fn insert(&mut self, row: usize, col_id: usize) -> bool {
+        self.columns.entry(col_id).or_default().insert(row)
+    }
+
+    pub fn contains(&self, row: usize, col_id: usize) -> bool {
+        self.columns
+            .get(&col_id)
+            .is_some_and(|rows| rows.contains(row))
+    }
+
+    pub fn clear(&mut self) {
+        self.columns.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    /// Number of flagged cells.
+    pub fn len(&self) -> usize {
+        self.columns.values().map(RowSet::len).sum()
+    }
+
+    /// Number of flagged cells of a column.
+    pub fn count(&self, col_id: usize) -> usize {
+        self.columns
+            .get(&col_id)
+            .map(RowSet::len)
+            .unwrap_or_default()
+    }
+
+    /// Columns with at least one flagged cell, in increasing order.
+    pub fn columns(&self) -> impl Iterator<Item = usize> + '_ {
+        self.columns.keys().copied()
+    }
+
+    /// Flagged rows of a column.
+    pub fn column(&self, col_id: usize) -> Option<&RowSet> {
+        self.columns.get(&col_id)
+    }
+
+    /// Flagged columns of a row, in increasing order.
+    pub fn row(&self, row: usize) -> Vec<usize> {
+        self.columns
+            .iter()
+            .filter(|(_, rows)| rows.contains(row))
+            .map(|(col_id, _)| *col_id)
+            .collect()
+    }
+
+    /// Rows with at least one flagged cell, in increasing order.
+    pub fn rows(&self) -> Vec<usize> {
+        let mut rows = RowSet::default();
+        for column in self.columns.values() {
+            for row in column.iter() {
+                rows.insert(row);
+            }
+        }
+
+        rows.iter().collect()
+    }
+
+    /// Page of flagged cells as `(row, col_id)` pairs: the rows of `col_id` if set, otherwise every
+    /// cell in row-major order.
+    ///
+    /// # Arguments
+    ///
+    /// * `col_id` - Column to list, `None` for all columns.
+    /// * `offset` - Number of cells to skip.
+    /// * `limit` - Max number of cells in the page.
+    ///
+    pub fn page(&self, col_id: Option<usize>, offset: usize, limit: usize) -> Vec<(usize, usize)> {
+        match col_id {
+            Some(col_id) => self
+                .columns
+                .get(&col_id)
+                .map(|rows| rows.page(offset, limit))
+                .unwrap_or_default()
+                .into_iter()
+                .map(|row| (row, col_id))
+                .collect(),
+            None => self.page_row_major(offset, limit),
+        }
+    }
+
+    /// Page of every flagged cell in row-major order.
+    ///
+    /// Containers with the same high bits cover the same rows in every column: whole groups of them
+    /// are skipped by their number of cells, the cells of the others are merged lazily.
+    fn page_row_major(&self, mut offset: usize, limit: usize) -> Vec<(usize, usize)> {
+        let mut page = Vec::with_capacity(limit.min(ARRAY_MAX_LEN));
+        let highs: BTreeSet<usize> = self
+            .columns
+            .values()
+            .flat_map(|rows| rows.containers.keys().copied())
+            .collect();
+
+        for high in highs {
+            if page.len() == limit {
+                break;
+            }
+
+            let containers: Vec<(usize, &Container)> = self
+                .columns
+                .iter()
+                .filter_map(|(col_id, rows)| Some((*col_id, rows.containers.get(&high)?)))
+                .collect();
+
+            let len: usize = containers
+                .iter()
+                .map(|(_, container)| container.len())
+                .sum();
+            if offset >= len {
+                offset -= len;
+                continue;
+            }
+
+            page.extend(
+                merge_containers(&containers)
+                    .skip(offset)
+                    .take(limit - page.len())
+                    .map(|(low, col_id)| ((high << CONTAINER_BITS) | low as usize, col_id)),
+            );
+            offset = 0;
+        }
+
+        page
+    }
+
+    /// Write the index to a file, to be read back by `load` (e.g. to free memory).
+    ///
+    /// # Errors
+    /// fs, io;
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path.as_ref())?);
+        writer.write_all(FILE_MAGIC)?;
+        write_u64(&mut writer, self.columns.len())?;
+
+        for (col_id, rows) in self.columns.iter() {
+            write_u64(&mut writer, *col_id)?;
+            write_u64(&mut writer, rows.containers.len())?;
+
+            for (high, container) in rows.containers.iter() {
+                write_u64(&mut writer, *high)?;
+                match container {
+                    Container::Array(values) => {
+                        writer.write_all(&[0])?;
+                        write_u64(&mut writer, values.len())?;
+                        for value in values {
+                            writer.write_all(&value.to_le_bytes())?;
+                        }
+                    }
+                    Container::Bitmap(words) => {
+                        writer.write_all(&[1])?;
+                        for word in words.iter() {
+                            writer.write_all(&word.to_le_bytes())?;
+                        }
+                    }
+                }
+            }
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Read an index written by `save`.
+    ///
+    /// # Errors
+    /// fs, io, not an index file;
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let invalid = || Error::parse("Not a cell index file").in_file(path.as_ref());
+        let mut reader = BufReader::new(File::open(path.as_ref())?);
+
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != *FILE_MAGIC {
+            return Err(invalid());
+        }
+
+        let mut index = CellIndex::default();
+        for _ in 0..read_u64(&mut reader)? {
+            let col_id = read_u64(&mut reader)?;
+            let mut rows = RowSet::default();
+
+            for _ in 0..read_u64(&mut reader)? {
+                let high = read_u64(&mut reader)?;
+                let mut kind = [0];
+                reader.read_exact(&mut kind)?;
+
+                let container = match kind[0] {
+                    0 => {
+                        let len = read_u64(&mut reader)?;
+                        let mut values = Vec::with_capacity(len.min(ARRAY_MAX_LEN));
+                        let mut buf = [0; 2];
+                        for _ in 0..len {
+                            reader.read_exact(&mut buf)?;
+                            values.push(u16::from_le_bytes(buf));
+                        }
+                        Container::Array(values)
+                    }
+                    1 => {
+                        let mut words = Box::new([0_u64; BITMAP_WORDS]);
+                        let mut buf = [0; 8];
+                        for word in words.iter_mut() {
+                            reader.read_exact(&mut buf)?;
+                            *word = u64::from_le_bytes(buf);
+                        }
+                        Container::Bitmap(words)
+                    }
+                    _ => return Err(invalid()),
+                };
+                rows.containers.insert(high, container);
+            }
+
+            index.columns.insert(col_id, rows);
+        }
+
+        Ok(index)
+    }
+}
+
+fn write_u64(writer: &mut impl Write, value: usize) -> std::io::Result<()> {
+    writer.write_all(&(value as u64).to_le_bytes())
+}
+
+fn read_u64(reader: &mut impl Read) -> std::io::Result<usize> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf) as usize)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CellIndex, RowSet};
+    use crate::constants::create_scratch_dir;
+
+    #[test]
+    fn test_row_set() {
+        let mut rows = RowSet::default();
+        // Dense range turned into a bitmap, sparse rows beyond it
+        for row in (0..10_000).chain([70_000, 1 << 40]) {
+            assert!(rows.insert(row));
+        }
+        assert!(!rows.insert(5));
+
+        assert_eq!(rows.len(), 10_002);
+        assert!(rows.contains(9_999) && rows.contains(1 << 40));
+        assert!(!rows.contains(10_000));
+        assert_eq!(rows.page(9_998, 3), vec![9_998, 9_999, 70_000]);
+        assert_eq!(rows.iter().last(), Some(1 << 40));
+    }
+
+    #[test]
+    fn test_cell_index() {
+        let mut index = CellIndex::default();
+        for row in 0..5_000 {
+            index.insert(row * 2, 3);
+        }
+        index.insert(1, 0);
+        index.insert(4, 0);
+
+        assert_eq!(index.len(), 5_002);
+        assert_eq!(index.count(0), 2);
+        assert_eq!(index.row(4), vec![0, 3]);
+        assert_eq!(&index.rows()[..3], &[0, 1, 2]);
+        assert_eq!(index.page(None, 1, 3), vec![(1, 0), (2, 3), (4, 0)]);
+        assert_eq!(index.page(None, 3, 2), vec![(4, 0), (4, 3)]);
+
+        // Containers of the first rows are skipped by their number of cells
+        index.insert(70_000, 0);
+        index.insert(70_000, 7);
+        assert_eq!(index.page(None, 5_002, 10), vec![(70_000, 0), (70_000, 7)]);
+        assert_eq!(index.page(Some(3), 4_999, 10), vec![(9_998, 3)]);
+
+        let dir = create_scratch_dir().unwrap();
+        let path = dir.join("index.bin");
+        index.save(&path).unwrap();
+        assert_eq!(CellIndex::load(&path).unwrap(), index);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
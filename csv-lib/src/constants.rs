@@ -1,12 +1,55 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::{
+    fs,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-pub const IMPORTS: [&str; 2] = ["use csv::Reader;", "use std::path::Path;"];
-pub const STRUCT_DERIVE: &str = "#[derive(Debug, Clone, PartialEq, Eq)]";
+pub const IMPORTS: [&str; 4] = [
+    "use csv::Reader;",
+    "use csv_lib::deserialization::{self, ParseOptions};",
+    "use csv_lib::error::Error;",
+    "use std::path::Path;",
+];
+pub const STRUCT_DERIVE: &str = "#[derive(Debug, Clone, PartialEq)]";
+pub const DEFAULT_NULL_TOKENS: [&str; 10] = [
+    "NA", "N/A", "n/a", "NaN", "nan", "null", "NULL", "None", "-", "?",
+];
+/// Max number of rows listed per type in a `TypeReport` (counts are always exact)
+pub const MAX_REPORTED_ROWS: usize = 100;
+/// Distinct values counted exactly per column before switching to a HyperLogLog estimate
+pub const EXACT_DISTINCT_LIMIT: usize = 10_000;
+/// HyperLogLog registers index bits (`2^12` registers, ~1.6% standard error)
+pub const HLL_PRECISION: u32 = 12;
+/// Values tracked per column to find the most frequent ones
+pub const TOP_K_CAPACITY: usize = 100;
+/// Distinct values tracked per column by a `CategoryProfile`, a column with more is not categorical
+pub const MAX_CATEGORIES: usize = 1_000;
+/// Bytes read from the start of a file to detect its dialect
+pub const SNIFF_SAMPLE_BYTES: usize = 64 * 1024;
+/// Rows of the sample compared to detect the delimiter and the header line
+pub const SNIFF_ROWS: usize = 100;
+/// Bytes of data parsed at once by a worker of a parallel pass (smaller files are read sequentially)
+pub const PARALLEL_CHUNK_BYTES: usize = 16 * 1024 * 1024;
 
-pub fn generate_temporary_file_name() -> String {
-    let tstmp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+static SCRATCH_DIR_COUNTER: AtomicUsize = AtomicUsize::new(0);
 
-    println!("Timestamp: {}", tstmp.as_millis());
+/// Create a new empty scratch directory (in the system temporary directory) for the intermediate
+/// files of a toolkit.
+pub fn create_scratch_dir() -> std::io::Result<PathBuf> {
+    let tstmp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    // Several toolkits could be created within the same millisecond (e.g. in tests)
+    let id = SCRATCH_DIR_COUNTER.fetch_add(1, Ordering::Relaxed);
 
-    return format!("temporary_{}_.dat", tstmp.as_millis());
+    let dir = std::env::temp_dir().join(format!(
+        "csv_toolkit_{}_{}_{}",
+        tstmp.as_millis(),
+        std::process::id(),
+        id
+    ));
+    fs::create_dir_all(&dir)?;
+
+    Ok(dir)
 }
@@ -1,105 +1,83 @@
 use std::{
-    cmp::min,
+    collections::HashMap,
     fs::{self, File},
     io::Write,
     ops::Sub,
     path::Path,
+    sync::OnceLock,
 };
 
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
 use csv::Reader;
 use regex::Regex;
 
-use crate::constants::{IMPORTS, STRUCT_DERIVE};
-use crate::error::Result;
+use crate::constants::{DEFAULT_NULL_TOKENS, IMPORTS, STRUCT_DERIVE};
+use crate::error::{Error, Result};
+use crate::schema::ColumnType;
 
+/// Smallest of one or more `DeserializationType` values, see `DeserializationType::min_of`.
+///
+/// # Return
+/// `Result<DeserializationType>`, an error for values which could not be compared.
 #[macro_export]
 macro_rules! min {
-    ($x:expr) => {
-        match $x {
-            DeserializationType::NUMBER(_) => $x,
-            _ => panic!("Incompatible expression type!"),
-        }
-    };
-
-    ($x:expr, $y:expr) => {
-        match ($x, $y) {
-            (DeserializationType::NUMBER(a), DeserializationType::NUMBER(b)) => {
-                if a < b { DeserializationType::NUMBER(a) } else { DeserializationType::NUMBER(b) }
-            },
-            _ => panic!("Incompatible expression type!"),
-        }
-    };
-
-    ($x:expr, $y:expr, $( $rest:expr ),+) => {
-        {
-            match ($x, $y) {
-                (DeserializationType::NUMBER(a), DeserializationType::NUMBER(b)) => {
-                    let min_of_two = if a < b { DeserializationType::NUMBER(a) } else { DeserializationType::NUMBER(b) };
-                    min!(min_of_two, $( $rest ),+)
-                },
-                _ => panic!("Incompatible expression type!"),
-            }
-        }
+    ($x:expr $(, $rest:expr)* $(,)?) => {
+        $crate::deserialization::DeserializationType::min_of([$x $(, $rest)*])
     };
 }
 
+/// Largest of one or more `DeserializationType` values, see `DeserializationType::max_of`.
+///
+/// # Return
+/// `Result<DeserializationType>`, an error for values which could not be compared.
 #[macro_export]
 macro_rules! max {
-    ($x:expr) => {
-        match $x {
-            DeserializationType::NUMBER(_) => $x,
-            _ => panic!("Incompatible expression type!"),
-        }
-    };
-
-    ($x:expr, $y:expr) => {
-        match ($x, $y) {
-            (DeserializationType::NUMBER(a), DeserializationType::NUMBER(b)) => {
-                if a > b { DeserializationType::NUMBER(a) } else { DeserializationType::NUMBER(b) }
-            },
-            _ => panic!("Incompatible expression type!"),
-        }
-    };
-
-    ($x:expr, $y:expr, $( $rest:expr ),+) => {
-        {
-            match ($x, $y) {
-                (DeserializationType::NUMBER(a), DeserializationType::NUMBER(b)) => {
-                    let max_of_two = if a > b { DeserializationType::NUMBER(a) } else { DeserializationType::NUMBER(b) };
-                    max!(max_of_two, $( $rest ),+)
-                },
-                _ => panic!("Incompatible expression type!"),
-            }
-        }
+    ($x:expr $(, $rest:expr)* $(,)?) => {
+        $crate::deserialization::DeserializationType::max_of([$x $(, $rest)*])
     };
 }
 
 #[derive(Debug, Clone)]
 pub enum DeserializationType {
-    NUMBER(f64),
+    INTEGER(i64),
+    FLOAT(f64),
     BOOLEAN(bool),
+    DATE(NaiveDate),
+    DATETIME(NaiveDateTime),
     STRING(String),
     EMPTY,
 }
 
+/// Difference of two numeric values, an error for any other value (see `checked_sub`).
 impl Sub<Self> for DeserializationType {
-    type Output = f64;
+    type Output = Result<f64>;
 
     fn sub(self, rhs: Self) -> Self::Output {
-        match (self, rhs) {
-            (DeserializationType::NUMBER(x), DeserializationType::NUMBER(y)) => x.sub(y),
-            _ => panic!("Could not subtract non numeric value(s)."),
-        }
+        self.checked_sub(&rhs).ok_or_else(|| {
+            Error::type_mismatch(
+                "numbers",
+                format!("{} and {}", self.type_name(), rhs.type_name()),
+            )
+            .with_value(format!(
+                "{} - {}",
+                self.to_cell_string(),
+                rhs.to_cell_string()
+            ))
+        })
     }
 }
 
+/// INTEGER and FLOAT values are compared by their numeric value (`INTEGER(1) == FLOAT(1.0)`).
 impl PartialEq for DeserializationType {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (DeserializationType::BOOLEAN(x), DeserializationType::BOOLEAN(y)) => x.eq(y),
-            (DeserializationType::NUMBER(x), DeserializationType::NUMBER(y)) => x.eq(y),
+            (DeserializationType::INTEGER(x), DeserializationType::INTEGER(y)) => x.eq(y),
+            (DeserializationType::DATE(x), DeserializationType::DATE(y)) => x.eq(y),
+            (DeserializationType::DATETIME(x), DeserializationType::DATETIME(y)) => x.eq(y),
             (DeserializationType::STRING(x), DeserializationType::STRING(y)) => x.eq(y),
             (DeserializationType::EMPTY, DeserializationType::EMPTY) => true,
+            (x, y) if x.is_numeric() && y.is_numeric() => x.as_f64().eq(&y.as_f64()),
             _ => false,
         }
     }
@@ -108,7 +86,12 @@ impl PartialEq for DeserializationType {
 impl PartialOrd for DeserializationType {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         match (self, other) {
-            (DeserializationType::NUMBER(x), DeserializationType::NUMBER(y)) => x.partial_cmp(y),
+            (DeserializationType::INTEGER(x), DeserializationType::INTEGER(y)) => x.partial_cmp(y),
+            (DeserializationType::DATE(x), DeserializationType::DATE(y)) => x.partial_cmp(y),
+            (DeserializationType::DATETIME(x), DeserializationType::DATETIME(y)) => {
+                x.partial_cmp(y)
+            }
+            (x, y) if x.is_numeric() && y.is_numeric() => x.as_f64().partial_cmp(&y.as_f64()),
             _ => None,
         }
     }
@@ -119,26 +102,226 @@ impl DeserializationType {
         format!("{}", self)
     }
 
+    /// INTEGER and FLOAT are considered the same (numeric) type.
     pub fn is_same_type(&self, other: &DeserializationType) -> bool {
+        matches!(
+            (self, other),
+            (
+                DeserializationType::BOOLEAN(_),
+                DeserializationType::BOOLEAN(_)
+            ) | (DeserializationType::DATE(_), DeserializationType::DATE(_))
+                | (
+                    DeserializationType::DATETIME(_),
+                    DeserializationType::DATETIME(_)
+                )
+                | (
+                    DeserializationType::STRING(_),
+                    DeserializationType::STRING(_)
+                )
+                | (DeserializationType::EMPTY, DeserializationType::EMPTY)
+        ) || (self.is_numeric() && other.is_numeric())
+    }
+
+    /// Common type of two column values (least upper bound of the type lattice):
+    /// `EMPTY` unifies with anything, `INTEGER` widens to `FLOAT`, `DATE` widens to `DATETIME`
+    /// and any other mix widens to `STRING`.
+    pub fn widen(&self, other: &DeserializationType) -> DeserializationType {
         match (self, other) {
-            (DeserializationType::BOOLEAN(_), DeserializationType::BOOLEAN(_)) => true,
-            (DeserializationType::NUMBER(_), DeserializationType::NUMBER(_)) => true,
-            (DeserializationType::STRING(_), DeserializationType::STRING(_)) => true,
-            (DeserializationType::EMPTY, DeserializationType::EMPTY) => true,
-            _ => false,
+            (DeserializationType::EMPTY, x) | (x, DeserializationType::EMPTY) => x.clone(),
+            (DeserializationType::INTEGER(_), DeserializationType::FLOAT(_))
+            | (DeserializationType::DATE(_), DeserializationType::DATETIME(_)) => other.clone(),
+            (DeserializationType::FLOAT(_), DeserializationType::INTEGER(_))
+            | (DeserializationType::DATETIME(_), DeserializationType::DATE(_)) => self.clone(),
+            (x, y) if x.is_same_type(y) => x.clone(),
+            _ => DeserializationType::STRING(String::default()),
+        }
+    }
+
+    /// Name of the variant, e.g. `"INTEGER"`.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            DeserializationType::INTEGER(_) => "INTEGER",
+            DeserializationType::FLOAT(_) => "FLOAT",
+            DeserializationType::BOOLEAN(_) => "BOOLEAN",
+            DeserializationType::DATE(_) => "DATE",
+            DeserializationType::DATETIME(_) => "DATETIME",
+            DeserializationType::STRING(_) => "STRING",
+            DeserializationType::EMPTY => "EMPTY",
         }
     }
 
+    /// Value as it should be written back to a csv cell.
+    pub fn to_cell_string(&self) -> String {
+        match self {
+            DeserializationType::INTEGER(x) => x.to_string(),
+            DeserializationType::FLOAT(x) => x.to_string(),
+            DeserializationType::BOOLEAN(x) => x.to_string(),
+            DeserializationType::DATE(x) => x.format("%Y-%m-%d").to_string(),
+            DeserializationType::DATETIME(x) => x.format("%Y-%m-%dT%H:%M:%S%.f").to_string(),
+            DeserializationType::STRING(x) => x.clone(),
+            DeserializationType::EMPTY => String::default(),
+        }
+    }
+
+    /// Numeric value of INTEGER and FLOAT values.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            DeserializationType::INTEGER(x) => Some(*x as f64),
+            DeserializationType::FLOAT(x) => Some(*x),
+            _ => None,
+        }
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            DeserializationType::INTEGER(_) | DeserializationType::FLOAT(_)
+        )
+    }
+
     pub fn is_ordered(&self) -> bool {
-        return self.is_same_type(&DeserializationType::NUMBER(f64::default()));
+        self.is_numeric()
+            || matches!(
+                self,
+                DeserializationType::DATE(_) | DeserializationType::DATETIME(_)
+            )
+    }
+
+    /// Difference of two numeric values.
+    ///
+    /// # Return
+    /// `None` if a value is not numeric or the difference is NaN (e.g. `inf - inf`).
+    pub fn checked_sub(&self, rhs: &Self) -> Option<f64> {
+        match (self, rhs) {
+            // Exact before the conversion, even where `i64` would overflow
+            (DeserializationType::INTEGER(x), DeserializationType::INTEGER(y)) => {
+                Some((*x as i128 - *y as i128) as f64)
+            }
+            _ => Some(self.as_f64()? - rhs.as_f64()?).filter(|d| !d.is_nan()),
+        }
+    }
+
+    /// Smaller of two ordered values (numbers, dates or datetimes). A NaN value is ignored, as by
+    /// `f64::min`.
+    ///
+    /// # Errors
+    /// Values which could not be compared, e.g. strings or a number and a date;
+    pub fn try_min(self, other: Self) -> Result<Self> {
+        Ok(match self.try_cmp(&other)? {
+            Some(std::cmp::Ordering::Greater) => other,
+            None if self.is_nan() => other,
+            _ => self,
+        })
+    }
+
+    /// Larger of two ordered values (numbers, dates or datetimes). A NaN value is ignored, as by
+    /// `f64::max`.
+    ///
+    /// # Errors
+    /// Values which could not be compared, e.g. strings or a number and a date;
+    pub fn try_max(self, other: Self) -> Result<Self> {
+        Ok(match self.try_cmp(&other)? {
+            Some(std::cmp::Ordering::Less) => other,
+            None if self.is_nan() => other,
+            _ => self,
+        })
+    }
+
+    /// Smallest of ordered values, see `try_min`.
+    ///
+    /// # Errors
+    /// No values, values which could not be compared;
+    pub fn min_of(values: impl IntoIterator<Item = Self>) -> Result<Self> {
+        Self::fold_ordered(values, Self::try_min)
+    }
+
+    /// Largest of ordered values, see `try_max`.
+    ///
+    /// # Errors
+    /// No values, values which could not be compared;
+    pub fn max_of(values: impl IntoIterator<Item = Self>) -> Result<Self> {
+        Self::fold_ordered(values, Self::try_max)
+    }
+
+    /// Total order of all values, for sorting: EMPTY < BOOLEAN < numbers < DATE < DATETIME <
+    /// STRING. INTEGER and FLOAT values are compared by their numeric value, NaN goes last as by
+    /// `f64::total_cmp`.
+    pub fn total_cmp(&self, other: &Self) -> std::cmp::Ordering {
+        use DeserializationType::*;
+
+        let rank = |value: &Self| match value {
+            EMPTY => 0,
+            BOOLEAN(_) => 1,
+            INTEGER(_) | FLOAT(_) => 2,
+            DATE(_) => 3,
+            DATETIME(_) => 4,
+            STRING(_) => 5,
+        };
+
+        match (self, other) {
+            (INTEGER(x), INTEGER(y)) => x.cmp(y),
+            (BOOLEAN(x), BOOLEAN(y)) => x.cmp(y),
+            (DATE(x), DATE(y)) => x.cmp(y),
+            (DATETIME(x), DATETIME(y)) => x.cmp(y),
+            (STRING(x), STRING(y)) => x.cmp(y),
+            (x, y) if x.is_numeric() && y.is_numeric() => x
+                .as_f64()
+                .unwrap_or_default()
+                .total_cmp(&y.as_f64().unwrap_or_default()),
+            (x, y) => rank(x).cmp(&rank(y)),
+        }
+    }
+
+    fn is_nan(&self) -> bool {
+        matches!(self, DeserializationType::FLOAT(x) if x.is_nan())
+    }
+
+    /// Comparison of ordered values, `None` if a value is NaN.
+    fn try_cmp(&self, other: &Self) -> Result<Option<std::cmp::Ordering>> {
+        let incomparable = || {
+            Error::type_mismatch(
+                "values of the same ordered type",
+                format!("{} and {}", self.type_name(), other.type_name()),
+            )
+        };
+
+        if !self.is_ordered() || !other.is_ordered() {
+            return Err(incomparable());
+        }
+        if self.is_nan() || other.is_nan() {
+            return match self.is_numeric() && other.is_numeric() {
+                true => Ok(None),
+                false => Err(incomparable()),
+            };
+        }
+
+        self.partial_cmp(other).map(Some).ok_or_else(incomparable)
+    }
+
+    fn fold_ordered(
+        values: impl IntoIterator<Item = Self>,
+        f: fn(Self, Self) -> Result<Self>,
+    ) -> Result<Self> {
+        let mut values = values.into_iter();
+        let first = values
+            .next()
+            .ok_or_else(|| Error::invalid_argument("No values to compare"))?;
+        if !first.is_ordered() {
+            return Err(Error::type_mismatch("an ordered value", first.type_name()));
+        }
+
+        values.try_fold(first, f)
     }
 }
 
 impl std::fmt::Display for DeserializationType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let col_type = match self {
-            DeserializationType::NUMBER(_) => "f64".to_owned(),
+            DeserializationType::INTEGER(_) => "i64".to_owned(),
+            DeserializationType::FLOAT(_) => "f64".to_owned(),
             DeserializationType::BOOLEAN(_) => "bool".to_owned(),
+            DeserializationType::DATE(_) => "String".to_owned(),
+            DeserializationType::DATETIME(_) => "String".to_owned(),
             DeserializationType::STRING(_) => "String".to_owned(),
             DeserializationType::EMPTY => "String".to_owned(),
         };
@@ -147,20 +330,325 @@ impl std::fmt::Display for DeserializationType {
     }
 }
 
+/// Settings of `parse_col_type`: number locale, accepted date formats and null markers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseOptions {
+    /// Separator of the fractional part (`.` or `,`)
+    pub decimal_separator: char,
+    /// Digit group separator of the integer part (`1,000`), if any
+    pub thousands_separator: Option<char>,
+    /// `chrono` formats of DATE values
+    pub date_formats: Vec<String>,
+    /// `chrono` formats of DATETIME values (RFC 3339 is always accepted)
+    pub datetime_formats: Vec<String>,
+    /// Values treated as `EMPTY` (gaps) in every column, e.g. `NA` or `?`
+    pub null_tokens: Vec<String>,
+    /// Per column null markers (by header name). Replace `null_tokens` for the column.
+    pub column_null_tokens: HashMap<String, Vec<String>>,
+    /// Declared column types (by header name). Values are coerced to them when possible.
+    pub column_types: HashMap<String, ColumnType>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            decimal_separator: '.',
+            thousands_separator: Some(','),
+            date_formats: vec!["%Y-%m-%d".to_owned()],
+            datetime_formats: vec![
+                "%Y-%m-%dT%H:%M:%S%.f".to_owned(),
+                "%Y-%m-%d %H:%M:%S%.f".to_owned(),
+                "%Y-%m-%dT%H:%M".to_owned(),
+                "%Y-%m-%d %H:%M".to_owned(),
+            ],
+            null_tokens: DEFAULT_NULL_TOKENS.iter().map(|s| s.to_string()).collect(),
+            column_null_tokens: HashMap::default(),
+            column_types: HashMap::default(),
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Continental European number locale: `1.234,5`
+    pub fn comma_decimal() -> Self {
+        ParseOptions {
+            decimal_separator: ',',
+            thousands_separator: Some('.'),
+            ..Default::default()
+        }
+    }
+
+    /// Parse a raw csv value to the most specific type:
+    /// INTEGER, FLOAT, BOOLEAN, DATETIME, DATE, STRING or EMPTY.
+    ///
+    /// File level `null_tokens` are used, see `parse_cell` for column specific null markers.
+    pub fn parse(&self, value: &str) -> Result<DeserializationType> {
+        self.parse_with_nulls(value, &self.null_tokens)
+    }
+
+    /// Parse a raw csv value of the `column` (header name), honouring `column_null_tokens` and `column_types`.
+    pub fn parse_cell(&self, column: &str, value: &str) -> Result<DeserializationType> {
+        let nulls = self
+            .column_null_tokens
+            .get(column)
+            .unwrap_or(&self.null_tokens);
+
+        match self.column_types.get(column) {
+            // Values like `0042` should not be parsed at all, blank ones are still gaps
+            Some(ColumnType::String)
+                if !value.trim().is_empty() && !nulls.iter().any(|token| token == value.trim()) =>
+            {
+                Ok(DeserializationType::STRING(value.trim().to_owned()))
+            }
+            Some(column_type) => {
+                Ok(column_type.coerce(value, self.parse_with_nulls(value, nulls)?))
+            }
+            None => self.parse_with_nulls(value, nulls),
+        }
+    }
+
+    /// Whether a raw value of the `column` is a null marker (or empty).
+    pub fn is_null(&self, column: &str, value: &str) -> bool {
+        let value = value.trim();
+        value.is_empty()
+            || self
+                .column_null_tokens
+                .get(column)
+                .unwrap_or(&self.null_tokens)
+                .iter()
+                .any(|token| token == value)
+    }
+
+    fn parse_with_nulls(&self, value: &str, nulls: &[String]) -> Result<DeserializationType> {
+        let value = value.trim();
+
+        if nulls.iter().any(|token| token == value) {
+            return Ok(DeserializationType::EMPTY);
+        }
+
+        if let Some(number) = self.parse_number(value) {
+            return Ok(number);
+        }
+
+        match value {
+            "FALSE" | "False" | "false" | "TRUE" | "True" | "true" => {
+                Ok(DeserializationType::BOOLEAN(value.to_lowercase().parse()?))
+            }
+            _ if value.is_empty() => Ok(DeserializationType::EMPTY),
+            _ => Ok(self
+                .parse_date(value)
+                .unwrap_or_else(|| DeserializationType::STRING(value.to_owned()))),
+        }
+    }
+
+    /// Parse every cell of a data row. Cells beyond `headers` use file level null markers.
+    pub fn parse_row(
+        &self,
+        headers: &[String],
+        row: &[String],
+    ) -> Result<Vec<DeserializationType>> {
+        row.iter()
+            .enumerate()
+            .map(|(col_id, value)| match headers.get(col_id) {
+                Some(header) => self.parse_cell(header, value),
+                None => self.parse(value),
+            })
+            .collect()
+    }
+
+    fn parse_number(&self, value: &str) -> Option<DeserializationType> {
+        let (sign, body) = match value.as_bytes().first()? {
+            b'-' => ("-", &value[1..]),
+            b'+' => ("", &value[1..]),
+            _ => ("", value),
+        };
+
+        let (mantissa, exponent) = match body.find(['e', 'E']) {
+            Some(pos) => (&body[..pos], Some(&body[pos + 1..])),
+            None => (body, None),
+        };
+
+        if let Some(exp) = exponent {
+            let digits = exp.strip_prefix(['+', '-']).unwrap_or(exp);
+            if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+        }
+
+        let (int_part, fraction) = match mantissa.split_once(self.decimal_separator) {
+            Some((int_part, fraction)) => (int_part, Some(fraction)),
+            None => (mantissa, None),
+        };
+
+        let int_digits = match self.thousands_separator {
+            Some(sep) if int_part.contains(sep) => {
+                let groups: Vec<&str> = int_part.split(sep).collect();
+                if groups[0].is_empty()
+                    || groups[0].len() > 3
+                    || groups[1..].iter().any(|g| g.len() != 3)
+                {
+                    return None;
+                }
+                groups.concat()
+            }
+            _ => int_part.to_owned(),
+        };
+
+        if !int_digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+
+        match fraction {
+            Some(f) if f.is_empty() || !f.bytes().all(|b| b.is_ascii_digit()) => return None,
+            None if int_digits.is_empty() => return None,
+            None if exponent.is_none() => {
+                if let Ok(x) = format!("{sign}{int_digits}").parse() {
+                    return Some(DeserializationType::INTEGER(x));
+                }
+            }
+            _ => {}
+        }
+
+        let normalized = format!(
+            "{sign}{}.{}{}",
+            if int_digits.is_empty() {
+                "0"
+            } else {
+                &int_digits
+            },
+            fraction.unwrap_or("0"),
+            exponent.map(|e| format!("e{e}")).unwrap_or_default()
+        );
+
+        normalized.parse().ok().map(DeserializationType::FLOAT)
+    }
+
+    fn parse_date(&self, value: &str) -> Option<DeserializationType> {
+        if !value.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+
+        if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+            return Some(DeserializationType::DATETIME(dt.naive_utc()));
+        }
+
+        for format in self.datetime_formats.iter() {
+            if let Ok(dt) = NaiveDateTime::parse_from_str(value, format) {
+                return Some(DeserializationType::DATETIME(dt));
+            }
+        }
+
+        for format in self.date_formats.iter() {
+            if let Ok(d) = NaiveDate::parse_from_str(value, format) {
+                return Some(DeserializationType::DATE(d));
+            }
+        }
+
+        None
+    }
+}
+
+/// Parse a raw csv value with the default `ParseOptions`.
 pub fn parse_col_type(value: &str) -> Result<DeserializationType> {
-    let int_re = Regex::new(r"^\d+$").unwrap();
-    let float_re = Regex::new(r"^(\d+)?\.\d+$").unwrap();
+    static DEFAULT_OPTIONS: OnceLock<ParseOptions> = OnceLock::new();
+
+    DEFAULT_OPTIONS
+        .get_or_init(ParseOptions::default)
+        .parse(value)
+}
 
-    let value = value.trim();
-    match value {
-        _ if int_re.is_match(value) | float_re.is_match(value) => {
-            Ok(DeserializationType::NUMBER(value.parse()?))
+/// Parse every cell of a data row through `parse_col_type`.
+pub fn parse_row(row: &[String]) -> Result<Vec<DeserializationType>> {
+    row.iter().map(|value| parse_col_type(value)).collect()
+}
+
+/// Parse a raw value of the `column` to a field of a struct generated by `generate_struct`.
+///
+/// # Errors
+/// Parse errors, a value which does not fit `T` (e.g. a gap of a column without gaps);
+pub fn parse_field<T>(options: &ParseOptions, column: &str, value: &str) -> Result<T>
+where
+    T: TryFrom<DeserializationType, Error = Error>,
+{
+    options
+        .parse_cell(column, value)
+        .and_then(T::try_from)
+        .map_err(|e| e.in_column(column).with_value(value))
+}
+
+/// Same as `parse_field`, for a column with gaps: `EMPTY` values (null tokens included) are `None`.
+///
+/// # Errors
+/// Parse errors, a value which does not fit `T`;
+pub fn parse_optional_field<T>(
+    options: &ParseOptions,
+    column: &str,
+    value: &str,
+) -> Result<Option<T>>
+where
+    T: TryFrom<DeserializationType, Error = Error>,
+{
+    match options.parse_cell(column, value) {
+        Ok(DeserializationType::EMPTY) => Ok(None),
+        parsed => parsed
+            .and_then(T::try_from)
+            .map(Some)
+            .map_err(|e| e.in_column(column).with_value(value)),
+    }
+}
+
+/// Raw value of a `String` field of a struct generated by `generate_struct`: the trimmed cell, as
+/// written in the file (DATE and DATETIME fields are strings too).
+pub fn parse_string_field(value: &str) -> String {
+    value.trim().to_owned()
+}
+
+/// Same as `parse_string_field`, for a column with gaps: `EMPTY` values (null tokens included) are
+/// `None`.
+///
+/// # Errors
+/// Parse errors;
+pub fn parse_optional_string_field(
+    options: &ParseOptions,
+    column: &str,
+    value: &str,
+) -> Result<Option<String>> {
+    match options.parse_cell(column, value) {
+        Ok(DeserializationType::EMPTY) => Ok(None),
+        Ok(_) => Ok(Some(parse_string_field(value))),
+        Err(e) => Err(e.in_column(column).with_value(value)),
+    }
+}
+
+impl TryFrom<DeserializationType> for i64 {
+    type Error = Error;
+
+    fn try_from(value: DeserializationType) -> Result<Self> {
+        match value {
+            DeserializationType::INTEGER(x) => Ok(x),
+            other => Err(Error::type_mismatch("INTEGER", other.type_name())),
         }
-        "FALSE" | "False" | "false" | "TRUE" | "True" | "true" => {
-            Ok(DeserializationType::BOOLEAN(value.to_lowercase().parse()?))
+    }
+}
+
+impl TryFrom<DeserializationType> for f64 {
+    type Error = Error;
+
+    fn try_from(value: DeserializationType) -> Result<Self> {
+        value
+            .as_f64()
+            .ok_or_else(|| Error::type_mismatch("number", value.type_name()))
+    }
+}
+
+impl TryFrom<DeserializationType> for bool {
+    type Error = Error;
+
+    fn try_from(value: DeserializationType) -> Result<Self> {
+        match value {
+            DeserializationType::BOOLEAN(x) => Ok(x),
+            other => Err(Error::type_mismatch("BOOLEAN", other.type_name())),
         }
-        _ if !value.is_empty() => Ok(DeserializationType::STRING(value.to_owned())),
-        _ => Ok(DeserializationType::EMPTY),
     }
 }
 
@@ -169,6 +657,10 @@ pub fn parse_col_type(value: &str) -> Result<DeserializationType> {
 /// This function takes a path to the source csv file, analyze it and return a mod in a String format to work with those data in common Rust format.
 /// Could be handy as a part of CLI tool.
 ///
+/// Values are parsed by the generated code through the default `ParseOptions` (see `parse_field`),
+/// `String` fields keep the raw cell (see `parse_string_field`). Fields of columns with gaps are
+/// `Option`s.
+///
 /// # Arguments
 /// `source` - Path to source csv file
 /// `dist` - Path to the output dir/file
@@ -178,39 +670,34 @@ pub fn parse_col_type(value: &str) -> Result<DeserializationType> {
 ///
 /// # Errors
 /// fs, io, type coersion;
-
 pub fn generate_struct<F: AsRef<Path>>(source: F, dist: F) -> Result<String> {
     let mut reader = Reader::from_path(source.as_ref())?;
-    let mut headers: Vec<String> = reader
+    let raw_headers: Vec<String> = reader
         .headers()?
-        .clone()
-        .into_iter()
+        .iter()
         .map(|s| s.trim().to_owned())
         .collect();
-    headers = parse_headers(headers);
+    let headers = parse_headers(raw_headers.clone());
 
-    let mut data_row: Vec<String> = vec![String::default(); headers.len()];
-    let mut tmp: Vec<String>;
+    // Column types are unified over all rows, so an INTEGER column with a single FLOAT becomes `f64`
+    let mut col_types: Vec<DeserializationType> = vec![DeserializationType::EMPTY; headers.len()];
+    let mut has_gaps: Vec<bool> = vec![false; headers.len()];
 
     for str_result in reader.records() {
         let str_rec = str_result?;
-        tmp = str_rec.into_iter().map(|s| s.trim().to_owned()).collect();
-        println!("Curr row: {:?}", tmp);
 
-        // Prove: both vec assigned above!
-        for i in 0..min(data_row.len(), tmp.len()) {
-            if data_row[i].is_empty() && !tmp[i].is_empty() {
-                data_row[i] = tmp[i].clone();
-            }
-        }
-
-        if !data_row.contains(&String::default()) {
-            println!("Break {:?}", &data_row);
-            break;
+        for ((col_type, gaps), value) in col_types
+            .iter_mut()
+            .zip(has_gaps.iter_mut())
+            .zip(str_rec.iter())
+        {
+            let value = parse_col_type(value)?;
+            *gaps |= value == DeserializationType::EMPTY;
+            *col_type = col_type.widen(&value);
         }
     }
 
-    let struct_name = if let Some(stem) = source.as_ref().file_stem().take() {
+    let struct_name = if let Some(stem) = source.as_ref().file_stem() {
         to_struct_name(stem.to_str().unwrap())
     } else {
         to_struct_name("CsvData")
@@ -219,18 +706,36 @@ pub fn generate_struct<F: AsRef<Path>>(source: F, dist: F) -> Result<String> {
     let mut struct_tmpl = format!("{STRUCT_DERIVE}\npub struct {}{{", &struct_name);
 
     let mut from_impl = format!(
-        "impl From<Vec<String>> for {}{{\n\tfn from(value: Vec<String>) -> Self {{\n\t\tSelf{{",
+        "impl TryFrom<Vec<String>> for {}{{\n\ttype Error = Error;\n\n\tfn try_from(value: Vec<String>) -> Result<Self, Error> {{\n\t\tlet options = ParseOptions::default();\n\n\t\tOk(Self{{",
         &struct_name
     );
 
     for (id, h_item) in headers.iter().enumerate() {
-        let value_type = parse_col_type(&data_row[id])?;
-        struct_tmpl += &format!("\n\t{h_item}: {},", value_type.display());
-        from_impl += &format!("\n\t\t\t{h_item}: value[{id}].parse().expect(&format!(\"Could not parse '{{}}' for '{h_item}'!\", value[{id}])),");
+        let value_type = col_types[id].display();
+        let column = &raw_headers[id];
+        let parse = match (has_gaps[id], value_type.as_str()) {
+            (true, "String") => format!(
+                "deserialization::parse_optional_string_field(&options, {column:?}, &value[{id}])?"
+            ),
+            (true, _) => format!(
+                "deserialization::parse_optional_field(&options, {column:?}, &value[{id}])?"
+            ),
+            (false, "String") => format!("deserialization::parse_string_field(&value[{id}])"),
+            (false, _) => {
+                format!("deserialization::parse_field(&options, {column:?}, &value[{id}])?")
+            }
+        };
+
+        if has_gaps[id] {
+            struct_tmpl += &format!("\n\t{h_item}: Option<{value_type}>,");
+        } else {
+            struct_tmpl += &format!("\n\t{h_item}: {value_type},");
+        }
+        from_impl += &format!("\n\t\t\t{h_item}: {parse},");
     }
 
     struct_tmpl += "\n}";
-    from_impl += "\n\t\t}\n\t}\n}";
+    from_impl += "\n\t\t})\n\t}\n}";
 
     if let Some(parent) = dist.as_ref().to_path_buf().parent() {
         if !parent.exists() {
@@ -256,7 +761,7 @@ pub fn init(source: impl AsRef<Path>) -> Result<Vec<{StructName}>, Box<dyn std::
         match iter {{
             Ok(data) => {{
                 let tmp: Vec<String> = data.into_iter().map(|s| s.trim().to_owned()).collect();
-                res.push({StructName}::from(tmp));
+                res.push({StructName}::try_from(tmp)?);
             }}
             Err(e) => {{
                 return Err(Box::new(e));
@@ -324,7 +829,7 @@ fn to_struct_name(file_name: &str) -> String {
 /// # Return
 /// Vector of strings which could be used as a struct property
 ///
-fn parse_headers(headers: Vec<String>) -> Vec<String> {
+pub(crate) fn parse_headers(headers: Vec<String>) -> Vec<String> {
     if headers.is_empty() {
         return Vec::default();
     }
@@ -353,7 +858,11 @@ fn parse_headers(headers: Vec<String>) -> Vec<String> {
 
 #[cfg(test)]
 mod test {
-    use super::{parse_col_type, parse_headers, DeserializationType};
+    use super::{
+        generate_struct, parse_col_type, parse_field, parse_headers, parse_optional_field,
+        parse_optional_string_field, parse_string_field, DeserializationType, ParseOptions,
+    };
+    use chrono::NaiveDate;
 
     #[test]
     fn test_is_same_type() {
@@ -362,13 +871,13 @@ mod test {
 
         assert!(e1.is_same_type(&e2));
 
-        let n1 = DeserializationType::NUMBER(1.2345);
-        let n2 = DeserializationType::NUMBER(3.345);
+        let n1 = DeserializationType::FLOAT(1.2345);
+        let n2 = DeserializationType::INTEGER(3);
 
         assert!(n1.is_same_type(&n2));
 
         let s1 = DeserializationType::STRING("1.2345".to_owned());
-        let n1 = DeserializationType::NUMBER(3.345);
+        let n1 = DeserializationType::FLOAT(3.345);
 
         assert!(!s1.is_same_type(&n1));
     }
@@ -410,12 +919,189 @@ mod test {
         assert_eq!(
             types,
             vec![
-                DeserializationType::NUMBER(3_f64),
-                DeserializationType::NUMBER(3.3),
+                DeserializationType::INTEGER(3),
+                DeserializationType::FLOAT(3.3),
                 DeserializationType::STRING("kek lol chebureck!".to_owned()),
                 DeserializationType::BOOLEAN(false),
                 DeserializationType::EMPTY
             ]
         )
     }
+
+    #[test]
+    fn test_number_parsing() {
+        let raw = vec![
+            "-3", "+2.0", "1e-5", "1,000", "-.5", "1,00", "1.", "158/88", "--",
+        ];
+        let types = raw
+            .into_iter()
+            .map(|s| parse_col_type(s).unwrap())
+            .collect::<Vec<DeserializationType>>();
+
+        assert!(matches!(types[0], DeserializationType::INTEGER(-3)));
+        assert!(matches!(types[1], DeserializationType::FLOAT(x) if x == 2_f64));
+        assert!(matches!(types[2], DeserializationType::FLOAT(x) if x == 1e-5));
+        assert!(matches!(types[3], DeserializationType::INTEGER(1000)));
+        assert!(matches!(types[4], DeserializationType::FLOAT(x) if x == -0.5));
+        for value in &types[5..] {
+            assert!(matches!(value, DeserializationType::STRING(_)));
+        }
+    }
+
+    #[test]
+    fn test_locale_parsing() {
+        let options = ParseOptions::comma_decimal();
+
+        assert_eq!(
+            options.parse("1.234,5").unwrap(),
+            DeserializationType::FLOAT(1234.5)
+        );
+        assert!(matches!(
+            options.parse("1.234").unwrap(),
+            DeserializationType::INTEGER(1234)
+        ));
+    }
+
+    #[test]
+    fn test_date_parsing() {
+        let date = NaiveDate::from_ymd_opt(2024, 2, 29).unwrap();
+
+        assert_eq!(
+            parse_col_type("2024-02-29").unwrap(),
+            DeserializationType::DATE(date)
+        );
+        assert_eq!(
+            parse_col_type("2024-02-29T10:30:00Z").unwrap(),
+            DeserializationType::DATETIME(date.and_hms_opt(10, 30, 0).unwrap())
+        );
+
+        let options = ParseOptions {
+            date_formats: vec!["%d/%m/%Y".to_owned()],
+            ..Default::default()
+        };
+        assert_eq!(
+            options.parse("29/02/2024").unwrap(),
+            DeserializationType::DATE(date)
+        );
+    }
+
+    #[test]
+    fn test_widen() {
+        let int = DeserializationType::INTEGER(1);
+        let float = DeserializationType::FLOAT(1.5);
+
+        assert_eq!(int.widen(&float), float);
+        assert_eq!(DeserializationType::EMPTY.widen(&int), int);
+        assert!(matches!(
+            int.widen(&DeserializationType::BOOLEAN(true)),
+            DeserializationType::STRING(_)
+        ));
+    }
+
+    #[test]
+    fn test_null_tokens() {
+        let mut options = ParseOptions::default();
+        options
+            .column_null_tokens
+            .insert("Cholesterol".to_owned(), vec!["0".to_owned()]);
+
+        for raw in ["NA", "N/A", "NaN", "null", "-", "?"] {
+            assert_eq!(parse_col_type(raw).unwrap(), DeserializationType::EMPTY);
+        }
+
+        assert_eq!(
+            options.parse_cell("Cholesterol", "0").unwrap(),
+            DeserializationType::EMPTY
+        );
+        assert_eq!(
+            options.parse_cell("Cholesterol", "?").unwrap(),
+            DeserializationType::STRING("?".to_owned())
+        );
+        assert_eq!(
+            options.parse_cell("Age", "0").unwrap(),
+            DeserializationType::INTEGER(0)
+        );
+    }
+
+    #[test]
+    fn test_fallible_arithmetic() {
+        use DeserializationType::{FLOAT, INTEGER, STRING};
+
+        assert_eq!(INTEGER(5).checked_sub(&FLOAT(1.5)), Some(3.5));
+        assert_eq!(
+            INTEGER(i64::MIN).checked_sub(&INTEGER(1)),
+            Some(i64::MIN as f64 - 1.)
+        );
+        assert_eq!(
+            FLOAT(f64::INFINITY).checked_sub(&FLOAT(f64::INFINITY)),
+            None
+        );
+        assert_eq!(STRING("a".into()).checked_sub(&INTEGER(1)), None);
+        assert!((STRING("a".into()) - INTEGER(1)).is_err());
+        assert_eq!((INTEGER(3) - INTEGER(1)).unwrap(), 2.);
+
+        assert_eq!(
+            crate::min!(INTEGER(3), FLOAT(f64::NAN), FLOAT(-1.)).unwrap(),
+            FLOAT(-1.)
+        );
+        assert_eq!(
+            crate::max!(FLOAT(f64::NAN), INTEGER(3)).unwrap(),
+            INTEGER(3)
+        );
+        assert_eq!(
+            crate::max!(INTEGER(3), FLOAT(f64::INFINITY)).unwrap(),
+            FLOAT(f64::INFINITY)
+        );
+        assert!(crate::min!(INTEGER(3), STRING("a".into())).is_err());
+        assert!(crate::min!(STRING("a".into())).is_err());
+
+        let mut values = [STRING("b".into()), FLOAT(f64::NAN), INTEGER(2), FLOAT(1.5)];
+        values.sort_by(|a, b| a.total_cmp(b));
+        assert_eq!(&values[..2], &[FLOAT(1.5), INTEGER(2)]);
+        assert_eq!(values[3], STRING("b".into()));
+    }
+
+    #[test]
+    fn test_generate_struct() {
+        let dist = std::env::temp_dir().join(format!("gaps_{}.rs", std::process::id()));
+        let module = generate_struct(std::path::Path::new("./tests/gaps.csv"), &dist).unwrap();
+        std::fs::remove_file(&dist).unwrap();
+
+        // Only the columns with gaps are optional
+        assert!(module.contains("\tid: i64,"));
+        assert!(module.contains("\tage: Option<i64>,"));
+        assert!(module.contains("\tscore: Option<f64>,"));
+        assert!(module.contains("\tgroup: Option<String>,"));
+        assert!(
+            module.contains("deserialization::parse_optional_field(&options, \"age\", &value[1])?")
+        );
+        assert!(!module.contains("expect("));
+
+        let options = ParseOptions::default();
+        assert_eq!(parse_field::<i64>(&options, "n", "1,000").unwrap(), 1000);
+        assert_eq!(
+            parse_optional_field::<f64>(&options, "n", "NA").unwrap(),
+            None
+        );
+
+        // String fields keep the cell as written
+        assert!(module.contains(
+            "deserialization::parse_optional_string_field(&options, \"group\", &value[3])?"
+        ));
+        for raw in ["0042", "1,000", "TRUE", "1.50", "2024-01-31T10:00:00Z"] {
+            assert_eq!(parse_string_field(&format!(" {raw} ")), raw);
+            assert_eq!(
+                parse_optional_string_field(&options, "s", raw).unwrap(),
+                Some(raw.to_owned())
+            );
+        }
+        assert_eq!(
+            parse_optional_string_field(&options, "s", " NA ").unwrap(),
+            None
+        );
+
+        let error = parse_field::<i64>(&options, "n", "NA").unwrap_err();
+        assert_eq!(error.location().column.as_deref(), Some("n"));
+        assert!(parse_field::<bool>(&options, "b", "1").is_err());
+    }
 }
@@ -0,0 +1,441 @@
+//! Detection of the csv dialect of a file, used by `CsvToolkit::open`.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use csv::{ReaderBuilder, Terminator};
+use regex::Regex;
+
+use crate::constants::{SNIFF_ROWS, SNIFF_SAMPLE_BYTES};
+use crate::error::{Error, Result};
+
+/// Field delimiters tried by `Dialect::sniff`, the first one wins a tie.
+const DELIMITERS: [u8; 5] = [b',', b';', b'\t', b'|', b':'];
+/// Quote characters tried by `Dialect::sniff`.
+const QUOTES: [u8; 2] = [b'"', b'\''];
+
+/// Character encoding of a csv file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextEncoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    /// ISO-8859-1, assumed for any file which is not valid UTF-8
+    Latin1,
+}
+
+/// Line terminator of a csv file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineTerminator {
+    /// `\r\n`
+    CrLf,
+    /// A single byte, usually `\n`
+    Any(u8),
+}
+
+impl LineTerminator {
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            LineTerminator::CrLf => b"\r\n",
+            LineTerminator::Any(b) => std::slice::from_ref(b),
+        }
+    }
+}
+
+impl From<LineTerminator> for Terminator {
+    fn from(terminator: LineTerminator) -> Self {
+        match terminator {
+            LineTerminator::CrLf => Terminator::CRLF,
+            LineTerminator::Any(b) => Terminator::Any(b),
+        }
+    }
+}
+
+/// How a csv file is written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dialect {
+    pub delimiter: u8,
+    pub quote: u8,
+    /// Quotes inside quoted fields are escaped by doubling them (`""`)
+    pub double_quotes: bool,
+    /// Quotes inside quoted fields are escaped by this character (e.g. `\"`)
+    pub escape: Option<u8>,
+    /// The first line is the header line
+    pub has_headers: bool,
+    pub terminator: LineTerminator,
+    pub encoding: TextEncoding,
+    /// The file starts with a byte order mark
+    pub bom: bool,
+}
+
+impl Default for Dialect {
+    fn default() -> Self {
+        Dialect {
+            delimiter: b',',
+            quote: b'"',
+            double_quotes: true,
+            escape: None,
+            has_headers: true,
+            terminator: LineTerminator::Any(b'\n'),
+            encoding: TextEncoding::Utf8,
+            bom: false,
+        }
+    }
+}
+
+impl Dialect {
+    /// Detect the dialect of a csv file from its first `SNIFF_SAMPLE_BYTES` bytes.
+    ///
+    /// Ambiguous samples fall back to the `Default` dialect, e.g. a file with a single column is
+    /// comma separated and a file with only text columns has a header line.
+    ///
+    /// # Errors
+    /// fs, io;
+    pub fn sniff(path: impl AsRef<Path>) -> Result<Self> {
+        let mut sample = Vec::with_capacity(SNIFF_SAMPLE_BYTES);
+        File::open(path.as_ref())?
+            .take(SNIFF_SAMPLE_BYTES as u64)
+            .read_to_end(&mut sample)?;
+
+        let (encoding, bom) = detect_encoding(&sample);
+        let mut text = decode(&sample[bom_len(encoding, bom)..], encoding);
+
+        // The last line of a truncated sample is incomplete
+        if sample.len() == SNIFF_SAMPLE_BYTES {
+            if let Some(end) = text.rfind('\n') {
+                text.truncate(end + 1);
+            }
+        }
+
+        let quote = detect_quote(&text);
+        let delimiter = detect_delimiter(&text, quote);
+        let escape_pattern = format!("\\\\{}", quote as char);
+        let double_pattern = format!("{0}{0}", quote as char);
+        let escape =
+            (text.contains(&escape_pattern) && !text.contains(&double_pattern)).then_some(b'\\');
+
+        let rows = sample_rows(&text, delimiter, quote, escape);
+
+        Ok(Dialect {
+            delimiter,
+            quote,
+            double_quotes: escape.is_none(),
+            escape,
+            has_headers: has_headers(&rows),
+            terminator: detect_terminator(&text),
+            encoding,
+            bom,
+        })
+    }
+
+    /// Whether the file must be converted to headed UTF-8 before the csv reader could read it.
+    pub(crate) fn needs_transcoding(&self) -> bool {
+        self.bom || self.encoding != TextEncoding::Utf8 || !self.has_headers
+    }
+
+    /// Copy `src` to `dst` as UTF-8, without the byte order mark and with generated headers
+    /// (`column_1`, `column_2`, ...) if the file has no header line.
+    ///
+    /// # Errors
+    /// fs, io, invalid UTF-16;
+    pub(crate) fn transcode(&self, src: &Path, dst: &Path) -> Result<()> {
+        let mut reader = BufReader::new(File::open(src)?);
+        let mut writer = BufWriter::new(File::create(dst)?);
+
+        let mut skip = vec![0; bom_len(self.encoding, self.bom)];
+        reader.read_exact(&mut skip)?;
+
+        if !self.has_headers {
+            let mut head = Vec::with_capacity(SNIFF_SAMPLE_BYTES);
+            File::open(src)?
+                .take(SNIFF_SAMPLE_BYTES as u64)
+                .read_to_end(&mut head)?;
+            let text = decode(&head[skip.len()..], self.encoding);
+
+            let columns = sample_rows(&text, self.delimiter, self.quote, self.escape)
+                .iter()
+                .map(Vec::len)
+                .max()
+                .unwrap_or(1);
+            let headers: Vec<String> = (1..=columns).map(|i| format!("column_{i}")).collect();
+            writer.write_all(
+                headers
+                    .join(&(self.delimiter as char).to_string())
+                    .as_bytes(),
+            )?;
+            writer.write_all(self.terminator.as_bytes())?;
+        }
+
+        let mut chunk = vec![0; SNIFF_SAMPLE_BYTES];
+        // Odd byte of UTF-16 and unpaired high surrogate left over from the previous chunk
+        let mut pending_byte: Option<u8> = None;
+        let mut pending_unit: Option<u16> = None;
+
+        loop {
+            let n = reader.read(&mut chunk)?;
+            if n == 0 {
+                break;
+            }
+
+            match self.encoding {
+                TextEncoding::Utf8 => writer.write_all(&chunk[..n])?,
+                TextEncoding::Latin1 => {
+                    let text: String = chunk[..n].iter().map(|b| *b as char).collect();
+                    writer.write_all(text.as_bytes())?;
+                }
+                TextEncoding::Utf16Le | TextEncoding::Utf16Be => {
+                    let bytes: Vec<u8> = pending_byte
+                        .take()
+                        .into_iter()
+                        .chain(chunk[..n].iter().copied())
+                        .collect();
+                    let mut units: Vec<u16> = pending_unit.take().into_iter().collect();
+                    for pair in bytes.chunks(2) {
+                        match pair {
+                            [a, b] => units.push(utf16_unit(self.encoding, *a, *b)),
+                            [a] => pending_byte = Some(*a),
+                            _ => unreachable!(),
+                        }
+                    }
+                    if units.last().is_some_and(|u| (0xD800..0xDC00).contains(u)) {
+                        pending_unit = units.pop();
+                    }
+
+                    let text = char::decode_utf16(units)
+                        .collect::<std::result::Result<String, _>>()
+                        .map_err(|e| {
+                            Error::parse(format!("Invalid UTF-16 data: {e}")).in_file(src)
+                        })?;
+                    writer.write_all(text.as_bytes())?;
+                }
+            }
+        }
+
+        if pending_byte.is_some() || pending_unit.is_some() {
+            return Err(Error::parse("Truncated UTF-16 data").in_file(src));
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+fn detect_encoding(sample: &[u8]) -> (TextEncoding, bool) {
+    match sample {
+        [0xEF, 0xBB, 0xBF, ..] => return (TextEncoding::Utf8, true),
+        [0xFF, 0xFE, ..] => return (TextEncoding::Utf16Le, true),
+        [0xFE, 0xFF, ..] => return (TextEncoding::Utf16Be, true),
+        _ => {}
+    }
+
+    // UTF-16 without BOM: ASCII characters have a zero high byte
+    let zeros = |parity: usize| {
+        sample
+            .iter()
+            .skip(parity)
+            .step_by(2)
+            .filter(|b| **b == 0)
+            .count()
+    };
+    let half = sample.len() / 2;
+    if half > 0 && zeros(1) * 2 > half {
+        return (TextEncoding::Utf16Le, false);
+    }
+    if half > 0 && zeros(0) * 2 > half {
+        return (TextEncoding::Utf16Be, false);
+    }
+
+    match std::str::from_utf8(sample) {
+        Ok(_) => (TextEncoding::Utf8, false),
+        // A multi-byte character cut by the end of the sample
+        Err(e) if e.error_len().is_none() => (TextEncoding::Utf8, false),
+        Err(_) => (TextEncoding::Latin1, false),
+    }
+}
+
+fn bom_len(encoding: TextEncoding, bom: bool) -> usize {
+    match (encoding, bom) {
+        (_, false) => 0,
+        (TextEncoding::Utf8, true) => 3,
+        (_, true) => 2,
+    }
+}
+
+fn utf16_unit(encoding: TextEncoding, a: u8, b: u8) -> u16 {
+    match encoding {
+        TextEncoding::Utf16Be => u16::from_be_bytes([a, b]),
+        _ => u16::from_le_bytes([a, b]),
+    }
+}
+
+fn decode(bytes: &[u8], encoding: TextEncoding) -> String {
+    match encoding {
+        TextEncoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+        TextEncoding::Latin1 => bytes.iter().map(|b| *b as char).collect(),
+        TextEncoding::Utf16Le | TextEncoding::Utf16Be => {
+            let units = bytes
+                .chunks_exact(2)
+                .map(|pair| utf16_unit(encoding, pair[0], pair[1]));
+            char::decode_utf16(units)
+                .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                .collect()
+        }
+    }
+}
+
+fn detect_terminator(text: &str) -> LineTerminator {
+    match text.find(['\r', '\n']) {
+        Some(i) if text[i..].starts_with("\r\n") => LineTerminator::CrLf,
+        Some(i) if text[i..].starts_with('\r') => LineTerminator::Any(b'\r'),
+        _ => LineTerminator::Any(b'\n'),
+    }
+}
+
+/// The quote character which encloses the most fields.
+fn detect_quote(text: &str) -> u8 {
+    let separators = regex::escape(&String::from_utf8_lossy(&DELIMITERS));
+
+    QUOTES
+        .into_iter()
+        .map(|quote| {
+            let q = regex::escape(&(quote as char).to_string());
+            let pattern = format!(
+                r"(?m)(?:^|[{separators}])[ ]*{q}[^{q}\r\n]*{q}[ ]*(?:\r?$|[{separators}])"
+            );
+            let count = Regex::new(&pattern)
+                .map(|re| re.find_iter(text).count())
+                .unwrap_or_default();
+            (quote, count)
+        })
+        .fold((b'"', 0), |best, (quote, count)| {
+            if count > best.1 {
+                (quote, count)
+            } else {
+                best
+            }
+        })
+        .0
+}
+
+/// The delimiter which gives the most consistent number of fields (more than one) per row.
+fn detect_delimiter(text: &str, quote: u8) -> u8 {
+    let mut best = (b',', 0., 0);
+
+    for delimiter in DELIMITERS {
+        let counts: Vec<usize> = sample_rows(text, delimiter, quote, None)
+            .iter()
+            .map(Vec::len)
+            .collect();
+        if counts.is_empty() {
+            continue;
+        }
+
+        let mut frequencies = std::collections::BTreeMap::new();
+        for count in &counts {
+            *frequencies.entry(*count).or_insert(0_usize) += 1;
+        }
+        let (mode, frequency) = frequencies
+            .into_iter()
+            .max_by_key(|(count, frequency)| (*frequency, *count))
+            .unwrap_or_default();
+        if mode < 2 {
+            continue;
+        }
+
+        let consistency = frequency as f64 / counts.len() as f64;
+        if consistency > best.1 || (consistency == best.1 && mode > best.2) {
+            best = (delimiter, consistency, mode);
+        }
+    }
+
+    best.0
+}
+
+fn sample_rows(text: &str, delimiter: u8, quote: u8, escape: Option<u8>) -> Vec<Vec<String>> {
+    ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .delimiter(delimiter)
+        .quote(quote)
+        .escape(escape)
+        .double_quote(escape.is_none())
+        .from_reader(text.as_bytes())
+        .records()
+        .take(SNIFF_ROWS)
+        .map_while(|record| record.ok())
+        .map(|record| record.iter().map(|s| s.to_owned()).collect())
+        .collect()
+}
+
+/// Vote per column: a first value which does not look like the values below it is a header.
+fn has_headers(rows: &[Vec<String>]) -> bool {
+    let Some((first, rest)) = rows.split_first() else {
+        return true;
+    };
+    if rest.is_empty() {
+        return true;
+    }
+
+    let is_number = |s: &str| !s.is_empty() && s.replace(',', ".").parse::<f64>().is_ok();
+    let mut votes = 0_i64;
+
+    for (col_id, head) in first.iter().enumerate() {
+        let values: Vec<&str> = rest
+            .iter()
+            .filter_map(|row| row.get(col_id).map(|s| s.as_str()))
+            .filter(|s| !s.is_empty())
+            .collect();
+        if values.is_empty() {
+            continue;
+        }
+
+        if head.is_empty() {
+            votes -= 1;
+        } else if values.iter().all(|v| is_number(v)) {
+            votes += if is_number(head) { -1 } else { 1 };
+        } else if values.contains(&head.as_str()) {
+            votes -= 1;
+        }
+    }
+
+    votes >= 0
+}
+
+#[cfg(test)]
+mod test {
+    use super::{detect_delimiter, detect_encoding, has_headers, sample_rows, TextEncoding};
+
+    #[test]
+    fn test_detect_delimiter() {
+        let text = "id;city;note\n1;\"Cape Town, SA\";x\n2;Kyiv;y\n";
+        assert_eq!(detect_delimiter(text, b'"'), b';');
+
+        let text = "a|b\n1|2\n";
+        assert_eq!(detect_delimiter(text, b'"'), b'|');
+        assert_eq!(detect_delimiter("single\nvalue\n", b'"'), b',');
+    }
+
+    #[test]
+    fn test_detect_encoding() {
+        assert_eq!(
+            detect_encoding(b"\xEF\xBB\xBFa,b"),
+            (TextEncoding::Utf8, true)
+        );
+        assert_eq!(
+            detect_encoding(b"a\0,\0b\0"),
+            (TextEncoding::Utf16Le, false)
+        );
+        assert_eq!(detect_encoding(b"caf\xE9,1"), (TextEncoding::Latin1, false));
+    }
+
+    #[test]
+    fn test_has_headers() {
+        let rows = sample_rows("1,2\n3,4\n", b',', b'"', None);
+        assert!(!has_headers(&rows));
+
+        let rows = sample_rows("age,name\n3,x\n", b',', b'"', None);
+        assert!(has_headers(&rows));
+    }
+}
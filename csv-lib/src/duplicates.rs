@@ -0,0 +1,181 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+
+use csv::{Position, Reader, StringRecord};
+
+use crate::error::Result;
+
+/// Which row of a group of duplicates `CsvToolkit::deduplicate` keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeepPolicy {
+    First,
+    Last,
+    /// A single row at the place of the first one, every gap filled with the first non empty value
+    /// of the column among the duplicates.
+    Merge,
+}
+
+/// A row which repeats an earlier one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Duplicate {
+    /// Index of the data row
+    pub row: usize,
+    /// Index of the first occurrence
+    pub first: usize,
+}
+
+/// Rows with the same key but different values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub key: String,
+    /// The first occurrence and every row which differs from it
+    pub rows: Vec<usize>,
+    /// Headers of the columns whose values differ
+    pub columns: Vec<String>,
+}
+
+/// Result of `CsvToolkit::find_duplicates`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DuplicateReport {
+    /// Exact copies of earlier rows
+    pub rows: Vec<Duplicate>,
+    /// Rows whose key was seen before (empty without a key column)
+    pub keys: Vec<Duplicate>,
+    pub conflicts: Vec<Conflict>,
+}
+
+/// 64 bit hash of the cells of a row. `DefaultHasher` is not stable across Rust releases, hashes
+/// only live as long as a single operation.
+pub(crate) fn hash_cells<'a>(cells: impl Iterator<Item = &'a str>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for cell in cells {
+        cell.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Whether two rows have the same cells, regardless of the padding of the cells.
+pub(crate) fn same_cells(a: &StringRecord, b: &StringRecord) -> bool {
+    a.iter().map(str::trim).eq(b.iter().map(str::trim))
+}
+
+/// Groups of rows with the same cells (the whole row or the key column).
+///
+/// Rows are looked up by hash, and a match is confirmed by comparing the cells with the first row
+/// of the group, read back from the data file by its position. Memory does not depend on the row
+/// width and a hash collision never merges distinct rows.
+pub(crate) struct RowGroups {
+    reader: Reader<File>,
+    key_id: Option<usize>,
+    /// First row and its position, for every group with the same hash
+    groups: HashMap<u64, Vec<(usize, Position)>>,
+}
+
+impl RowGroups {
+    /// # Arguments
+    ///
+    /// * `reader` - Reader of the data file, used to read back the first rows of the groups.
+    /// * `key_id` - Column compared instead of the whole row.
+    pub(crate) fn new(reader: Reader<File>, key_id: Option<usize>) -> Self {
+        RowGroups {
+            reader,
+            key_id,
+            groups: HashMap::new(),
+        }
+    }
+
+    /// Add a row read from the data file, starting a new group unless it matches an earlier one.
+    ///
+    /// # Return
+    /// Index and cells of the first row of the group, `None` if the row starts a new group.
+    ///
+    /// # Errors
+    /// csv, io;
+    pub(crate) fn insert(
+        &mut self,
+        row_id: usize,
+        record: &StringRecord,
+    ) -> Result<Option<(usize, StringRecord)>> {
+        if let Some(first) = self.find(record)? {
+            return Ok(Some(first));
+        }
+
+        let position = record.position().cloned().unwrap_or_else(Position::new);
+        self.groups
+            .entry(self.hash(record))
+            .or_default()
+            .push((row_id, position));
+
+        Ok(None)
+    }
+
+    /// Index and cells of the first row of the group `record` belongs to.
+    ///
+    /// # Errors
+    /// csv, io;
+    fn find(&mut self, record: &StringRecord) -> Result<Option<(usize, StringRecord)>> {
+        let Some(candidates) = self.groups.get(&self.hash(record)) else {
+            return Ok(None);
+        };
+
+        let mut first = StringRecord::new();
+        for (row_id, position) in candidates {
+            self.reader.seek(position.clone())?;
+            self.reader.read_record(&mut first)?;
+
+            let same = match self.key_id {
+                Some(key_id) => {
+                    first.get(key_id).map(str::trim) == record.get(key_id).map(str::trim)
+                }
+                None => same_cells(&first, record),
+            };
+            if same {
+                return Ok(Some((*row_id, first)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn hash(&self, record: &StringRecord) -> u64 {
+        match self.key_id {
+            Some(key_id) => hash_cells(record.get(key_id).map(str::trim).into_iter()),
+            None => hash_cells(record.iter().map(str::trim)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{hash_cells, RowGroups};
+
+    #[test]
+    fn test_hash_cells() {
+        let hash = |cells: &[&str]| hash_cells(cells.iter().copied());
+
+        assert_eq!(hash(&["a", "b"]), hash(&["a", "b"]));
+        // Cell boundaries are a part of the hash
+        assert_ne!(hash(&["ab", ""]), hash(&["a", "b"]));
+    }
+
+    #[test]
+    fn test_hash_collision() {
+        let mut reader = csv::Reader::from_path("./tests/duplicates.csv").unwrap();
+        let rows: Vec<csv::StringRecord> = reader.records().map(|r| r.unwrap()).collect();
+        let mut groups = RowGroups::new(
+            csv::Reader::from_path("./tests/duplicates.csv").unwrap(),
+            None,
+        );
+
+        assert!(groups.insert(0, &rows[0]).unwrap().is_none());
+        assert_eq!(groups.insert(2, &rows[2]).unwrap().unwrap().0, 0);
+
+        // A distinct row with the hash of the first one starts its own group
+        let first = groups.groups.remove(&groups.hash(&rows[0])).unwrap();
+        groups.groups.insert(groups.hash(&rows[1]), first);
+        assert!(groups.insert(1, &rows[1]).unwrap().is_none());
+        assert_eq!(groups.find(&rows[1]).unwrap().unwrap().0, 1);
+    }
+}
@@ -0,0 +1,384 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::deserialization::parse_headers;
+use crate::error::{Error, Result};
+
+/// Encoding of a categorical column requested from `CsvToolkit::encoding`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EncodingMethod {
+    /// A `0`/`1` column per category.
+    OneHot {
+        /// Skip the column of the first category (alphabetical order) to avoid collinearity
+        drop_first: bool,
+        /// Categories rarer than this proportion share a single "other" column
+        min_frequency: Option<f64>,
+    },
+    /// Integer code of the category: its index in `order`, or in alphabetical order if `None`.
+    Ordinal { order: Option<Vec<String>> },
+    /// Proportion of the category in the column.
+    Frequency,
+    /// Mean of the numeric `label` column over the rows of the category, smoothed towards the
+    /// global mean: `(n * mean + smoothing * prior) / (n + smoothing)`.
+    Target { label: String, smoothing: f64 },
+}
+
+/// Fitted encoding of a single column. Serializable, so it could be reused with another dataset.
+///
+/// Categories unseen while fitting are encoded into the "other" column (if any) by `OneHot`,
+/// into a gap by `Ordinal`, into `0` by `Frequency` and into the global mean by `Target`.
+/// Gaps stay gaps.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum Encoding {
+    OneHot {
+        /// Category and header of its column, in the order of the columns
+        columns: Vec<(String, String)>,
+        /// Header of the column of rare and unseen categories
+        other: Option<String>,
+    },
+    Ordinal {
+        codes: BTreeMap<String, usize>,
+    },
+    Frequency {
+        frequencies: BTreeMap<String, f64>,
+    },
+    Target {
+        label: String,
+        means: BTreeMap<String, f64>,
+        prior: f64,
+    },
+}
+
+/// Category counts (and label sums for target encoding) of one column.
+#[derive(Debug, Default)]
+pub(crate) struct LevelAccumulator {
+    counts: HashMap<String, usize>,
+    label_sums: HashMap<String, f64>,
+    label_counts: HashMap<String, usize>,
+}
+
+impl LevelAccumulator {
+    /// Register a non empty category and the label value of the row, if needed.
+    pub fn push(&mut self, category: &str, label: Option<f64>) {
+        *self.counts.entry(category.to_owned()).or_default() += 1;
+
+        if let Some(y) = label {
+            *self.label_sums.entry(category.to_owned()).or_default() += y;
+            *self.label_counts.entry(category.to_owned()).or_default() += 1;
+        }
+    }
+}
+
+/// Unique header for a generated column.
+///
+/// Generated headers are already in the form produced by `parse_headers`, so they stay the same in
+/// a struct generated by `generate_struct`.
+fn generated_header(column: &str, suffix: &str, taken: &mut HashSet<String>) -> String {
+    let base = parse_headers(vec![format!("{column} {suffix}")]).remove(0);
+
+    let mut header = base.clone();
+    let mut n = 2;
+    while taken.contains(&header) {
+        header = format!("{base}_{n}");
+        n += 1;
+    }
+
+    taken.insert(header.clone());
+    header
+}
+
+impl EncodingMethod {
+    /// Fit the encoding of the `column`.
+    ///
+    /// # Arguments
+    ///
+    /// * `column` - Header of the encoded column.
+    /// * `levels` - Categories of the column (see `LevelAccumulator`).
+    /// * `taken` - Headers which new columns should not collide with. Updated with the new ones.
+    ///
+    /// # Errors
+    /// A category missing from the explicit ordinal `order`.
+    pub(crate) fn fit(
+        &self,
+        column: &str,
+        levels: &LevelAccumulator,
+        taken: &mut HashSet<String>,
+    ) -> Result<Encoding> {
+        let total: usize = levels.counts.values().sum();
+        let mut categories: Vec<&String> = levels.counts.keys().collect();
+        categories.sort();
+
+        let encoding = match self {
+            EncodingMethod::OneHot {
+                drop_first,
+                min_frequency,
+            } => {
+                let is_rare = |category: &String| {
+                    min_frequency
+                        .is_some_and(|min| (levels.counts[category] as f64) < min * total as f64)
+                };
+
+                let has_rare = categories.iter().any(|category| is_rare(category));
+                let columns = categories
+                    .iter()
+                    .filter(|category| !is_rare(category))
+                    .skip(usize::from(*drop_first))
+                    .map(|category| {
+                        (
+                            category.to_string(),
+                            generated_header(column, category, taken),
+                        )
+                    })
+                    .collect();
+
+                Encoding::OneHot {
+                    columns,
+                    other: has_rare.then(|| generated_header(column, "other", taken)),
+                }
+            }
+            EncodingMethod::Ordinal { order } => {
+                let order: Vec<&String> = match order {
+                    Some(order) => {
+                        if let Some(category) = categories.iter().find(|c| !order.contains(c)) {
+                            return Err(Error::invalid_argument(
+                                "Category is missing from the ordinal order",
+                            )
+                            .in_column(column)
+                            .with_value(category.as_str()));
+                        }
+                        order.iter().collect()
+                    }
+                    None => categories,
+                };
+
+                Encoding::Ordinal {
+                    codes: order
+                        .into_iter()
+                        .enumerate()
+                        .map(|(code, category)| (category.clone(), code))
+                        .collect(),
+                }
+            }
+            EncodingMethod::Frequency => Encoding::Frequency {
+                frequencies: categories
+                    .into_iter()
+                    .map(|category| {
+                        (
+                            category.clone(),
+                            levels.counts[category] as f64 / total as f64,
+                        )
+                    })
+                    .collect(),
+            },
+            EncodingMethod::Target { label, smoothing } => {
+                let n: usize = levels.label_counts.values().sum();
+                let prior = match n {
+                    0 => 0.,
+                    n => levels.label_sums.values().sum::<f64>() / n as f64,
+                };
+
+                let means = levels
+                    .label_counts
+                    .iter()
+                    .map(|(category, count)| {
+                        let sum = levels.label_sums[category];
+                        let mean = (sum + smoothing * prior) / (*count as f64 + smoothing);
+                        (category.clone(), mean)
+                    })
+                    .collect();
+
+                Encoding::Target {
+                    label: label.clone(),
+                    means,
+                    prior,
+                }
+            }
+        };
+
+        Ok(encoding)
+    }
+}
+
+impl Encoding {
+    /// Headers which replace the encoded `column`.
+    pub fn headers(&self, column: &str) -> Vec<String> {
+        match self {
+            Encoding::OneHot { columns, other } => columns
+                .iter()
+                .map(|(_, header)| header.clone())
+                .chain(other.clone())
+                .collect(),
+            _ => vec![column.to_owned()],
+        }
+    }
+
+    /// Cells which replace a value of the column (one per header, see `headers`).
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - Raw category, `None` for a gap.
+    ///
+    pub fn encode(&self, value: Option<&str>) -> Vec<String> {
+        match (self, value) {
+            (Encoding::OneHot { columns, other }, None) => {
+                vec![String::default(); columns.len() + usize::from(other.is_some())]
+            }
+            (Encoding::OneHot { columns, other }, Some(value)) => {
+                let known = columns.iter().any(|(category, _)| category == value);
+                columns
+                    .iter()
+                    .map(|(category, _)| category == value)
+                    .chain(other.as_ref().map(|_| !known))
+                    .map(|hot| if hot { "1" } else { "0" }.to_owned())
+                    .collect()
+            }
+            (_, None) => vec![String::default()],
+            (Encoding::Ordinal { codes }, Some(value)) => vec![codes
+                .get(value)
+                .map(|code| code.to_string())
+                .unwrap_or_default()],
+            (Encoding::Frequency { frequencies }, Some(value)) => {
+                vec![frequencies.get(value).copied().unwrap_or(0.).to_string()]
+            }
+            (Encoding::Target { means, prior, .. }, Some(value)) => {
+                vec![means.get(value).unwrap_or(prior).to_string()]
+            }
+        }
+    }
+}
+
+/// Fitted encodings of several columns (see `CsvToolkit::encoding`), stored between runs
+/// (`CsvToolkit::apply_encoding` to encode another dataset the same way).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Encoder {
+    pub columns: BTreeMap<String, Encoding>,
+}
+
+impl Encoder {
+    pub fn new(columns: BTreeMap<String, Encoding>) -> Self {
+        Encoder { columns }
+    }
+
+    pub fn get(&self, column: &str) -> Option<&Encoding> {
+        self.columns.get(column)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Save the encoder as a JSON file.
+    ///
+    /// # Errors
+    /// fs, io, serialization;
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        if let Some(parent) = path.as_ref().parent() {
+            if !parent.as_os_str().is_empty() && !parent.exists() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+
+        fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+
+    /// Load an encoder saved by `Encoder::save`.
+    ///
+    /// # Errors
+    /// fs, io, deserialization;
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use super::{Encoder, Encoding, EncodingMethod, LevelAccumulator};
+    use crate::deserialization::parse_headers;
+
+    fn levels(rows: &[(&str, f64)]) -> LevelAccumulator {
+        let mut levels = LevelAccumulator::default();
+        for (category, label) in rows {
+            levels.push(category, Some(*label));
+        }
+        levels
+    }
+
+    #[test]
+    fn test_one_hot() {
+        let levels = levels(&[("b", 0.), ("a", 0.), ("b", 0.), ("c", 0.), ("b", 0.)]);
+        let mut taken = HashSet::from(["diet_b".to_owned()]);
+
+        let method = EncodingMethod::OneHot {
+            drop_first: false,
+            min_frequency: Some(0.25),
+        };
+        let encoding = method.fit("Diet", &levels, &mut taken).unwrap();
+        let headers = encoding.headers("Diet");
+
+        // `a` and `c` are rare, `b` collides with an existing header
+        assert_eq!(headers, vec!["diet_b_2", "diet_other"]);
+        assert_eq!(parse_headers(headers.clone()), headers);
+        assert_eq!(encoding.encode(Some("b")), vec!["1", "0"]);
+        assert_eq!(encoding.encode(Some("unseen")), vec!["0", "1"]);
+        assert_eq!(encoding.encode(None), vec!["", ""]);
+
+        let method = EncodingMethod::OneHot {
+            drop_first: true,
+            min_frequency: None,
+        };
+        let encoding = method.fit("Diet", &levels, &mut HashSet::new()).unwrap();
+        assert_eq!(encoding.headers("Diet"), vec!["diet_b", "diet_c"]);
+        assert_eq!(encoding.encode(Some("a")), vec!["0", "0"]);
+    }
+
+    #[test]
+    fn test_ordinal_frequency_target() {
+        let levels = levels(&[("low", 1.), ("high", 3.), ("low", 2.), ("high", 6.)]);
+        let mut taken = HashSet::new();
+
+        let ordinal = EncodingMethod::Ordinal {
+            order: Some(vec!["low".to_owned(), "high".to_owned()]),
+        };
+        let encoding = ordinal.fit("level", &levels, &mut taken).unwrap();
+        assert_eq!(encoding.encode(Some("high")), vec!["1"]);
+        assert_eq!(encoding.encode(Some("mid")), vec![""]);
+
+        let missing = EncodingMethod::Ordinal {
+            order: Some(vec!["low".to_owned()]),
+        };
+        assert!(missing.fit("level", &levels, &mut taken).is_err());
+
+        let encoding = EncodingMethod::Frequency
+            .fit("level", &levels, &mut taken)
+            .unwrap();
+        assert_eq!(encoding.encode(Some("low")), vec!["0.5"]);
+
+        let target = EncodingMethod::Target {
+            label: "y".to_owned(),
+            smoothing: 2.,
+        };
+        let encoding = target.fit("level", &levels, &mut taken).unwrap();
+        // prior = 3, low: (3 + 2 * 3) / (2 + 2)
+        assert_eq!(encoding.encode(Some("low")), vec!["2.25"]);
+        assert_eq!(encoding.encode(Some("mid")), vec!["3"]);
+
+        let encoder = Encoder::new([("level".to_owned(), encoding)].into());
+        let json = encoder.to_json().unwrap();
+        assert_eq!(Encoder::from_json(&json).unwrap(), encoder);
+        assert!(matches!(
+            encoder.get("level"),
+            Some(Encoding::Target { .. })
+        ));
+    }
+}
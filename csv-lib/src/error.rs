@@ -1,23 +1,266 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
 pub type Result<T> = core::result::Result<T, Error>;
-pub type Error = Box<dyn std::error::Error>;
 
-#[derive(Debug)]
-pub struct CustomError {
-    details: String,
+/// Where an error occurred. Fields are filled when known, e.g. a UI can highlight the cell of an
+/// error with both `row` and `column`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Location {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+    /// Index of the data row (the header line is not counted)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub row: Option<usize>,
+    /// Header of the column
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub column: Option<String>,
+    /// Offending raw value
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
 }
 
-impl CustomError {
-    pub fn new(value: &str) -> Self {
-        CustomError {
-            details: value.to_owned(),
+/// Error of any toolkit operation. Serialized with a `kind` tag and flattened `Location` (boxed to
+/// keep `Result` small):
+///
+/// ```json
+/// {"kind": "TypeMismatch", "row": 3, "column": "Age", "value": "old", "expected": "number", "found": "STRING"}
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum Error {
+    /// A value does not have the type an operation needs, e.g. a word in a numeric column
+    TypeMismatch {
+        #[serde(flatten)]
+        location: Box<Location>,
+        expected: String,
+        found: String,
+    },
+    /// Malformed input: csv record, expression, schema or encoder file, text encoding
+    ParseError {
+        #[serde(flatten)]
+        location: Box<Location>,
+        message: String,
+    },
+    Io {
+        #[serde(flatten)]
+        location: Box<Location>,
+        message: String,
+    },
+    /// A column does not exist or could not be used by an operation
+    InvalidColumn {
+        #[serde(flatten)]
+        location: Box<Location>,
+        message: String,
+    },
+    /// An argument is out of its domain, e.g. split ratios or the number of folds
+    InvalidArgument {
+        #[serde(flatten)]
+        location: Box<Location>,
+        message: String,
+    },
+}
+
+impl Error {
+    pub fn type_mismatch(expected: impl Into<String>, found: impl Into<String>) -> Self {
+        Error::TypeMismatch {
+            location: Box::default(),
+            expected: expected.into(),
+            found: found.into(),
         }
     }
+
+    pub fn parse(message: impl Into<String>) -> Self {
+        Error::ParseError {
+            location: Box::default(),
+            message: message.into(),
+        }
+    }
+
+    pub fn io(message: impl Into<String>) -> Self {
+        Error::Io {
+            location: Box::default(),
+            message: message.into(),
+        }
+    }
+
+    /// Error of the `column` (header), e.g. a missing one.
+    pub fn invalid_column(column: impl Into<String>, message: impl Into<String>) -> Self {
+        Error::InvalidColumn {
+            location: Box::default(),
+            message: message.into(),
+        }
+        .in_column(column)
+    }
+
+    pub fn invalid_argument(message: impl Into<String>) -> Self {
+        Error::InvalidArgument {
+            location: Box::default(),
+            message: message.into(),
+        }
+    }
+
+    pub fn location(&self) -> &Location {
+        match self {
+            Error::TypeMismatch { location, .. }
+            | Error::ParseError { location, .. }
+            | Error::Io { location, .. }
+            | Error::InvalidColumn { location, .. }
+            | Error::InvalidArgument { location, .. } => location,
+        }
+    }
+
+    fn location_mut(&mut self) -> &mut Location {
+        match self {
+            Error::TypeMismatch { location, .. }
+            | Error::ParseError { location, .. }
+            | Error::Io { location, .. }
+            | Error::InvalidColumn { location, .. }
+            | Error::InvalidArgument { location, .. } => location,
+        }
+    }
+
+    /// Set the file of the error, unless it is already known.
+    pub fn in_file(mut self, file: impl AsRef<Path>) -> Self {
+        let location = self.location_mut();
+        location
+            .file
+            .get_or_insert_with(|| file.as_ref().to_path_buf());
+        self
+    }
+
+    /// Set the data row of the error, unless it is already known.
+    pub fn at_row(mut self, row: usize) -> Self {
+        self.location_mut().row.get_or_insert(row);
+        self
+    }
+
+    /// Replace the data row of the error, e.g. a row counted from the start of a chunk.
+    pub(crate) fn relocate_row(mut self, row: usize) -> Self {
+        self.location_mut().row = Some(row);
+        self
+    }
+
+    /// Set the column (header) of the error, unless it is already known.
+    pub fn in_column(mut self, column: impl Into<String>) -> Self {
+        let location = self.location_mut();
+        location.column.get_or_insert_with(|| column.into());
+        self
+    }
+
+    /// Set the offending value of the error, unless it is already known.
+    pub fn with_value(mut self, value: impl Into<String>) -> Self {
+        let location = self.location_mut();
+        location.value.get_or_insert_with(|| value.into());
+        self
+    }
 }
 
-impl std::fmt::Display for CustomError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.details)
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(file) = &self.file {
+            parts.push(format!("file '{}'", file.display()));
+        }
+        if let Some(row) = self.row {
+            parts.push(format!("row {row}"));
+        }
+        if let Some(column) = &self.column {
+            parts.push(format!("column '{column}'"));
+        }
+        if let Some(value) = &self.value {
+            parts.push(format!("value '{value}'"));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::TypeMismatch {
+                expected, found, ..
+            } => write!(f, "Expected {expected}, found {found}")?,
+            Error::ParseError { message, .. }
+            | Error::Io { message, .. }
+            | Error::InvalidColumn { message, .. }
+            | Error::InvalidArgument { message, .. } => write!(f, "{message}")?,
+        }
+
+        let location = self.location();
+        if *location != Location::default() {
+            write!(f, " ({location})")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::io(e.to_string())
+    }
+}
+
+impl From<csv::Error> for Error {
+    fn from(e: csv::Error) -> Self {
+        // Record 0 is the header line
+        let row = e
+            .position()
+            .and_then(|pos| (pos.record() as usize).checked_sub(1));
+        let error = match e.kind() {
+            csv::ErrorKind::Io(e) => Error::io(e.to_string()),
+            _ => Error::parse(e.to_string()),
+        };
+
+        match row {
+            Some(row) => error.at_row(row),
+            None => error,
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::parse(e.to_string())
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(e: toml::de::Error) -> Self {
+        Error::parse(e.to_string())
     }
 }
 
-impl std::error::Error for CustomError {}
+impl From<std::str::ParseBoolError> for Error {
+    fn from(e: std::str::ParseBoolError) -> Self {
+        Error::parse(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Error;
+
+    #[test]
+    fn test_error_serialization() {
+        let error = Error::type_mismatch("number", "STRING")
+            .at_row(3)
+            .in_column("Age")
+            .with_value("old");
+
+        assert_eq!(
+            error.to_string(),
+            "Expected number, found STRING (row 3, column 'Age', value 'old')"
+        );
+
+        let json = serde_json::to_value(&error).unwrap();
+        assert_eq!(json["kind"], "TypeMismatch");
+        assert_eq!(json["row"], 3);
+        assert_eq!(json["column"], "Age");
+        assert_eq!(serde_json::from_value::<Error>(json).unwrap(), error);
+    }
+}
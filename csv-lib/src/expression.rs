@@ -0,0 +1,774 @@
+use crate::deserialization::DeserializationType;
+use crate::error::{Error, Result};
+
+/// Compiled `UserInput::EXPR` expression.
+///
+/// Grammar (from the lowest precedence to the highest):
+///
+/// * `a || b`
+/// * `a && b`
+/// * `a == b`, `a != b`, `a < b`, `a <= b`, `a > b`, `a >= b`
+/// * `a + b`, `a - b` (`+` concatenates if one of operands is a string)
+/// * `a * b`, `a / b`, `a % b`
+/// * `-a`, `!a`
+/// * literals (`1.5`, `"text"`, `true`), column references and function calls
+///
+/// Columns are referenced by their header name: `Age`, or `[Heart Attack Risk]` when the name is not a
+/// plain identifier. Functions: `if(cond, then, else)`, `abs`, `round`, `floor`, `ceil`, `sqrt`, `ln`,
+/// `min`, `max`, `len`, `lower`, `upper`, `trim`, `concat`, `contains`, `substr`, `replace`,
+/// `is_empty`, `coalesce`.
+///
+/// An `EMPTY` operand of an arithmetic operation or a function (except `if`, `is_empty` and
+/// `coalesce`) makes the result `EMPTY` as well.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Literal(DeserializationType),
+    Column(usize),
+    Unary(UnaryOp, Box<Expression>),
+    Binary(BinaryOp, Box<Expression>, Box<Expression>),
+    Call(Function, Vec<Expression>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Function {
+    If,
+    Abs,
+    Round,
+    Floor,
+    Ceil,
+    Sqrt,
+    Ln,
+    Min,
+    Max,
+    Len,
+    Lower,
+    Upper,
+    Trim,
+    Concat,
+    Contains,
+    Substr,
+    Replace,
+    IsEmpty,
+    Coalesce,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        let function = match name.to_lowercase().as_str() {
+            "if" => Function::If,
+            "abs" => Function::Abs,
+            "round" => Function::Round,
+            "floor" => Function::Floor,
+            "ceil" => Function::Ceil,
+            "sqrt" => Function::Sqrt,
+            "ln" => Function::Ln,
+            "min" => Function::Min,
+            "max" => Function::Max,
+            "len" => Function::Len,
+            "lower" => Function::Lower,
+            "upper" => Function::Upper,
+            "trim" => Function::Trim,
+            "concat" => Function::Concat,
+            "contains" => Function::Contains,
+            "substr" => Function::Substr,
+            "replace" => Function::Replace,
+            "is_empty" => Function::IsEmpty,
+            "coalesce" => Function::Coalesce,
+            _ => return None,
+        };
+
+        Some(function)
+    }
+
+    /// Allowed number of arguments (min, max)
+    fn arity(&self) -> (usize, usize) {
+        match self {
+            Function::If | Function::Replace => (3, 3),
+            Function::Substr => (2, 3),
+            Function::Contains => (2, 2),
+            Function::Round => (1, 2),
+            Function::Min | Function::Max | Function::Concat | Function::Coalesce => {
+                (1, usize::MAX)
+            }
+            _ => (1, 1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(DeserializationType),
+    Str(String),
+    Ident(String),
+    Column(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Comma,
+}
+
+const OPERATORS: [&str; 15] = [
+    "==", "!=", "<=", ">=", "&&", "||", "<", ">", "+", "-", "*", "/", "%", "!", "=",
+];
+
+fn tokenize(src: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = src.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit()
+            || (c == '.' && chars.get(i + 1).is_some_and(|n| n.is_ascii_digit()))
+        {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                let mut j = i + 1;
+                if j < chars.len() && (chars[j] == '+' || chars[j] == '-') {
+                    j += 1;
+                }
+                if j < chars.len() && chars[j].is_ascii_digit() {
+                    i = j;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let literal: String = chars[start..i].iter().collect();
+            let number =
+                match literal.parse::<i64>() {
+                    Ok(x) => DeserializationType::INTEGER(x),
+                    Err(_) => DeserializationType::FLOAT(literal.parse().map_err(|_| {
+                        Error::parse(format!("Invalid number '{literal}' at {start}"))
+                    })?),
+                };
+            tokens.push(Token::Number(number));
+        } else if c == '"' || c == '\'' {
+            let start = i;
+            let mut value = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    Some('\\') => {
+                        match chars.get(i + 1) {
+                            Some('n') => value.push('\n'),
+                            Some('t') => value.push('\t'),
+                            Some(other) => value.push(*other),
+                            None => break,
+                        }
+                        i += 2;
+                    }
+                    Some(q) if *q == c => {
+                        i += 1;
+                        break;
+                    }
+                    Some(other) => {
+                        value.push(*other);
+                        i += 1;
+                    }
+                    None => {
+                        return Err(Error::parse(format!(
+                            "Unterminated string literal at {start}"
+                        )))
+                    }
+                }
+            }
+            tokens.push(Token::Str(value));
+        } else if c == '[' {
+            let start = i;
+            match chars[i..].iter().position(|ch| *ch == ']') {
+                Some(len) => {
+                    tokens.push(Token::Column(chars[i + 1..i + len].iter().collect()));
+                    i += len + 1;
+                }
+                None => {
+                    return Err(Error::parse(format!(
+                        "Unterminated column reference at {start}"
+                    )))
+                }
+            }
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else if c == '(' {
+            tokens.push(Token::LParen);
+            i += 1;
+        } else if c == ')' {
+            tokens.push(Token::RParen);
+            i += 1;
+        } else if c == ',' {
+            tokens.push(Token::Comma);
+            i += 1;
+        } else {
+            let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+            match OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+                Some(op) => {
+                    tokens.push(Token::Op(op));
+                    i += op.len();
+                }
+                None => return Err(Error::parse(format!("Unexpected symbol '{c}' at {i}"))),
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    headers: &'a [String],
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            other => Err(Error::parse(format!(
+                "Expected {expected:?}, found {other:?}"
+            ))),
+        }
+    }
+
+    fn binary(
+        &mut self,
+        operators: &[(&str, BinaryOp)],
+        operand: fn(&mut Self) -> Result<Expression>,
+    ) -> Result<Expression> {
+        let mut lhs = operand(self)?;
+
+        while let Some(Token::Op(op)) = self.peek() {
+            let Some((_, bin_op)) = operators.iter().find(|(s, _)| s == op) else {
+                break;
+            };
+            let bin_op = *bin_op;
+            self.pos += 1;
+
+            let rhs = operand(self)?;
+            lhs = Expression::Binary(bin_op, Box::new(lhs), Box::new(rhs));
+        }
+
+        Ok(lhs)
+    }
+
+    fn or(&mut self) -> Result<Expression> {
+        self.binary(&[("||", BinaryOp::Or)], Self::and)
+    }
+
+    fn and(&mut self) -> Result<Expression> {
+        self.binary(&[("&&", BinaryOp::And)], Self::comparison)
+    }
+
+    fn comparison(&mut self) -> Result<Expression> {
+        self.binary(
+            &[
+                ("==", BinaryOp::Eq),
+                ("=", BinaryOp::Eq),
+                ("!=", BinaryOp::Ne),
+                ("<", BinaryOp::Lt),
+                ("<=", BinaryOp::Le),
+                (">", BinaryOp::Gt),
+                (">=", BinaryOp::Ge),
+            ],
+            Self::additive,
+        )
+    }
+
+    fn additive(&mut self) -> Result<Expression> {
+        self.binary(
+            &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+            Self::multiplicative,
+        )
+    }
+
+    fn multiplicative(&mut self) -> Result<Expression> {
+        self.binary(
+            &[
+                ("*", BinaryOp::Mul),
+                ("/", BinaryOp::Div),
+                ("%", BinaryOp::Rem),
+            ],
+            Self::unary,
+        )
+    }
+
+    fn unary(&mut self) -> Result<Expression> {
+        match self.peek() {
+            Some(Token::Op("-")) => {
+                self.pos += 1;
+                Ok(Expression::Unary(UnaryOp::Neg, Box::new(self.unary()?)))
+            }
+            Some(Token::Op("!")) => {
+                self.pos += 1;
+                Ok(Expression::Unary(UnaryOp::Not, Box::new(self.unary()?)))
+            }
+            _ => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expression> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(Expression::Literal(n)),
+            Some(Token::Str(s)) => Ok(Expression::Literal(DeserializationType::STRING(s))),
+            Some(Token::Column(name)) => self.column(&name),
+            Some(Token::Ident(name)) => {
+                if self.peek() == Some(&Token::LParen) {
+                    self.pos += 1;
+                    return self.call(&name);
+                }
+
+                match name.to_lowercase().as_str() {
+                    "true" => Ok(Expression::Literal(DeserializationType::BOOLEAN(true))),
+                    "false" => Ok(Expression::Literal(DeserializationType::BOOLEAN(false))),
+                    "empty" => Ok(Expression::Literal(DeserializationType::EMPTY)),
+                    _ => self.column(&name),
+                }
+            }
+            Some(Token::LParen) => {
+                let expr = self.or()?;
+                self.expect(Token::RParen)?;
+                Ok(expr)
+            }
+            other => Err(Error::parse(format!("Unexpected token {other:?}"))),
+        }
+    }
+
+    fn column(&self, name: &str) -> Result<Expression> {
+        let name = name.trim();
+        self.headers
+            .iter()
+            .position(|h| h == name)
+            .or_else(|| {
+                self.headers
+                    .iter()
+                    .position(|h| h.to_lowercase() == name.to_lowercase())
+            })
+            .map(Expression::Column)
+            .ok_or_else(|| Error::invalid_column(name, "Column does not exist"))
+    }
+
+    fn call(&mut self, name: &str) -> Result<Expression> {
+        let function = Function::from_name(name)
+            .ok_or_else(|| Error::parse(format!("Unknown function '{name}'")))?;
+
+        let mut args = Vec::new();
+        if self.peek() == Some(&Token::RParen) {
+            self.pos += 1;
+        } else {
+            loop {
+                args.push(self.or()?);
+                match self.next() {
+                    Some(Token::Comma) => continue,
+                    Some(Token::RParen) => break,
+                    other => {
+                        return Err(Error::parse(format!(
+                            "Expected ',' or ')' in '{name}' call, found {other:?}"
+                        )))
+                    }
+                }
+            }
+        }
+
+        let (min_args, max_args) = function.arity();
+        if args.len() < min_args || args.len() > max_args {
+            return Err(Error::parse(format!(
+                "Wrong number of arguments ({}) for '{name}'",
+                args.len()
+            )));
+        }
+
+        Ok(Expression::Call(function, args))
+    }
+}
+
+impl Expression {
+    /// Compile an expression.
+    ///
+    /// # Arguments
+    ///
+    /// * `src` - Source of the expression (e.g. `Cholesterol / Age`)
+    /// * `headers` - Column names used to resolve column references
+    ///
+    /// # Errors
+    /// Syntax errors, unknown columns and functions, wrong number of function arguments;
+    pub fn parse(src: &str, headers: &[String]) -> Result<Self> {
+        let parse = || {
+            let mut parser = Parser {
+                tokens: tokenize(src)?,
+                pos: 0,
+                headers,
+            };
+
+            let expr = parser.or()?;
+            if let Some(token) = parser.peek() {
+                return Err(Error::parse(format!("Unexpected token {token:?}")));
+            }
+
+            Ok(expr)
+        };
+
+        // The expression is the offending value of syntax errors
+        parse().map_err(|e| e.with_value(src))
+    }
+
+    /// Indexes of the columns referenced by the expression.
+    pub(crate) fn columns(&self) -> Vec<usize> {
+        match self {
+            Expression::Literal(_) => Vec::new(),
+            Expression::Column(col_id) => vec![*col_id],
+            Expression::Unary(_, expr) => expr.columns(),
+            Expression::Binary(_, lhs, rhs) => {
+                let mut columns = lhs.columns();
+                columns.extend(rhs.columns());
+                columns
+            }
+            Expression::Call(_, args) => args.iter().flat_map(|arg| arg.columns()).collect(),
+        }
+    }
+
+    /// Evaluate the expression against a row parsed through `parse_col_type`.
+    ///
+    /// Missing cells of a short row are treated as `EMPTY`.
+    ///
+    /// # Errors
+    /// Operations on incompatible types;
+    pub fn evaluate(&self, row: &[DeserializationType]) -> Result<DeserializationType> {
+        match self {
+            Expression::Literal(value) => Ok(value.clone()),
+            Expression::Column(col_id) => Ok(row
+                .get(*col_id)
+                .cloned()
+                .unwrap_or(DeserializationType::EMPTY)),
+            Expression::Unary(op, expr) => match (op, expr.evaluate(row)?) {
+                (_, DeserializationType::EMPTY) => Ok(DeserializationType::EMPTY),
+                (UnaryOp::Neg, DeserializationType::INTEGER(x)) => Ok(x.checked_neg().map_or(
+                    DeserializationType::FLOAT(-(x as f64)),
+                    DeserializationType::INTEGER,
+                )),
+                (UnaryOp::Neg, DeserializationType::FLOAT(x)) => Ok(DeserializationType::FLOAT(-x)),
+                (UnaryOp::Not, DeserializationType::BOOLEAN(x)) => {
+                    Ok(DeserializationType::BOOLEAN(!x))
+                }
+                (op, value) => Err(type_error(&format!("{op:?}"), &[value])),
+            },
+            Expression::Binary(op, lhs, rhs) => Self::binary(*op, lhs, rhs, row),
+            Expression::Call(function, args) => Self::call(*function, args, row),
+        }
+    }
+
+    fn binary(
+        op: BinaryOp,
+        lhs: &Expression,
+        rhs: &Expression,
+        row: &[DeserializationType],
+    ) -> Result<DeserializationType> {
+        use DeserializationType::{BOOLEAN, EMPTY, FLOAT, INTEGER, STRING};
+
+        // Short-circuit logic
+        if let BinaryOp::And | BinaryOp::Or = op {
+            let short = op == BinaryOp::Or;
+            return match lhs.evaluate(row)? {
+                BOOLEAN(x) if x == short => Ok(BOOLEAN(short)),
+                BOOLEAN(_) => match rhs.evaluate(row)? {
+                    BOOLEAN(y) => Ok(BOOLEAN(y)),
+                    other => Err(type_error(&format!("{op:?}"), &[other])),
+                },
+                other => Err(type_error(&format!("{op:?}"), &[other])),
+            };
+        }
+
+        let (a, b) = (lhs.evaluate(row)?, rhs.evaluate(row)?);
+
+        match op {
+            BinaryOp::Eq => return Ok(BOOLEAN(a == b)),
+            BinaryOp::Ne => return Ok(BOOLEAN(a != b)),
+            _ => {}
+        }
+
+        match (op, a, b) {
+            (_, EMPTY, _) | (_, _, EMPTY) => Ok(EMPTY),
+            // Integer arithmetic stays integer unless it overflows
+            (BinaryOp::Add, INTEGER(x), INTEGER(y)) if x.checked_add(y).is_some() => {
+                Ok(INTEGER(x + y))
+            }
+            (BinaryOp::Sub, INTEGER(x), INTEGER(y)) if x.checked_sub(y).is_some() => {
+                Ok(INTEGER(x - y))
+            }
+            (BinaryOp::Mul, INTEGER(x), INTEGER(y)) if x.checked_mul(y).is_some() => {
+                Ok(INTEGER(x * y))
+            }
+            (BinaryOp::Rem, INTEGER(x), INTEGER(y)) if x.checked_rem(y).is_some() => {
+                Ok(INTEGER(x % y))
+            }
+            (
+                BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem,
+                a,
+                b,
+            ) if a.is_numeric() && b.is_numeric() => {
+                let (x, y) = (
+                    a.as_f64().unwrap_or_default(),
+                    b.as_f64().unwrap_or_default(),
+                );
+                Ok(FLOAT(match op {
+                    BinaryOp::Add => x + y,
+                    BinaryOp::Sub => x - y,
+                    BinaryOp::Mul => x * y,
+                    BinaryOp::Div => x / y,
+                    _ => x % y,
+                }))
+            }
+            (BinaryOp::Add, a @ STRING(_), b) | (BinaryOp::Add, a, b @ STRING(_)) => {
+                Ok(STRING(a.to_cell_string() + &b.to_cell_string()))
+            }
+            (BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge, a, b) => {
+                let ordering = match (&a, &b) {
+                    (STRING(x), STRING(y)) => Some(x.cmp(y)),
+                    (a, b) => a.partial_cmp(b),
+                };
+                let Some(ordering) = ordering else {
+                    return Err(type_error(&format!("{op:?}"), &[a, b]));
+                };
+
+                Ok(BOOLEAN(match op {
+                    BinaryOp::Lt => ordering.is_lt(),
+                    BinaryOp::Le => ordering.is_le(),
+                    BinaryOp::Gt => ordering.is_gt(),
+                    _ => ordering.is_ge(),
+                }))
+            }
+            (op, a, b) => Err(type_error(&format!("{op:?}"), &[a, b])),
+        }
+    }
+
+    fn call(
+        function: Function,
+        args: &[Expression],
+        row: &[DeserializationType],
+    ) -> Result<DeserializationType> {
+        use DeserializationType::{BOOLEAN, EMPTY, FLOAT, INTEGER, STRING};
+
+        // Lazy functions
+        match function {
+            Function::If => {
+                return match args[0].evaluate(row)? {
+                    BOOLEAN(true) => args[1].evaluate(row),
+                    BOOLEAN(false) | EMPTY => args[2].evaluate(row),
+                    other => Err(type_error("if", &[other])),
+                }
+            }
+            Function::Coalesce => {
+                for arg in args {
+                    let value = arg.evaluate(row)?;
+                    if value != EMPTY {
+                        return Ok(value);
+                    }
+                }
+                return Ok(EMPTY);
+            }
+            Function::IsEmpty => return Ok(BOOLEAN(args[0].evaluate(row)? == EMPTY)),
+            _ => {}
+        }
+
+        let values = args
+            .iter()
+            .map(|arg| arg.evaluate(row))
+            .collect::<Result<Vec<DeserializationType>>>()?;
+
+        if values.contains(&EMPTY) {
+            return Ok(EMPTY);
+        }
+
+        let name = format!("{function:?}").to_lowercase();
+        let numbers: Vec<Option<f64>> = values.iter().map(|v| v.as_f64()).collect();
+
+        let result = match (function, values.as_slice(), numbers.as_slice()) {
+            (Function::Abs, [INTEGER(x)], _) if x.checked_abs().is_some() => INTEGER(x.abs()),
+            (Function::Abs, _, [Some(x)]) => FLOAT(x.abs()),
+            (Function::Round, _, [Some(x)]) => FLOAT(x.round()),
+            (Function::Round, _, [Some(x), Some(digits)]) => {
+                let factor = 10_f64.powi(*digits as i32);
+                FLOAT((x * factor).round() / factor)
+            }
+            (Function::Floor, _, [Some(x)]) => FLOAT(x.floor()),
+            (Function::Ceil, _, [Some(x)]) => FLOAT(x.ceil()),
+            (Function::Sqrt, _, [Some(x)]) => FLOAT(x.sqrt()),
+            (Function::Ln, _, [Some(x)]) => FLOAT(x.ln()),
+            (Function::Min | Function::Max, values, numbers)
+                if numbers.iter().all(|x| x.is_some()) =>
+            {
+                // Keep the original value (and type) of the extreme
+                let mut best = &values[0];
+                for value in values.iter().skip(1) {
+                    let better = if function == Function::Min {
+                        value < best
+                    } else {
+                        value > best
+                    };
+                    if better {
+                        best = value;
+                    }
+                }
+                best.clone()
+            }
+            (Function::Len, [value], _) => INTEGER(value.to_cell_string().chars().count() as i64),
+            (Function::Lower, [value], _) => STRING(value.to_cell_string().to_lowercase()),
+            (Function::Upper, [value], _) => STRING(value.to_cell_string().to_uppercase()),
+            (Function::Trim, [value], _) => STRING(value.to_cell_string().trim().to_owned()),
+            (Function::Concat, values, _) => {
+                STRING(values.iter().map(|v| v.to_cell_string()).collect())
+            }
+            (Function::Contains, [haystack, needle], _) => {
+                BOOLEAN(haystack.to_cell_string().contains(&needle.to_cell_string()))
+            }
+            (Function::Substr, [value, ..], [_, Some(start), rest @ ..]) => {
+                let text = value.to_cell_string();
+                let chars = text.chars().skip(start.max(0_f64) as usize);
+                STRING(match rest {
+                    [Some(len)] => chars.take(len.max(0_f64) as usize).collect(),
+                    [] => chars.collect(),
+                    _ => return Err(type_error(&name, &values)),
+                })
+            }
+            (Function::Replace, [value, from, to], _) => STRING(
+                value
+                    .to_cell_string()
+                    .replace(&from.to_cell_string(), &to.to_cell_string()),
+            ),
+            _ => return Err(type_error(&name, &values)),
+        };
+
+        Ok(result)
+    }
+}
+
+fn type_error(operation: &str, values: &[DeserializationType]) -> Error {
+    let types: Vec<&str> = values.iter().map(|v| v.type_name()).collect();
+    Error::type_mismatch(format!("operands of '{operation}'"), types.join(", "))
+}
+
+#[cfg(test)]
+mod test {
+    use super::Expression;
+    use crate::deserialization::{parse_col_type, DeserializationType};
+
+    fn headers() -> Vec<String> {
+        vec![
+            "Age",
+            "Cholesterol",
+            "Smoking",
+            "Country",
+            "Heart Attack Risk",
+        ]
+        .into_iter()
+        .map(|s| s.to_owned())
+        .collect()
+    }
+
+    fn row() -> Vec<DeserializationType> {
+        vec!["50", "200", "1", "South Africa", ""]
+            .into_iter()
+            .map(|s| parse_col_type(s).unwrap())
+            .collect()
+    }
+
+    fn eval(src: &str) -> DeserializationType {
+        Expression::parse(src, &headers())
+            .unwrap()
+            .evaluate(&row())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(eval("Cholesterol / Age"), DeserializationType::FLOAT(4_f64));
+        assert!(matches!(
+            eval("1 + 2 * 3 - -1"),
+            DeserializationType::INTEGER(8)
+        ));
+        assert_eq!(eval("(1 + 2) * 3 % 5"), DeserializationType::INTEGER(4));
+        assert_eq!(eval("round(10 / 3, 2)"), DeserializationType::FLOAT(3.33));
+        assert_eq!(eval("max(Age, 2.5, 7)"), DeserializationType::INTEGER(50));
+        assert_eq!(eval("[Heart Attack Risk] + 1"), DeserializationType::EMPTY);
+    }
+
+    #[test]
+    fn test_conditions() {
+        assert_eq!(
+            eval(r#"if(Smoking == 1, "yes", "no")"#),
+            DeserializationType::STRING("yes".to_owned())
+        );
+        assert_eq!(
+            eval("Age >= 18 && !(Cholesterol < 100) || false"),
+            DeserializationType::BOOLEAN(true)
+        );
+        assert_eq!(
+            eval("coalesce([Heart Attack Risk], 0)"),
+            DeserializationType::INTEGER(0)
+        );
+    }
+
+    #[test]
+    fn test_strings() {
+        assert_eq!(
+            eval("upper(substr(Country, 0, 5)) + '!'"),
+            DeserializationType::STRING("SOUTH!".to_owned())
+        );
+        assert_eq!(
+            eval(r#"contains(lower(country), "africa")"#),
+            DeserializationType::BOOLEAN(true)
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(Expression::parse("Weight * 2", &headers()).is_err());
+        assert!(Expression::parse("abs(1, 2)", &headers()).is_err());
+        assert!(Expression::parse("(Age + 1", &headers()).is_err());
+        assert!(Expression::parse("Age - Country", &headers())
+            .unwrap()
+            .evaluate(&row())
+            .is_err());
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use crate::deserialization::DeserializationType;
+use crate::error::{Error, Result};
+use crate::statistics::median;
+use crate::user_input::UserInput;
+
+/// Strategy used by `CsvToolkit::fill_gaps` to replace empty cells of a column.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FillStrategy {
+    /// Literal `UserInput::VALUE` or `UserInput::EXPR` evaluated against the current row.
+    Input(UserInput),
+    /// Arithmetic mean of the column (numeric columns only).
+    Mean,
+    /// Median of the column (numeric columns only).
+    Median,
+    /// Most frequent value of the column. Ties are resolved by the first occurrence.
+    Mode,
+    /// Last non-empty value above the gap.
+    ForwardFill,
+    /// First non-empty value below the gap.
+    BackFill,
+}
+
+impl FillStrategy {
+    /// Whether the strategy needs a full pass over the column before any cell can be filled.
+    pub fn needs_statistics(&self) -> bool {
+        matches!(
+            self,
+            FillStrategy::Mean | FillStrategy::Median | FillStrategy::Mode | FillStrategy::BackFill
+        )
+    }
+}
+
+/// Collects everything a `FillStrategy` needs to know about one column during the statistics pass.
+#[derive(Debug, Default)]
+pub(crate) struct ColumnAccumulator {
+    sum: f64,
+    numbers: Vec<f64>,
+    frequencies: HashMap<String, (usize, usize)>,
+    pending_rows: Vec<usize>,
+}
+
+impl ColumnAccumulator {
+    /// Register a single cell of the column.
+    ///
+    /// # Return
+    /// Rows whose gaps should be back-filled with `raw` (empty for every strategy except `BackFill`).
+    ///
+    /// # Errors
+    /// Mean/median of a non numeric value.
+    pub fn push(
+        &mut self,
+        strategy: &FillStrategy,
+        column_name: &str,
+        row_id: usize,
+        raw: &str,
+        value: &DeserializationType,
+    ) -> Result<Vec<usize>> {
+        if *value == DeserializationType::EMPTY {
+            if *strategy == FillStrategy::BackFill {
+                self.pending_rows.push(row_id);
+            }
+            return Ok(Vec::new());
+        }
+
+        match (strategy, value) {
+            (FillStrategy::Mean | FillStrategy::Median, value) if value.is_numeric() => {
+                let x = value.as_f64().unwrap_or_default();
+                self.sum += x;
+                self.numbers.push(x);
+            }
+            (FillStrategy::Mean | FillStrategy::Median, _) => {
+                return Err(Error::type_mismatch("number", value.type_name())
+                    .at_row(row_id)
+                    .in_column(column_name)
+                    .with_value(raw));
+            }
+            (FillStrategy::Mode, _) => {
+                let entry = self
+                    .frequencies
+                    .entry(raw.to_owned())
+                    .or_insert((0, row_id));
+                entry.0 += 1;
+            }
+            (FillStrategy::BackFill, _) => return Ok(std::mem::take(&mut self.pending_rows)),
+            _ => {}
+        }
+
+        Ok(Vec::new())
+    }
+
+    /// Value which replaces every gap of the column, if the strategy has one.
+    pub fn fill_value(&mut self, strategy: &FillStrategy) -> Option<String> {
+        match strategy {
+            FillStrategy::Mean if !self.numbers.is_empty() => {
+                Some((self.sum / self.numbers.len() as f64).to_string())
+            }
+            FillStrategy::Median => median(&mut self.numbers).map(|m| m.to_string()),
+            FillStrategy::Mode => self
+                .frequencies
+                .iter()
+                .max_by(|(_, (a_count, a_row)), (_, (b_count, b_row))| {
+                    a_count.cmp(b_count).then(b_row.cmp(a_row))
+                })
+                .map(|(value, _)| value.clone()),
+            _ => None,
+        }
+    }
+}
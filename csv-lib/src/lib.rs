@@ -1,42 +1,91 @@
 use std::{
-    collections::HashMap,
-    fs::File,
-    io::{BufWriter, Write},
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::{self, File},
     path::{Path, PathBuf},
 };
 
-use constants::generate_temporary_file_name;
-use csv::{Position, Reader, ReaderBuilder, Terminator};
+use categories::CategoryProfile;
+use cell_index::{CellIndex, RowSet};
+use constants::create_scratch_dir;
+use csv::{
+    Position, QuoteStyle, Reader, ReaderBuilder, StringRecord, Terminator, Writer, WriterBuilder,
+};
 
+pub mod categories;
+pub mod cell_index;
 pub mod constants;
 pub mod deserialization;
+pub mod dialect;
+pub mod duplicates;
+pub mod encoding;
 pub mod error;
+pub mod expression;
+pub mod filling;
+pub mod normalization;
+pub mod outliers;
+pub mod output;
+pub mod parallel;
+pub mod pipeline;
+pub mod random;
+pub mod resampling;
+pub mod schema;
+pub mod sketches;
+pub mod split;
+pub mod statistics;
+pub mod type_report;
 pub mod user_input;
 
-use deserialization::{parse_col_type, DeserializationType};
-use error::{CustomError, Result};
-
-type ColSpec = HashMap<usize, DeserializationType>;
+use deserialization::{DeserializationType, ParseOptions};
+use dialect::Dialect;
+use duplicates::{same_cells, Conflict, Duplicate, DuplicateReport, KeepPolicy, RowGroups};
+use encoding::{Encoder, EncodingMethod};
+use error::{Error, Result};
+use expression::Expression;
+use filling::FillStrategy;
+use normalization::{Normalization, NormalizationMethod, Scaler};
+use outliers::{Fences, OutlierDetector, OutlierTreatment};
+use output::{line_terminator, SaveOptions};
+use parallel::{Parallelism, RowScanner};
+use pipeline::Pipeline;
+use random::SplitMix64;
+use resampling::{Features, ResamplingMethod};
+use schema::{Schema, Split, Violation};
+use split::{Assignment, FoldOutput, FoldSummary, SequentialSampler, SplitStrategy};
+use statistics::{quantile, ColumnStatistics};
+use type_report::TypeReport;
+use user_input::UserInput;
 
 pub struct CsvToolkit {
     reader: Reader<File>,
 
     pub headers: Vec<String>,
     pub types: Vec<DeserializationType>,
+    pub type_reports: Vec<TypeReport>,
 
     pub min: HashMap<String, DeserializationType>,
     pub max: HashMap<String, DeserializationType>,
-
-    pub gaps: HashMap<usize, ColSpec>,
-    pub outliers: HashMap<usize, ColSpec>,
-
+    pub categories: HashMap<String, CategoryProfile>,
+    pub statistics: Vec<ColumnStatistics>,
+
+    /// Empty cells (and null markers)
+    pub gaps: CellIndex,
+    /// Cells flagged by `postprocessing`, see `fences` for the bound and score of a value
+    pub outliers: CellIndex,
+    /// Bounds fitted by `postprocessing`, per column index
+    pub fences: HashMap<usize, Fences>,
+    pub violations: Vec<Violation>,
+
+    /// File read by `reader`: the source, or `tmp_file` once the data was rewritten
+    data_file: PathBuf,
     data_position: Position,
-    delimiter: u8,
+    dialect: Dialect,
     comment: Option<u8>,
-    double_quotes: bool,
-    escape: Option<u8>,
     terminator: Terminator,
+    parse_options: ParseOptions,
+    schema: Option<Schema>,
+    parallelism: Parallelism,
 
+    scratch_dir: PathBuf,
     tmp_file: PathBuf,
 }
 
@@ -49,26 +98,134 @@ impl CsvToolkit {
         escape: Option<u8>,
         terminator: Option<u8>,
     ) -> Result<Self> {
+        Self::new_with_options(
+            src,
+            delimiter,
+            comment,
+            double_quotes,
+            escape,
+            terminator,
+            ParseOptions::default(),
+        )
+    }
+
+    /// Same as `new`, but values are parsed with custom `ParseOptions` (number locale, date formats).
+    pub fn new_with_options(
+        src: impl AsRef<Path>,
+        delimiter: u8,
+        comment: Option<u8>,
+        double_quotes: bool,
+        escape: Option<u8>,
+        terminator: Option<u8>,
+        parse_options: ParseOptions,
+    ) -> Result<Self> {
+        let dialect = Dialect {
+            delimiter,
+            double_quotes,
+            escape,
+            terminator: line_terminator(src.as_ref(), terminator)?,
+            ..Dialect::default()
+        };
         let terminator = match terminator {
             Some(s) => Terminator::Any(s),
             None => Terminator::CRLF,
         };
 
-        let mut reader = ReaderBuilder::new()
-            .trim(csv::Trim::All)
-            .terminator(terminator)
-            .flexible(true)
-            .escape(escape)
-            .double_quote(double_quotes)
-            .comment(comment)
-            .delimiter(delimiter)
-            .from_path(src)?;
+        Self::from_dialect(
+            src.as_ref(),
+            dialect,
+            comment,
+            terminator,
+            parse_options,
+            create_scratch_dir()?,
+        )
+        .map_err(|e| e.in_file(src))
+    }
+
+    /// Open a csv file of unknown dialect: the delimiter, quote character, header line, line
+    /// terminator and encoding are detected by `Dialect::sniff` (see `dialect()` for the result).
+    ///
+    /// Files which are not UTF-8, start with a byte order mark or have no header line are first
+    /// converted to UTF-8 with generated headers (`column_1`, `column_2`, ...) in the scratch
+    /// directory. Saved files are always UTF-8.
+    ///
+    /// # Errors
+    /// fs, io, csv, invalid UTF-16;
+    pub fn open(src: impl AsRef<Path>) -> Result<Self> {
+        Self::open_with_options(src, ParseOptions::default())
+    }
+
+    /// Same as `open`, but values are parsed with custom `ParseOptions`.
+    pub fn open_with_options(src: impl AsRef<Path>, parse_options: ParseOptions) -> Result<Self> {
+        let dialect = Dialect::sniff(src.as_ref()).map_err(|e| e.in_file(src.as_ref()))?;
+        let scratch_dir = create_scratch_dir()?;
+
+        let source = if dialect.needs_transcoding() {
+            let source = scratch_dir.join("source.csv");
+            if let Err(e) = dialect.transcode(src.as_ref(), &source) {
+                let _ = fs::remove_dir_all(&scratch_dir);
+                return Err(e);
+            }
+            source
+        } else {
+            src.as_ref().to_path_buf()
+        };
+
+        // The CRLF terminator of the reader accepts any line ending
+        Self::from_dialect(
+            &source,
+            dialect,
+            None,
+            Terminator::CRLF,
+            parse_options,
+            scratch_dir,
+        )
+        .map_err(|e| e.in_file(src))
+    }
+
+    /// Dialect of the source file, as given to `new` or detected by `open`.
+    ///
+    /// Once the data is rewritten, quotes are doubled unless the dialect has an escape character.
+    pub fn dialect(&self) -> &Dialect {
+        &self.dialect
+    }
+
+    /// Queue operations on the data, run in as few passes as possible by `Pipeline::run`.
+    pub fn pipeline(&mut self) -> Pipeline<'_> {
+        Pipeline::new(self)
+    }
+
+    /// Threads of the passes computing statistics (`preprocessing`, `postprocessing`,
+    /// `normalizing`, `treat_outliers`). Results are identical whatever the parallelism.
+    ///
+    /// Rows are parsed in parallel, the statistics are accumulated sequentially (see `parallel`).
+    ///
+    /// Statistics of the source file are computed by the constructor with `Parallelism::default()`.
+    pub fn set_parallelism(&mut self, parallelism: Parallelism) {
+        self.parallelism = parallelism;
+    }
+
+    fn from_dialect(
+        src: &Path,
+        dialect: Dialect,
+        comment: Option<u8>,
+        terminator: Terminator,
+        parse_options: ParseOptions,
+        scratch_dir: PathBuf,
+    ) -> Result<Self> {
+        let mut reader = match csv_reader(src, &dialect, comment, terminator) {
+            Ok(reader) => reader,
+            Err(e) => {
+                let _ = fs::remove_dir_all(&scratch_dir);
+                return Err(e.into());
+            }
+        };
 
         let mut data_position = Position::new();
         let headers: Vec<String> = reader
             .headers()?
             .into_iter()
-            .map(|s| s.to_owned())
+            .map(|s| s.trim().to_owned())
             .collect();
 
         let pos = reader.position();
@@ -81,19 +238,27 @@ impl CsvToolkit {
 
         let mut toolkit = Self {
             reader,
+            dialect,
             comment,
-            escape,
-            double_quotes,
             terminator,
-            delimiter,
+            parse_options,
             headers,
             types: Vec::with_capacity(row_len),
+            type_reports: Vec::with_capacity(row_len),
             min: HashMap::with_capacity(row_len),
             max: HashMap::with_capacity(row_len),
+            categories: HashMap::default(),
+            statistics: Vec::with_capacity(row_len),
+            data_file: src.to_path_buf(),
             data_position,
-            gaps: HashMap::default(),
-            outliers: HashMap::default(),
-            tmp_file: PathBuf::from(generate_temporary_file_name()),
+            parallelism: Parallelism::default(),
+            gaps: CellIndex::default(),
+            outliers: CellIndex::default(),
+            fences: HashMap::default(),
+            violations: Vec::default(),
+            schema: None,
+            tmp_file: scratch_dir.join("data.csv"),
+            scratch_dir,
         };
 
         toolkit.preprocessing()?;
@@ -106,7 +271,10 @@ impl CsvToolkit {
             if value.is_same_type(old_value) {
                 self.min.insert(header, value);
             } else {
-                return Err(Box::new(CustomError::new("Incompatible value tyeps!")));
+                return Err(
+                    Error::type_mismatch(old_value.type_name(), value.type_name())
+                        .in_column(header),
+                );
             }
         } else {
             self.min.insert(header, value);
@@ -119,7 +287,10 @@ impl CsvToolkit {
             if value.is_same_type(old_value) {
                 self.max.insert(header, value);
             } else {
-                return Err(Box::new(CustomError::new("Incompatible value tyeps!")));
+                return Err(
+                    Error::type_mismatch(old_value.type_name(), value.type_name())
+                        .in_column(header),
+                );
             }
         } else {
             self.max.insert(header, value);
@@ -127,352 +298,2589 @@ impl CsvToolkit {
         Ok(())
     }
 
-    pub fn postprocessing(&mut self) -> Result<()> {
-        self.reset_reader()?;
-        self.gaps.clear();
-        self.outliers.clear();
+    /// Save the current data, with every applied transform, to a csv file.
+    ///
+    /// The data is written to a hidden file next to `path` and renamed over it once it is complete
+    /// and synced, so an interrupted save never leaves a truncated file behind (nor corrupts the
+    /// source file if `path` is the source file itself).
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the saved file. Missing parent directories are created.
+    /// * `options` - Delimiter, header line and overwrite policy (see `SaveOptions`).
+    ///
+    /// # Errors
+    ///
+    /// Existing file without `overwrite`, fs, io;
+    pub fn save_as(&mut self, path: impl AsRef<Path>, options: SaveOptions) -> Result<()> {
+        let path = path.as_ref();
 
-        for (row_id, data_row) in self.reader.records().enumerate() {
-            let data_row = data_row?;
-            for ((col_id, col_name), value) in self.headers.iter().enumerate().zip(data_row.iter())
-            {
-                let value = parse_col_type(value)?;
-
-                if value == DeserializationType::EMPTY {
-                    // Update gaps
-
-                    if let Some(col_hash) = self.gaps.get_mut(&row_id) {
-                        col_hash.insert(col_id, DeserializationType::EMPTY);
-                    } else {
-                        let mut col_hash: HashMap<usize, DeserializationType> = HashMap::new();
-                        col_hash.insert(col_id, DeserializationType::EMPTY);
-                        self.gaps.insert(row_id, col_hash);
-                    }
-                } else {
-                    // Update outliers
-                    if !value.is_ordered() {
-                        continue;
-                    }
+        if !options.overwrite && path.exists() {
+            return Err(Error::io("File already exists").in_file(path));
+        }
 
-                    // self.update_outliers(row_id, col_id, col_name, value);
-
-                    if let Some(limit) = self.max.get(col_name) {
-                        let limit = limit.clone();
-                        if value > limit {
-                            if let Some(col_hash) = self.outliers.get_mut(&row_id) {
-                                col_hash.insert(col_id, limit);
-                            } else {
-                                let mut col_hash: HashMap<usize, DeserializationType> =
-                                    HashMap::new();
-                                col_hash.insert(col_id, limit);
-                                self.outliers.insert(row_id, col_hash);
-                            }
-                        }
-                    }
+        let Some(file_name) = path.file_name() else {
+            return Err(Error::invalid_argument("Not a file path").in_file(path));
+        };
 
-                    if let Some(limit) = self.min.get(col_name) {
-                        let limit = limit.clone();
-                        if value < limit {
-                            if let Some(col_hash) = self.outliers.get_mut(&row_id) {
-                                col_hash.insert(col_id, limit);
-                            } else {
-                                let mut col_hash: HashMap<usize, DeserializationType> =
-                                    HashMap::new();
-                                col_hash.insert(col_id, limit);
-                                self.outliers.insert(row_id, col_hash);
-                            }
-                        }
-                    }
-                }
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() && !parent.exists() {
+                fs::create_dir_all(parent)?;
             }
         }
 
+        let partial = path.with_file_name(format!(".{}.partial", file_name.to_string_lossy()));
+        if let Err(e) = self.write_data(&partial, &options) {
+            let _ = fs::remove_file(&partial);
+            return Err(e);
+        }
+
+        fs::rename(&partial, path)?;
         Ok(())
     }
 
-    pub fn normalizing(&mut self, column_list: Vec<String>) -> Result<()> {
-        self.reset_reader()?;
-
-        // Check if all columns is NUMBER
-        for h in column_list.iter() {
-            if let Some(min_value) = self.min.get(h) {
-                if !min_value.is_ordered() {
-                    return Err(Box::new(CustomError::new(&format!(
-                        "Column '{h}' could not be normalized!"
-                    ))));
-                }
-            }
-        }
-
-        let fh = File::create(self.tmp_file.as_path())?;
-        let delimiter = String::from(core::str::from_utf8(&[self.delimiter]).unwrap());
+    /// Descriptive statistics of a column (see `ColumnStatistics`), by header name.
+    pub fn column_statistics(&self, header: &str) -> Option<&ColumnStatistics> {
+        let col_id = self.headers.iter().position(|h| h == header)?;
+        self.statistics.get(col_id)
+    }
 
-        println!("DELIMITER: {}", delimiter);
+    /// Apply a `Schema` (see `Schema::load`).
+    ///
+    /// Declared types override the inferred ones, `split` columns are replaced by their parts and
+    /// every value is validated against its column constraints (see `violations`). A value which
+    /// could not be split into the declared number of parts gives a gap in every part.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a column of the schema does not exist (the parts of split columns excepted).
+    pub fn with_schema(mut self, schema: Schema) -> Result<Self> {
+        let parts: HashSet<&String> = schema
+            .columns
+            .values()
+            .filter_map(|spec| spec.split.as_ref())
+            .flat_map(|split| split.into.iter())
+            .collect();
 
-        self.reset_reader()?;
+        if let Some(header) = schema
+            .columns
+            .keys()
+            .find(|header| !parts.contains(header) && !self.headers.contains(header))
+        {
+            return Err(Error::invalid_column(
+                header.as_str(),
+                "Column does not exist",
+            ));
+        }
 
-        let mut buf_writer = BufWriter::new(fh);
-        let mut row: Vec<String> = Vec::with_capacity(self.headers.len());
+        for (header, spec) in schema.columns.iter() {
+            if let Some(column_type) = spec.column_type {
+                self.parse_options
+                    .column_types
+                    .insert(header.clone(), column_type);
+            }
+        }
 
-        // Write CSV headers
-        writeln!(&mut buf_writer, "{}", &self.headers.join(&delimiter))?;
-        println!("HEADERS: {}", &self.headers.join(&delimiter));
+        let splits: HashMap<usize, Split> = self.column_plan(
+            schema
+                .columns
+                .iter()
+                .filter_map(|(header, spec)| Some((header.clone(), spec.split.clone()?)))
+                .collect(),
+        )?;
+        self.schema = Some(schema);
+
+        if splits.is_empty() {
+            self.reset_reader()?;
+            self.preprocessing()?;
+            return Ok(self);
+        }
 
-        for (_row_id, it) in self.reader.records().enumerate() {
-            let string_record = it?;
-            row.clear();
+        let mut headers = Vec::with_capacity(self.headers.len() + splits.len());
+        for (col_id, header) in self.headers.iter().enumerate() {
+            match splits.get(&col_id) {
+                Some(split) => headers.extend(split.into.iter().cloned()),
+                None => headers.push(header.clone()),
+            }
+        }
 
-            for ((_col_id, header), string_value) in self
-                .headers
-                .iter()
-                .enumerate()
-                .zip(string_record.into_iter())
-            {
-                if column_list.contains(header) {
-                    let value = parse_col_type(string_value)?;
-                    match value {
-                        DeserializationType::NUMBER(_) => {
-                            match (self.min.get(header), self.max.get(header)) {
-                                (Some(min), Some(max)) => {
-                                    let min = min.clone();
-                                    let max = max.clone();
-
-                                    let n_val =
-                                        (value.clone() - min.clone()) / (max.clone() - min.clone());
-                                    row.push(n_val.to_string());
-
-                                    println!("N-val: {}", n_val);
-                                }
-                                _ => row.push(string_value.to_string()),
-                            }
+        self.rewrite_records(headers, |_row_id, row| {
+            let mut split_row = Vec::with_capacity(row.len() + splits.len());
+            for (col_id, value) in row.into_iter().enumerate() {
+                match splits.get(&col_id) {
+                    Some(split) => {
+                        let parts: Vec<String> = value
+                            .split(split.separator.as_str())
+                            .map(|part| part.trim().to_owned())
+                            .collect();
+
+                        if parts.len() == split.into.len() {
+                            split_row.extend(parts);
+                        } else {
+                            split_row.resize(split_row.len() + split.into.len(), String::default());
                         }
-                        _ => row.push(string_value.to_string()),
                     }
-                } else {
-                    row.push(string_value.to_string());
+                    None => split_row.push(value),
                 }
             }
 
-            // Write data row (with normalized data)
-            writeln!(&mut buf_writer, "{}", row.join(&delimiter))?;
-            println!("DAT: {}", row.join(&delimiter));
-        }
-
-        buf_writer.flush()?;
+            Ok(Some(split_row))
+        })?;
 
-        self.switch_reader_to_tmp_file()?;
-        self.preprocessing()?;
+        Ok(self)
+    }
 
-        Ok(())
+    /// Type report of a column (see `TypeReport`), by header name.
+    pub fn type_report(&self, header: &str) -> Option<&TypeReport> {
+        let col_id = self.headers.iter().position(|h| h == header)?;
+        self.type_reports.get(col_id)
     }
 
-    fn preprocessing(&mut self) -> Result<()> {
-        self.min.clear();
-        self.max.clear();
-        self.types.clear();
-        self.gaps.clear();
+    /// Find gaps and statistical outliers.
+    ///
+    /// Every detector is fitted on the non empty values of its column first, then each value outside
+    /// the fitted bounds is recorded in `outliers` with the violated bound and its score.
+    ///
+    /// # Arguments
+    ///
+    /// * `detectors` - Map of column names (as in `headers`) to the outlier detector of the column.
+    ///
+    /// # Errors
+    ///
+    /// Unknown or non numeric column, io;
+    pub fn postprocessing(&mut self, detectors: HashMap<String, OutlierDetector>) -> Result<()> {
+        let plan = self.column_plan(detectors)?;
+
+        // Fit detectors
+        let mut samples = self.numeric_samples(|col_id| plan.contains_key(&col_id))?;
+        self.fences = samples
+            .iter_mut()
+            .filter_map(|(col_id, values)| plan[col_id].fit(values).map(|f| (*col_id, f)))
+            .collect();
 
-        for (row_id, it) in self.reader.records().enumerate() {
-            let res = it?;
-            for ((col_id, header), variable) in self.headers.iter().enumerate().zip(res.into_iter())
-            {
-                let var = parse_col_type(variable)?;
-
-                Self::check_or_insert_column_type(&mut self.types, col_id, &header, &var)?;
-
-                match var {
-                    DeserializationType::NUMBER(_) => {
-                        if let Some(curr) = self.min.get(header).take() {
-                            self.min
-                                .insert(header.to_owned(), min!(curr.clone(), var.clone()));
-                        } else {
-                            self.min.insert(header.to_owned(), var.clone());
-                        }
+        self.gaps.clear();
+        self.outliers.clear();
 
-                        if let Some(curr) = self.max.get(header).take() {
-                            self.max
-                                .insert(header.to_owned(), max!(curr.clone(), var.clone()));
-                        } else {
-                            self.max.insert(header.to_owned(), var.clone());
+        let scanner = self.row_scanner();
+        scanner.for_each(
+            |_| true,
+            |row_id, _record, values| {
+                for (col_id, value) in values.iter().enumerate() {
+                    match value {
+                        DeserializationType::EMPTY => {
+                            self.gaps.insert(row_id, col_id);
                         }
-                    }
-                    DeserializationType::EMPTY => {
-                        if let Some(g_row) = self.gaps.get_mut(&row_id) {
-                            g_row.insert(col_id, var.clone());
-                        } else {
-                            let mut tmp = HashMap::new();
-                            tmp.insert(col_id, var.clone());
-                            self.gaps.insert(row_id, tmp);
+                        DeserializationType::INTEGER(_) | DeserializationType::FLOAT(_) => {
+                            let outlier = value
+                                .as_f64()
+                                .and_then(|x| self.fences.get(&col_id)?.check(x));
+                            if outlier.is_some() {
+                                self.outliers.insert(row_id, col_id);
+                            }
                         }
+                        _ => continue,
                     }
-                    _ => continue,
                 }
-            }
-        }
 
-        Ok(())
+                Ok(())
+            },
+        )
     }
 
-    /// Reset seek position for inner `reader` (csv::Reader) instance to be able to read src file one more.
+    /// Normalize numeric columns, each one with its own method.
     ///
-    fn reset_reader(&mut self) -> std::result::Result<(), csv::Error> {
-        self.reader.seek(self.data_position.clone())
+    /// # Arguments
+    ///
+    /// * `methods` - Map of column names (as in `headers`) to the normalization method of the column.
+    ///
+    /// # Return
+    /// Fitted `Scaler`, to apply the same transform to another dataset (see `apply_normalization`).
+    /// Columns without any value are left untouched and omitted.
+    ///
+    /// # Errors
+    ///
+    /// Unknown or non numeric column, io;
+    pub fn normalizing(&mut self, methods: HashMap<String, NormalizationMethod>) -> Result<Scaler> {
+        let mut report = self.pipeline().normalize(methods).run()?;
+
+        Ok(report.scalers.pop().unwrap_or_default())
     }
 
-    /// Switch the `reader` (csv::Reader) obj to the temporary file.
+    /// Apply an already fitted `Scaler` (e.g. fitted by `normalizing` on a train split).
     ///
-    /// This method should call after all types of source data mutation!
+    /// # Errors
     ///
-    fn switch_reader_to_tmp_file(&mut self) -> Result<()> {
-        self.reader = ReaderBuilder::new()
-            .trim(csv::Trim::All)
-            .terminator(self.terminator.clone())
-            .flexible(true)
-            .escape(self.escape.clone())
-            .double_quote(self.double_quotes)
-            .comment(self.comment.clone())
-            .delimiter(self.delimiter)
-            .from_path(self.tmp_file.as_path())?;
+    /// Unknown column, values which could not be transformed (see `Normalization::transform`), io;
+    pub fn apply_normalization(&mut self, scaler: &Scaler) -> Result<()> {
+        self.pipeline().normalize_with(scaler).run().map(|_| ())
+    }
 
-        Ok(())
+    /// Restore the original values of columns normalized with the `scaler`.
+    ///
+    /// # Errors
+    ///
+    /// Unknown column, io;
+    pub fn invert_normalization(&mut self, scaler: &Scaler) -> Result<()> {
+        self.map_numeric_columns(scaler, |normalization, y| Ok(normalization.inverse(y)))
     }
 
-    /// Checks the type of a value in a column and ensures that all values in the column are of the same type. Add one if absent.
+    /// Encode categorical columns into numbers, each one with its own method.
     ///
-    /// This method compares the type of a new value with the existing type for the specified column. If the types
-    /// do not match, it returns an error. If this is the first value for the column, it records the type of the value.
+    /// One-hot encoded columns are replaced by a column per category, with headers generated in
+    /// the form of `parse_headers`. Other methods replace the values in place.
     ///
     /// # Arguments
     ///
-    /// * `types` - A mutable reference to the current instance of `types` vector.
-    /// * `column_id` - The index of the column to check.
-    /// * `column_name` - The name of the column, used for error reporting.
-    /// * `column_value` - A reference to the value whose type will be checked.
+    /// * `methods` - Map of column names (as in `headers`) to the encoding method of the column.
     ///
-    /// # Errors
+    /// # Return
+    /// Fitted `Encoder`, to encode another dataset the same way (see `apply_encoding`).
     ///
-    /// Returns an error if the type of `column_value` does not match the previously recorded type for the column.
-    /// The error message includes the column name and specifies that the types are inconsistent.
+    /// # Errors
     ///
+    /// Unknown column or label column, non numeric label, category missing from an ordinal order, io;
+    pub fn encoding(&mut self, methods: HashMap<String, EncodingMethod>) -> Result<Encoder> {
+        let mut report = self.pipeline().encode(methods).run()?;
 
-    fn check_or_insert_column_type(
-        types: &mut Vec<DeserializationType>,
-        column_id: usize,
-        column_name: &str,
-        column_value: &DeserializationType,
-    ) -> Result<()> {
-        if let Some(curr_type) = types.get(column_id) {
-            if !curr_type.is_same_type(column_value) {
-                return Err(Box::new(CustomError::new(&format!(
-                    "Seems like in column '{}' not all values are the same type!",
-                    column_name
-                ))));
-            }
-        } else {
-            types.insert(column_id, column_value.clone());
-        }
-
-        Ok(())
+        Ok(report.encoders.pop().unwrap_or_default())
     }
 
-    // fn update_outliers(
-    //     &mut self,
-    //     row_id: usize,
-    //     column_id: usize,
-    //     column_name: &str,
-    //     current_value: DeserializationType,
-    // ) {
-    //     if let Some(limit) = self.max.get(column_name) {
-    //         let limit = limit.clone();
-    //         if current_value > limit {
-    //             if let Some(col_hash) = self.outliers.get_mut(&row_id) {
-    //                 col_hash.insert(column_id, limit);
-    //             } else {
-    //                 let mut col_hash: HashMap<usize, DeserializationType> = HashMap::new();
-    //                 col_hash.insert(column_id, limit);
-    //                 self.outliers.insert(row_id, col_hash);
-    //             }
-    //         }
-    //     }
-
-    //     if let Some(limit) = self.min.get(column_name) {
-    //         let limit = limit.clone();
-    //         if current_value < limit {
-    //             if let Some(col_hash) = self.outliers.get_mut(&row_id) {
-    //                 col_hash.insert(column_id, limit);
-    //             } else {
-    //                 let mut col_hash: HashMap<usize, DeserializationType> = HashMap::new();
-    //                 col_hash.insert(column_id, limit);
-    //                 self.outliers.insert(row_id, col_hash);
-    //             }
-    //         }
-    //     }
-    // }
-}
+    /// Apply an already fitted `Encoder` (e.g. fitted by `encoding` on a train split).
+    ///
+    /// # Errors
+    ///
+    /// Unknown column, io;
+    pub fn apply_encoding(&mut self, encoder: &Encoder) -> Result<()> {
+        self.pipeline().encode_with(encoder).run().map(|_| ())
+    }
 
-#[cfg(test)]
-pub mod test {
+    /// Split the data into several csv files, e.g. train, validation and test sets.
+    ///
+    /// The data is read twice: the first pass counts rows (per label value or group, or collects the
+    /// ordering values for `SplitStrategy::TimeOrdered`), the second one streams every row to its
+    /// output. Rows keep their relative order inside each output.
+    ///
+    /// # Arguments
+    ///
+    /// * `outputs` - Paths of the output files with their relative sizes, e.g. `[("train.csv", 0.8), ("test.csv", 0.2)]`.
+    /// * `strategy` - How rows are assigned to the outputs.
+    /// * `seed` - Seed of the shuffling, the same seed gives the same split.
+    ///
+    /// # Return
+    /// Number of rows written to each output.
+    ///
+    /// # Errors
+    ///
+    /// Invalid ratios, unknown column, gaps in the ordering column, fs, io;
+    pub fn split<P: AsRef<Path>>(
+        &mut self,
+        outputs: &[(P, f64)],
+        strategy: SplitStrategy,
+        seed: u64,
+    ) -> Result<Vec<usize>> {
+        let ratios: Vec<f64> = outputs.iter().map(|(_, ratio)| *ratio).collect();
+        let mut rng = SplitMix64::new(seed);
+        let (mut assignment, key_column) = self.row_assignment(&ratios, &strategy, &mut rng)?;
+
+        let mut writers = Vec::with_capacity(outputs.len());
+        for (path, _) in outputs {
+            let mut writer = self.csv_writer(path.as_ref(), None)?;
+            writer.write_record(&self.headers)?;
+            writers.push(writer);
+        }
 
-    use super::*;
-    use std::fs;
-    use std::path::Path;
+        let mut written = vec![0; outputs.len()];
 
-    fn init() -> Result<CsvToolkit> {
-        return CsvToolkit::new(Path::new("./tests/test.csv"), b',', None, false, None, None);
-    }
+        self.reset_reader()?;
+        for (row_id, it) in self.reader.records().enumerate() {
+            let data_row = it?;
+            let key = key_column.and_then(|col_id| data_row.get(col_id));
+            let key = key.unwrap_or_default().trim();
+            let Some(part) = assignment.part(row_id, key, &mut rng) else {
+                continue;
+            };
+
+            writers[part].write_record(&data_row)?;
+            written[part] += 1;
+        }
 
-    #[test]
-    pub fn initialize_test() {
-        match init() {
-            Ok(_) => assert!(true),
-            Err(e) => assert!(false, "Could not initiate CsvToolkit!\n{e}"),
+        for mut writer in writers {
+            writer.flush()?;
         }
+
+        Ok(written)
     }
 
-    #[test]
-    pub fn test_min_max() {
-        match init() {
-            Ok(toolkit) => {
-                let test_key = String::from("Exercise Hours Per Week");
+    /// Assign every row to one of `k` folds of the same size for cross-validation.
+    ///
+    /// `SplitStrategy::Stratified` keeps the label distribution of every fold, `SplitStrategy::Grouped`
+    /// puts all rows of a group into a single fold and `SplitStrategy::TimeOrdered` gives contiguous
+    /// blocks of time.
+    ///
+    /// # Arguments
+    ///
+    /// * `k` - Number of folds (at least 2).
+    /// * `strategy` - How rows are assigned to the folds.
+    /// * `seed` - Seed of the shuffling, the same seed gives the same folds.
+    /// * `output` - Append a fold column to the data (statistics are recomputed) or write train/validation file pairs.
+    /// * `label` - Column summarized per fold. The label of `SplitStrategy::Stratified` if `None`.
+    ///
+    /// # Return
+    /// Rows and label distribution per fold.
+    ///
+    /// # Errors
+    ///
+    /// Less than 2 folds, unknown column, gaps in the ordering column, fs, io;
+    pub fn k_fold(
+        &mut self,
+        k: usize,
+        strategy: SplitStrategy,
+        seed: u64,
+        output: FoldOutput,
+        label: Option<String>,
+    ) -> Result<FoldSummary> {
+        if k < 2 {
+            return Err(Error::invalid_argument(format!(
+                "K-fold needs at least 2 folds, got {k}"
+            )));
+        }
 
-                assert_eq!(
-                    toolkit.min.get(&test_key),
-                    Some(&DeserializationType::NUMBER(0.1945150606299495))
-                );
+        let label = match (label, &strategy) {
+            (None, SplitStrategy::Stratified { label }) => Some(label.clone()),
+            (label, _) => label,
+        };
+        let label_column = match label {
+            Some(label) => self
+                .column_plan(HashMap::from([(label, ())]))?
+                .into_keys()
+                .next(),
+            None => None,
+        };
 
-                assert_eq!(
-                    toolkit.max.get(&test_key),
-                    Some(&DeserializationType::NUMBER(19.633268156072297))
-                )
+        let mut rng = SplitMix64::new(seed);
+        let (mut assignment, key_column) =
+            self.row_assignment(&vec![1.; k], &strategy, &mut rng)?;
+        let mut summary = FoldSummary::new(k);
+
+        match output {
+            FoldOutput::Column(header) => {
+                let mut headers = self.headers.clone();
+                let width = headers.len();
+                headers.push(header);
+
+                self.rewrite_records(headers, |row_id, mut row| {
+                    let key = key_column.and_then(|col_id| row.get(col_id));
+                    let fold = assignment.part(row_id, key.map_or("", |k| k.trim()), &mut rng);
+                    let Some(fold) = fold else {
+                        return Ok(Some(row));
+                    };
+
+                    summary.push(
+                        fold,
+                        label_column
+                            .and_then(|col_id| row.get(col_id))
+                            .map(|l| l.trim()),
+                    );
+
+                    row.resize(width, String::default());
+                    row.push(fold.to_string());
+                    Ok(Some(row))
+                })?;
             }
-            Err(e) => assert!(false, "Could not initiate CsvToolkit!\n{e}"),
-        }
-    }
+            FoldOutput::Files(dir) => {
+                fs::create_dir_all(&dir)?;
+
+                let mut writers = Vec::with_capacity(2 * k);
+                for fold in 0..k {
+                    for part in ["train", "val"] {
+                        let path = dir.join(format!("fold_{fold}_{part}.csv"));
+                        let mut writer = self.csv_writer(&path, None)?;
+                        writer.write_record(&self.headers)?;
+                        writers.push(writer);
+                    }
+                }
 
-    #[test]
-    pub fn test_normalization() {
-        match init() {
-            Ok(mut toolkit) => {
-                let test_key = String::from("Exercise Hours Per Week");
-                let tmp_file = toolkit.tmp_file.clone();
+                self.reset_reader()?;
+                for (row_id, it) in self.reader.records().enumerate() {
+                    let data_row = it?;
+                    let key = key_column.and_then(|col_id| data_row.get(col_id));
+                    let key = key.unwrap_or_default().trim();
+                    let Some(fold) = assignment.part(row_id, key, &mut rng) else {
+                        continue;
+                    };
 
-                match toolkit.normalizing(vec![test_key.clone()]) {
-                    Ok(_) => {
-                        fs::remove_file(tmp_file.as_path()).unwrap();
-                        assert_eq!(
-                            toolkit.min.get(&test_key),
-                            Some(&DeserializationType::NUMBER(0_f64))
-                        );
+                    let label = label_column.and_then(|col_id| data_row.get(col_id));
+                    summary.push(fold, label.map(str::trim));
 
-                        assert_eq!(
-                            toolkit.max.get(&test_key),
-                            Some(&DeserializationType::NUMBER(1_f64))
-                        )
+                    for other in 0..k {
+                        // Validation file of the row fold, train file of every other fold
+                        let writer = 2 * other + usize::from(other == fold);
+                        writers[writer].write_record(&data_row)?;
                     }
-                    Err(e) => assert!(false, "{e}"),
+                }
+
+                for mut writer in writers {
+                    writer.flush()?;
                 }
             }
-            Err(e) => assert!(false, "Could not initiate CsvToolkit!\n{e}"),
         }
+
+        Ok(summary)
+    }
+
+    /// Resample the rows to change the class distribution of the `label` column.
+    ///
+    /// SMOTE interpolates the numeric columns of `types` (except the label); distances between rows
+    /// are measured in standard deviations of every column. Other columns of a synthetic row are
+    /// copied from the row it is based on. Rows without a label are kept as they are. Statistics
+    /// are recomputed afterwards.
+    ///
+    /// The data is read twice; SMOTE keeps the numeric values of the oversampled classes in memory.
+    /// New rows are written right after the row they are based on.
+    ///
+    /// # Arguments
+    ///
+    /// * `label` - Column of the classes.
+    /// * `method` - Resampling method.
+    /// * `ratio` - Target ratio of the smallest to the largest class, in `(0, 1]` (`1` for equal classes).
+    /// * `seed` - Seed of the sampling, the same seed gives the same rows.
+    ///
+    /// # Return
+    /// Number of rows of every class after resampling.
+    ///
+    /// # Errors
+    ///
+    /// Unknown column, ratio out of range, io;
+    pub fn balance(
+        &mut self,
+        label: &str,
+        method: ResamplingMethod,
+        ratio: f64,
+        seed: u64,
+    ) -> Result<BTreeMap<String, usize>> {
+        let plan = self.column_plan(HashMap::from([(label.to_owned(), ())]))?;
+        let label_id = plan.into_keys().next().unwrap_or_default();
+
+        let features: Vec<usize> = (0..self.types.len())
+            .filter(|col_id| *col_id != label_id && self.types[*col_id].is_numeric())
+            .collect();
+        let scales: Vec<f64> = features
+            .iter()
+            .map(|col_id| {
+                self.statistics
+                    .get(*col_id)
+                    .and_then(|statistics| statistics.std())
+                    .filter(|std| *std > 0.)
+                    .unwrap_or(1.)
+            })
+            .collect();
+
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+
+        self.reset_reader()?;
+        for data_row in self.reader.records() {
+            let data_row = data_row?;
+            let class = data_row.get(label_id).unwrap_or_default().trim();
+            if !self.parse_options.is_null(label, class) {
+                *counts.entry(class.to_owned()).or_default() += 1;
+            }
+        }
+
+        let targets = method.targets(&counts, ratio)?;
+
+        // Features are only needed for the classes which get synthetic rows
+        let mut class_rows: HashMap<String, Vec<Features>> = HashMap::new();
+        if let ResamplingMethod::Smote { .. } = method {
+            self.reset_reader()?;
+            for data_row in self.reader.records() {
+                let data_row = data_row?;
+                let class = data_row.get(label_id).unwrap_or_default().trim();
+                if self.parse_options.is_null(label, class) || targets[class] <= counts[class] {
+                    continue;
+                }
+
+                let row_features = features
+                    .iter()
+                    .map(|col_id| {
+                        let value = data_row.get(*col_id)?;
+                        let value = self.parse_options.parse_cell(&self.headers[*col_id], value);
+                        value.ok()?.as_f64()
+                    })
+                    .collect();
+                class_rows
+                    .entry(class.to_owned())
+                    .or_default()
+                    .push(row_features);
+            }
+        }
+        let mut rng = SplitMix64::new(seed);
+
+        // Plans per class, by the index of the row among the rows of its class
+        let mut samplers: HashMap<String, SequentialSampler> = HashMap::new();
+        let mut copies: HashMap<String, HashMap<usize, usize>> = HashMap::new();
+        let mut synthetic: HashMap<String, HashMap<usize, Vec<Features>>> = HashMap::new();
+
+        for (class, count) in counts.iter() {
+            let target = targets[class];
+            match method {
+                ResamplingMethod::RandomUnder => {
+                    let sizes = vec![target, count - target];
+                    samplers.insert(class.clone(), SequentialSampler::new(sizes));
+                }
+                ResamplingMethod::RandomOver => {
+                    let class_copies = copies.entry(class.clone()).or_default();
+                    for _ in *count..target {
+                        *class_copies.entry(rng.below(*count)).or_default() += 1;
+                    }
+                }
+                ResamplingMethod::Smote { k } => {
+                    let rows = class_rows.remove(class).unwrap_or_default();
+                    let new_rows = resampling::smote(&rows, &scales, k, target - count, &mut rng);
+                    synthetic.insert(class.clone(), new_rows);
+                }
+            }
+        }
+
+        let options = self.parse_options.clone();
+        let types = self.types.clone();
+        let mut ranks: HashMap<String, usize> = HashMap::new();
+
+        self.expand_records(self.headers.clone(), |_row_id, row| {
+            let class = row
+                .get(label_id)
+                .map(|class| class.trim().to_owned())
+                .unwrap_or_default();
+            if options.is_null(label, &class) {
+                return Ok(vec![row]);
+            }
+
+            let rank = ranks.entry(class.clone()).or_default();
+            let row_rank = *rank;
+            *rank += 1;
+
+            let rows = match method {
+                ResamplingMethod::RandomUnder => {
+                    let sampler = samplers.get_mut(&class);
+                    match sampler.and_then(|sampler| sampler.next(&mut rng)) {
+                        Some(0) => vec![row],
+                        _ => Vec::new(),
+                    }
+                }
+                ResamplingMethod::RandomOver => {
+                    let n = copies
+                        .get(&class)
+                        .and_then(|class_copies| class_copies.get(&row_rank))
+                        .copied()
+                        .unwrap_or_default();
+                    vec![row; n + 1]
+                }
+                ResamplingMethod::Smote { .. } => {
+                    let new_rows = synthetic
+                        .get_mut(&class)
+                        .and_then(|class_rows| class_rows.remove(&row_rank))
+                        .unwrap_or_default();
+
+                    let mut rows = Vec::with_capacity(new_rows.len() + 1);
+                    for row_features in new_rows {
+                        let mut new_row = row.clone();
+                        for (col_id, x) in features.iter().zip(row_features) {
+                            let (Some(x), Some(cell)) = (x, new_row.get_mut(*col_id)) else {
+                                continue;
+                            };
+                            *cell = match types[*col_id] {
+                                DeserializationType::INTEGER(_) => (x.round() as i64).to_string(),
+                                _ => x.to_string(),
+                            };
+                        }
+                        rows.push(new_row);
+                    }
+                    rows.insert(0, row);
+                    rows
+                }
+            };
+
+            Ok(rows)
+        })?;
+
+        Ok(targets)
+    }
+
+    /// Find repeated rows in a single streaming pass. Rows are looked up by hash and compared with
+    /// the first row of the matching group (see `RowGroups`).
+    ///
+    /// Rows are exact duplicates if every value is the same. With a `key` column, rows with the
+    /// same key are duplicates as well and a key whose rows differ is reported as a conflict (the
+    /// rows of conflicting keys are read once more to find the differing columns).
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - Column identifying a record, e.g. `Patient ID`.
+    ///
+    /// # Errors
+    ///
+    /// Unknown column, io;
+    pub fn find_duplicates(&mut self, key: Option<&str>) -> Result<DuplicateReport> {
+        let key_id = self.key_column(key)?;
+        let mut report = DuplicateReport::default();
+
+        let mut seen_rows = self.row_groups(None)?;
+        let mut seen_keys = match key_id {
+            Some(key_id) => Some(self.row_groups(Some(key_id))?),
+            None => None,
+        };
+        // First row of a key to the rows of the key which differ from it
+        let mut conflicts: HashMap<usize, Vec<usize>> = HashMap::new();
+
+        self.reset_reader()?;
+        for (row_id, it) in self.reader.records().enumerate() {
+            let data_row = it?;
+
+            if let Some((first, _)) = seen_rows.insert(row_id, &data_row)? {
+                report.rows.push(Duplicate { row: row_id, first });
+            }
+
+            let Some(seen_keys) = seen_keys.as_mut() else {
+                continue;
+            };
+
+            if let Some((first, first_row)) = seen_keys.insert(row_id, &data_row)? {
+                report.keys.push(Duplicate { row: row_id, first });
+                if !same_cells(&first_row, &data_row) {
+                    conflicts
+                        .entry(first)
+                        .or_insert_with(|| vec![first])
+                        .push(row_id);
+                }
+            }
+        }
+
+        if conflicts.is_empty() {
+            return Ok(report);
+        }
+
+        let conflicting: HashSet<usize> = conflicts.values().flatten().copied().collect();
+        let mut rows: HashMap<usize, csv::StringRecord> = HashMap::new();
+
+        self.reset_reader()?;
+        for (row_id, it) in self.reader.records().enumerate() {
+            let data_row = it?;
+            if conflicting.contains(&row_id) {
+                rows.insert(row_id, data_row);
+            }
+        }
+
+        let mut groups: Vec<Vec<usize>> = conflicts.into_values().collect();
+        groups.sort();
+
+        for group in groups {
+            let first = &rows[&group[0]];
+            let columns = self
+                .headers
+                .iter()
+                .enumerate()
+                .filter(|(col_id, _)| {
+                    group.iter().any(|row_id| {
+                        rows[row_id].get(*col_id).map(str::trim)
+                            != first.get(*col_id).map(str::trim)
+                    })
+                })
+                .map(|(_, header)| header.clone())
+                .collect();
+
+            report.conflicts.push(Conflict {
+                key: key_id
+                    .and_then(|key_id| first.get(key_id))
+                    .unwrap_or_default()
+                    .trim()
+                    .to_owned(),
+                rows: group,
+                columns,
+            });
+        }
+
+        Ok(report)
+    }
+
+    /// Remove repeated rows (see `find_duplicates`). Statistics are recomputed afterwards.
+    ///
+    /// Groups are found in a single pass, which records the kept rows: the rewrite (and the merge
+    /// pass of `KeepPolicy::Merge`) only looks them up.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - Column identifying a record. Whole rows are compared if `None`.
+    /// * `keep` - Which row of every group of duplicates is kept.
+    ///
+    /// # Return
+    /// Number of removed rows.
+    ///
+    /// # Errors
+    ///
+    /// Unknown column, io;
+    pub fn deduplicate(&mut self, key: Option<&str>, keep: KeepPolicy) -> Result<usize> {
+        let key_id = self.key_column(key)?;
+        let mut row_groups = self.row_groups(key_id)?;
+
+        // Last row and size of every group by its first row, and the first row of every duplicate
+        let mut groups: HashMap<usize, (usize, usize)> = HashMap::new();
+        let mut duplicates: HashMap<usize, usize> = HashMap::new();
+
+        self.reset_reader()?;
+        for (row_id, it) in self.reader.records().enumerate() {
+            let first = match row_groups.insert(row_id, &it?)? {
+                Some((first, _)) => {
+                    duplicates.insert(row_id, first);
+                    first
+                }
+                None => row_id,
+            };
+            let group = groups.entry(first).or_insert((row_id, 0));
+            group.0 = row_id;
+            group.1 += 1;
+        }
+
+        let mut kept = RowSet::default();
+        for (first, (last, _)) in groups.iter() {
+            kept.insert(match keep {
+                KeepPolicy::First | KeepPolicy::Merge => *first,
+                KeepPolicy::Last => *last,
+            });
+        }
+
+        // Merged rows of the groups of duplicates (only when the rows could differ)
+        let mut merged: HashMap<usize, Vec<String>> = HashMap::new();
+        if keep == KeepPolicy::Merge && key_id.is_some() && !duplicates.is_empty() {
+            self.reset_reader()?;
+            for (row_id, it) in self.reader.records().enumerate() {
+                let data_row = it?;
+                let first = duplicates.get(&row_id).copied().unwrap_or(row_id);
+                if groups[&first].1 < 2 {
+                    continue;
+                }
+
+                let row = merged.entry(first).or_default();
+                for (col_id, value) in data_row.iter().enumerate() {
+                    if row.len() <= col_id {
+                        row.resize(col_id + 1, String::default());
+                    }
+
+                    let header = self.headers.get(col_id).map_or("", |h| h.as_str());
+                    if self.parse_options.is_null(header, &row[col_id]) {
+                        row[col_id] = value.to_owned();
+                    }
+                }
+            }
+        }
+
+        let mut removed = 0;
+        self.rewrite_records(self.headers.clone(), |row_id, row| {
+            if !kept.contains(row_id) {
+                removed += 1;
+                return Ok(None);
+            }
+
+            // The kept row of a merged group is its first one
+            Ok(Some(merged.remove(&row_id).unwrap_or(row)))
+        })?;
+
+        Ok(removed)
+    }
+
+    /// Fill gaps (see `gaps`) of the passed columns according to the chosen strategies.
+    ///
+    /// Statistics based strategies (mean, median, mode, back-fill) are computed in a separate pass
+    /// before the data is rewritten to the temporary file. Gaps which could not be filled (e.g. a
+    /// leading gap with `FillStrategy::ForwardFill`) are left empty. Statistics are recomputed afterwards.
+    ///
+    /// # Arguments
+    ///
+    /// * `strategies` - Map of column names (as in `headers`) to the fill strategy of the column.
+    ///
+    /// # Errors
+    ///
+    /// Unknown column, non numeric column for mean/median, io;
+    pub fn fill_gaps(&mut self, strategies: HashMap<String, FillStrategy>) -> Result<()> {
+        let plan = self.column_plan(strategies)?;
+
+        if self.gaps.is_empty() || plan.is_empty() {
+            return Ok(());
+        }
+
+        let strategies = plan
+            .into_iter()
+            .map(|(col_id, strategy)| (self.headers[col_id].clone(), strategy))
+            .collect();
+        self.pipeline().fill_gaps(strategies).run().map(|_| ())
+    }
+
+    /// Rewrite the values recorded in `outliers` (see `postprocessing`).
+    ///
+    /// Only outliers of the listed columns are treated. `outliers` and `fences` are cleared
+    /// afterwards since row indexes could be shifted by dropped rows; statistics and `gaps` are
+    /// recomputed.
+    ///
+    /// # Arguments
+    ///
+    /// * `treatments` - Map of column names (as in `headers`) to the treatment of the column outliers.
+    ///
+    /// # Errors
+    ///
+    /// Unknown column, non numeric column for winsorizing, io;
+    pub fn treat_outliers(&mut self, treatments: HashMap<String, OutlierTreatment>) -> Result<()> {
+        let plan = self.column_plan(treatments)?;
+
+        if self.outliers.is_empty() || plan.is_empty() {
+            return Ok(());
+        }
+
+        // Percentile bounds of winsorized columns
+        let mut limits: HashMap<usize, (f64, f64)> = HashMap::new();
+        if plan
+            .values()
+            .any(|t| matches!(t, OutlierTreatment::Winsorize { .. }))
+        {
+            let samples = self.numeric_samples(|col_id| {
+                matches!(plan.get(&col_id), Some(OutlierTreatment::Winsorize { .. }))
+            })?;
+
+            for (col_id, mut values) in samples {
+                if let Some(OutlierTreatment::Winsorize { lower, upper }) = plan.get(&col_id) {
+                    values.sort_by(|a, b| a.total_cmp(b));
+                    if let (Some(lo), Some(hi)) = (
+                        quantile(&values, lower / 100_f64),
+                        quantile(&values, upper / 100_f64),
+                    ) {
+                        limits.insert(col_id, (lo, hi));
+                    }
+                }
+            }
+        }
+
+        let outliers = std::mem::take(&mut self.outliers);
+        let fences = std::mem::take(&mut self.fences);
+        let options = self.parse_options.clone();
+        let headers = self.headers.clone();
+
+        self.rewrite_records(self.headers.clone(), |row_id, mut row| {
+            for (col_id, treatment) in plan.iter() {
+                if !outliers.contains(row_id, *col_id) {
+                    continue;
+                }
+                let Some(cell) = row.get_mut(*col_id) else {
+                    continue;
+                };
+
+                match treatment {
+                    OutlierTreatment::Clip => {
+                        let outlier = options
+                            .parse_cell(&headers[*col_id], cell)?
+                            .as_f64()
+                            .and_then(|x| fences.get(col_id)?.check(x));
+                        if let Some(outlier) = outlier {
+                            *cell = outlier.bound.to_cell_string();
+                        }
+                    }
+                    OutlierTreatment::Winsorize { .. } => {
+                        if let (Some((lo, hi)), Some(x)) = (
+                            limits.get(col_id),
+                            options.parse_cell(&headers[*col_id], cell)?.as_f64(),
+                        ) {
+                            *cell = x.clamp(*lo, *hi).to_string();
+                        }
+                    }
+                    OutlierTreatment::ToGap => cell.clear(),
+                    OutlierTreatment::DropRow => return Ok(None),
+                }
+            }
+
+            Ok(Some(row))
+        })
+    }
+
+    /// Create a column (or overwrite an existing one) with a constant or computed value.
+    ///
+    /// New columns are appended after the last one. Expressions are evaluated against the row values
+    /// parsed through `ParseOptions::parse` (see `expression::Expression`).
+    ///
+    /// # Arguments
+    ///
+    /// * `header` - Name of the column to create or overwrite.
+    /// * `input` - `UserInput::VALUE` for a constant, `UserInput::EXPR` for a computed column.
+    ///
+    /// # Errors
+    ///
+    /// Invalid expression, evaluation errors, io;
+    pub fn derive_column(&mut self, header: String, input: UserInput) -> Result<()> {
+        let expr = match &input {
+            UserInput::EXPR(src) => Some(Expression::parse(src, &self.headers)?),
+            UserInput::VALUE(_) => None,
+        };
+
+        let options = self.parse_options.clone();
+        let source_headers = self.headers.clone();
+        let mut headers = self.headers.clone();
+        let col_id = match headers.iter().position(|h| *h == header) {
+            Some(col_id) => col_id,
+            None => {
+                headers.push(header);
+                headers.len() - 1
+            }
+        };
+
+        self.rewrite_records(headers, |_row_id, mut row| {
+            let value = match (&expr, &input) {
+                (Some(expr), _) => expr
+                    .evaluate(&options.parse_row(&source_headers, &row)?)?
+                    .to_cell_string(),
+                (None, UserInput::VALUE(value)) => value.clone(),
+                (None, UserInput::EXPR(_)) => String::default(),
+            };
+
+            if row.len() <= col_id {
+                row.resize(col_id + 1, String::default());
+            }
+            row[col_id] = value;
+
+            Ok(Some(row))
+        })
+    }
+
+    fn preprocessing(&mut self) -> Result<()> {
+        self.clear_statistics();
+
+        let scanner = self.row_scanner();
+        scanner.for_each(
+            |_| true,
+            |row_id, record, values| {
+                self.accumulate_row(row_id, record, values);
+                Ok(())
+            },
+        )?;
+
+        self.finish_statistics();
+
+        Ok(())
+    }
+
+    /// Reset everything `preprocessing` computes, before the first data row.
+    fn clear_statistics(&mut self) {
+        self.min.clear();
+        self.max.clear();
+        self.categories.clear();
+        self.types.clear();
+        self.type_reports.clear();
+        self.gaps.clear();
+        self.violations.clear();
+        self.statistics.clear();
+        self.statistics
+            .resize(self.headers.len(), ColumnStatistics::default());
+    }
+
+    /// Complete what `preprocessing` computes, after the last data row.
+    fn finish_statistics(&mut self) {
+        // Declared types win over the inferred ones, even if some values do not conform
+        if let Some(schema) = &self.schema {
+            for (col_id, header) in self.headers.iter().enumerate() {
+                let declared = schema.get(header).and_then(|spec| spec.column_type);
+                if let (Some(column_type), Some(slot)) = (declared, self.types.get_mut(col_id)) {
+                    *slot = column_type.placeholder();
+                }
+            }
+        }
+
+        // Category tables are kept for STRING and BOOLEAN columns only
+        let types = &self.types;
+        let headers = &self.headers;
+        self.categories.retain(|header, _| {
+            headers
+                .iter()
+                .position(|h| h == header)
+                .is_some_and(|col_id| {
+                    matches!(
+                        types.get(col_id),
+                        Some(DeserializationType::STRING(_) | DeserializationType::BOOLEAN(_))
+                    )
+                })
+        });
+    }
+
+    /// Add a data row to the statistics, types, extrema, categories, gaps and violations (see
+    /// `preprocessing`).
+    ///
+    /// # Arguments
+    ///
+    /// * `row_id` - The index of the data row.
+    /// * `record` - Raw values of the row.
+    /// * `values` - Parsed values of the row, one per header at most.
+    ///
+    fn accumulate_row(
+        &mut self,
+        row_id: usize,
+        record: &StringRecord,
+        values: &[DeserializationType],
+    ) {
+        for ((col_id, header), (variable, var)) in self
+            .headers
+            .iter()
+            .enumerate()
+            .zip(record.iter().zip(values))
+        {
+            let variable = variable.trim();
+            self.statistics[col_id].push(variable, var);
+
+            if *var != DeserializationType::EMPTY {
+                self.categories
+                    .entry(header.to_owned())
+                    .or_default()
+                    .push(variable);
+            }
+
+            if let Some(schema) = &self.schema {
+                self.violations
+                    .extend(schema.validate(row_id, header, variable, var));
+            }
+
+            Self::check_or_insert_column_type(
+                &mut self.types,
+                &mut self.type_reports,
+                row_id,
+                col_id,
+                var,
+            );
+
+            match var {
+                _ if var.is_ordered() => {
+                    // Values of a mixed column which could not be compared with the first
+                    // ordered value are skipped (see `type_reports`)
+                    let min = match self.min.get(header) {
+                        Some(curr) => curr.clone().try_min(var.clone()).ok(),
+                        None => Some(var.clone()),
+                    };
+                    if let Some(min) = min {
+                        self.min.insert(header.to_owned(), min);
+                    }
+
+                    let max = match self.max.get(header) {
+                        Some(curr) => curr.clone().try_max(var.clone()).ok(),
+                        None => Some(var.clone()),
+                    };
+                    if let Some(max) = max {
+                        self.max.insert(header.to_owned(), max);
+                    }
+                }
+                DeserializationType::EMPTY => {
+                    self.gaps.insert(row_id, col_id);
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    /// First pass of `split` and `k_fold`: prepare the output of every row.
+    ///
+    /// # Return
+    /// The assignment and the index of the column it is keyed by (`None` for `SplitStrategy::Random`).
+    fn row_assignment(
+        &mut self,
+        ratios: &[f64],
+        strategy: &SplitStrategy,
+        rng: &mut SplitMix64,
+    ) -> Result<(Assignment, Option<usize>)> {
+        let key_column = match strategy {
+            SplitStrategy::Random => None,
+            SplitStrategy::Stratified { label: column }
+            | SplitStrategy::Grouped { group: column }
+            | SplitStrategy::TimeOrdered { column } => {
+                let plan = self.column_plan(HashMap::from([(column.clone(), ())]))?;
+                plan.into_keys().next()
+            }
+        };
+        let key_of = |row: &csv::StringRecord| -> String {
+            key_column
+                .and_then(|col_id| row.get(col_id))
+                .unwrap_or_default()
+                .trim()
+                .to_owned()
+        };
+
+        self.reset_reader()?;
+        let assignment = match strategy {
+            SplitStrategy::TimeOrdered { column } => {
+                let mut keys = Vec::new();
+                for data_row in self.reader.records() {
+                    keys.push(self.parse_options.parse_cell(column, &key_of(&data_row?))?);
+                }
+                Assignment::by_order(keys, ratios).map_err(|e| e.in_column(column.as_str()))?
+            }
+            _ => {
+                let mut counts: HashMap<String, usize> = HashMap::new();
+                for data_row in self.reader.records() {
+                    *counts.entry(key_of(&data_row?)).or_default() += 1;
+                }
+
+                let grouped = matches!(strategy, SplitStrategy::Grouped { .. });
+                Assignment::by_keys(counts, ratios, grouped, rng)?
+            }
+        };
+
+        Ok((assignment, key_column))
+    }
+
+    /// Empty `RowGroups` of the current data file.
+    fn row_groups(&self, key_id: Option<usize>) -> Result<RowGroups> {
+        let reader = csv_reader(
+            &self.data_file,
+            &self.dialect,
+            self.comment,
+            self.terminator,
+        )?;
+        Ok(RowGroups::new(reader, key_id))
+    }
+
+    /// Index of an optional key column.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the column does not exist.
+    fn key_column(&self, key: Option<&str>) -> Result<Option<usize>> {
+        match key {
+            Some(key) => Ok(self
+                .column_plan(HashMap::from([(key.to_owned(), ())]))?
+                .into_keys()
+                .next()),
+            None => Ok(None),
+        }
+    }
+
+    /// Resolve column names of a per-column specification to column indexes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the columns does not exist.
+    fn column_plan<T>(&self, spec: HashMap<String, T>) -> Result<HashMap<usize, T>> {
+        column_plan(&self.headers, spec)
+    }
+
+    /// Collect all non empty values of the selected columns.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the selected columns contains a non numeric value.
+    fn numeric_samples<F>(&self, selected: F) -> Result<HashMap<usize, Vec<f64>>>
+    where
+        F: Fn(usize) -> bool + Sync,
+    {
+        let mut samples: HashMap<usize, Vec<f64>> = HashMap::new();
+
+        self.row_scanner()
+            .for_each(&selected, |row_id, record, values| {
+                for (col_id, (value, parsed)) in record.iter().zip(values).enumerate() {
+                    if !selected(col_id) {
+                        continue;
+                    }
+
+                    match parsed {
+                        DeserializationType::EMPTY => continue,
+                        parsed if parsed.is_numeric() => samples
+                            .entry(col_id)
+                            .or_default()
+                            .push(parsed.as_f64().unwrap_or_default()),
+                        other => {
+                            return Err(Error::type_mismatch("number", other.type_name())
+                                .at_row(row_id)
+                                .in_column(self.headers[col_id].as_str())
+                                .with_value(value))
+                        }
+                    }
+                }
+
+                Ok(())
+            })?;
+
+        Ok(samples)
+    }
+
+    /// Rewrite every numeric value of the columns of the `scaler` with `map`.
+    fn map_numeric_columns<F>(&mut self, scaler: &Scaler, map: F) -> Result<()>
+    where
+        F: Fn(&Normalization, f64) -> Result<f64>,
+    {
+        let plan = self.column_plan(scaler.columns.clone().into_iter().collect())?;
+        let options = self.parse_options.clone();
+        let headers = self.headers.clone();
+
+        self.rewrite_records(self.headers.clone(), |_row_id, mut row| {
+            for (col_id, string_value) in row.iter_mut().enumerate() {
+                if let Some(normalization) = plan.get(&col_id) {
+                    if let Some(x) = options.parse_cell(&headers[col_id], string_value)?.as_f64() {
+                        *string_value = map(normalization, x)?.to_string();
+                    }
+                }
+            }
+
+            Ok(Some(row))
+        })
+    }
+
+    /// Stream every data row through `transform` into the temporary file and switch the `reader` to it.
+    ///
+    /// The data is written to a staging file first and moved over `tmp_file` afterwards, so the
+    /// `reader` never reads the file which is being written. Statistics are computed from the
+    /// rows as they are written.
+    ///
+    /// # Arguments
+    ///
+    /// * `headers` - Headers of the rewritten data. Replace `headers` once the data is written.
+    /// * `transform` - Takes the row index and the row values. Returns the row to write or `None` to drop the row.
+    ///
+    fn rewrite_records<F>(&mut self, headers: Vec<String>, mut transform: F) -> Result<()>
+    where
+        F: FnMut(usize, Vec<String>) -> Result<Option<Vec<String>>>,
+    {
+        self.expand_records(headers, |row_id, row| {
+            Ok(transform(row_id, row)?.into_iter().collect())
+        })
+    }
+
+    /// Same as `rewrite_records`, but every row could be replaced with any number of rows.
+    ///
+    /// # Arguments
+    ///
+    /// * `headers` - Headers of the rewritten data. Replace `headers` once the data is written.
+    /// * `transform` - Takes the row index and the row values. Returns the rows to write in its place.
+    ///
+    fn expand_records<F>(&mut self, headers: Vec<String>, mut transform: F) -> Result<()>
+    where
+        F: FnMut(usize, Vec<String>) -> Result<Vec<Vec<String>>>,
+    {
+        let scanner = self.row_scanner();
+
+        let staging = self.tmp_file.with_extension("swp");
+        let mut writer = self.csv_writer(staging.as_path(), None)?;
+
+        // Write CSV headers
+        writer.write_record(&headers)?;
+
+        let source_headers = std::mem::replace(&mut self.headers, headers);
+        self.clear_statistics();
+
+        let mut written = 0;
+        let result = scanner
+            .for_each(
+                |_| false,
+                |row_id, record, _| {
+                    let row: Vec<String> = record.iter().map(|s| s.to_owned()).collect();
+
+                    for row in transform(row_id, row)? {
+                        writer.write_record(&row)?;
+                        self.accumulate_written_row(written, row)?;
+                        written += 1;
+                    }
+
+                    Ok(())
+                },
+            )
+            .and_then(|_| Ok(writer.flush()?));
+        drop(writer);
+
+        // The data is left as it was, and so are its statistics
+        if let Err(e) = result {
+            let _ = fs::remove_file(staging.as_path());
+            self.headers = source_headers;
+            self.preprocessing()?;
+            return Err(e);
+        }
+
+        self.finish_statistics();
+        fs::rename(staging.as_path(), self.tmp_file.as_path())?;
+
+        self.switch_reader_to_tmp_file()
+    }
+
+    /// Add a row written by `expand_records` to the statistics, as it will be read back.
+    fn accumulate_written_row(&mut self, row_id: usize, row: Vec<String>) -> Result<()> {
+        let mut record = StringRecord::from(row);
+        // An empty row is written as a single empty field
+        if record.is_empty() {
+            record.push_field("");
+        }
+        record.trim();
+
+        let values = self
+            .headers
+            .iter()
+            .zip(record.iter())
+            .map(|(header, value)| self.parse_options.parse_cell(header, value))
+            .collect::<Result<Vec<_>>>()
+            .map_err(|e| e.at_row(row_id))?;
+        self.accumulate_row(row_id, &record, &values);
+
+        Ok(())
+    }
+
+    /// Write every data row to a new file and sync it to the disk.
+    fn write_data(&mut self, path: &Path, options: &SaveOptions) -> Result<()> {
+        self.reset_reader()?;
+
+        let mut writer = self.csv_writer(path, options.delimiter)?;
+
+        if options.headers {
+            writer.write_record(&self.headers)?;
+        }
+
+        for it in self.reader.records() {
+            writer.write_record(&it?)?;
+        }
+
+        let fh = writer.into_inner().map_err(|e| e.into_error())?;
+        fh.sync_all()?;
+
+        Ok(())
+    }
+
+    /// Create a `csv::Writer` with the dialect the toolkit was opened with.
+    ///
+    /// Fields are quoted only when necessary (delimiter, quote, line break or a leading comment
+    /// character), so unedited fields are written back as they were read.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the new file.
+    /// * `delimiter` - Field delimiter, the one of the source file if `None`.
+    ///
+    fn csv_writer(&self, path: &Path, delimiter: Option<u8>) -> Result<Writer<File>> {
+        Ok(WriterBuilder::new()
+            .delimiter(delimiter.unwrap_or(self.dialect.delimiter))
+            .terminator(self.dialect.terminator.into())
+            .flexible(true)
+            .quote_style(QuoteStyle::Necessary)
+            // Without an escape character a quote could only be written doubled
+            .quote(self.dialect.quote)
+            .double_quote(self.dialect.double_quotes || self.dialect.escape.is_none())
+            .escape(self.dialect.escape.unwrap_or(b'\\'))
+            .comment(self.comment)
+            .from_path(path)?)
+    }
+
+    /// Reader of the data for the passes which may run on several threads (see `set_parallelism`).
+    fn row_scanner(&self) -> RowScanner {
+        RowScanner {
+            path: self.data_file.clone(),
+            data_position: self.data_position.clone(),
+            dialect: self.dialect.clone(),
+            comment: self.comment,
+            terminator: self.terminator,
+            headers: self.headers.clone(),
+            options: self.parse_options.clone(),
+            parallelism: self.parallelism,
+        }
+    }
+
+    /// Reset seek position for inner `reader` (csv::Reader) instance to be able to read src file one more.
+    ///
+    fn reset_reader(&mut self) -> std::result::Result<(), csv::Error> {
+        self.reader.seek(self.data_position.clone())
+    }
+
+    /// Switch the `reader` (csv::Reader) obj to the temporary file.
+    ///
+    /// This method should call after all types of source data mutation!
+    ///
+    fn switch_reader_to_tmp_file(&mut self) -> Result<()> {
+        // Quotes of the temporary file are doubled when there is no escape character (see `csv_writer`)
+        self.dialect.double_quotes |= self.dialect.escape.is_none();
+        self.reader = csv_reader(
+            self.tmp_file.as_path(),
+            &self.dialect,
+            self.comment,
+            self.terminator,
+        )?;
+
+        // Header line of the temporary file could differ from the source one
+        self.reader.headers()?;
+        self.data_position = self.reader.position().clone();
+        self.data_file = self.tmp_file.clone();
+
+        Ok(())
+    }
+
+    /// Reconcile the type of a column with a new value of the column. Add one if absent.
+    ///
+    /// Column types never conflict: they are widened along the type lattice (see
+    /// `DeserializationType::widen`), e.g. an INTEGER column with a single FLOAT value becomes FLOAT and
+    /// a numeric column with a single word becomes STRING. Every value is registered in the column
+    /// `TypeReport` to find the rows with minority types afterwards.
+    ///
+    /// # Arguments
+    ///
+    /// * `types` - A mutable reference to the current instance of `types` vector.
+    /// * `reports` - A mutable reference to the current instance of `type_reports` vector.
+    /// * `row_id` - The index of the data row.
+    /// * `column_id` - The index of the column to check.
+    /// * `column_value` - A reference to the value whose type will be checked.
+    ///
+    fn check_or_insert_column_type(
+        types: &mut Vec<DeserializationType>,
+        reports: &mut Vec<TypeReport>,
+        row_id: usize,
+        column_id: usize,
+        column_value: &DeserializationType,
+    ) {
+        if types.len() <= column_id {
+            types.resize(column_id + 1, DeserializationType::EMPTY);
+            reports.resize(column_id + 1, TypeReport::default());
+        }
+
+        types[column_id] = types[column_id].widen(column_value);
+        reports[column_id].push(row_id, column_value);
+    }
+}
+
+/// Resolve column names of a per-column specification to their indexes in `headers`.
+///
+/// # Errors
+/// Unknown column;
+pub(crate) fn column_plan<T>(
+    headers: &[String],
+    spec: impl IntoIterator<Item = (String, T)>,
+) -> Result<HashMap<usize, T>> {
+    let mut plan: HashMap<usize, T> = HashMap::new();
+    for (header, item) in spec {
+        match headers.iter().position(|h| *h == header) {
+            Some(col_id) => {
+                plan.insert(col_id, item);
+            }
+            None => return Err(Error::invalid_column(header, "Column does not exist")),
+        }
+    }
+
+    Ok(plan)
+}
+
+/// Create a csv reader of a file of the given dialect.
+///
+/// Fields are read as they are written, so unedited fields are written back unchanged: values are
+/// trimmed where they are interpreted (`ParseOptions::parse_cell`, statistics, keys and labels).
+pub(crate) fn csv_reader(
+    path: &Path,
+    dialect: &Dialect,
+    comment: Option<u8>,
+    terminator: Terminator,
+) -> std::result::Result<Reader<File>, csv::Error> {
+    reader_builder(dialect, comment, terminator).from_path(path)
+}
+
+/// Reader settings shared by `csv_reader` and the workers of parallel passes.
+pub(crate) fn reader_builder(
+    dialect: &Dialect,
+    comment: Option<u8>,
+    terminator: Terminator,
+) -> ReaderBuilder {
+    let mut builder = ReaderBuilder::new();
+    builder
+        .terminator(terminator)
+        .flexible(true)
+        .quote(dialect.quote)
+        .escape(dialect.escape)
+        .double_quote(dialect.double_quotes)
+        .comment(comment)
+        .delimiter(dialect.delimiter);
+    builder
+}
+
+/// Intermediate files live in the scratch directory of the toolkit, only `save_as` keeps the data.
+impl Drop for CsvToolkit {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(self.scratch_dir.as_path());
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+
+    use super::*;
+    use dialect::{LineTerminator, TextEncoding};
+    use std::fs;
+    use std::path::Path;
+
+    #[allow(clippy::needless_return)]
+    fn init() -> Result<CsvToolkit> {
+        return CsvToolkit::new(Path::new("./tests/test.csv"), b',', None, false, None, None);
+    }
+
+    fn init_with_gaps() -> Result<CsvToolkit> {
+        CsvToolkit::new(Path::new("./tests/gaps.csv"), b',', None, false, None, None)
+    }
+
+    fn column_values(path: &Path, col_id: usize) -> Vec<String> {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .skip(1)
+            .map(|line| line.split(',').nth(col_id).unwrap_or_default().to_owned())
+            .collect()
+    }
+
+    #[test]
+    #[allow(clippy::assertions_on_constants)]
+    pub fn initialize_test() {
+        match init() {
+            Ok(_) => assert!(true),
+            Err(e) => assert!(false, "Could not initiate CsvToolkit!\n{e}"),
+        }
+    }
+
+    #[test]
+    #[allow(clippy::assertions_on_constants)]
+    pub fn test_min_max() {
+        match init() {
+            Ok(toolkit) => {
+                let test_key = String::from("Exercise Hours Per Week");
+
+                assert_eq!(
+                    toolkit.min.get(&test_key),
+                    Some(&DeserializationType::FLOAT(0.1945150606299495))
+                );
+
+                assert_eq!(
+                    toolkit.max.get(&test_key),
+                    Some(&DeserializationType::FLOAT(19.633268156072297))
+                )
+            }
+            Err(e) => assert!(false, "Could not initiate CsvToolkit!\n{e}"),
+        }
+    }
+
+    #[test]
+    #[allow(clippy::assertions_on_constants)]
+    pub fn test_normalization() {
+        match init() {
+            Ok(mut toolkit) => {
+                let test_key = String::from("Exercise Hours Per Week");
+
+                let methods = HashMap::from([(test_key.clone(), NormalizationMethod::MinMax)]);
+
+                match toolkit.normalizing(methods) {
+                    Ok(_) => {
+                        assert_eq!(
+                            toolkit.min.get(&test_key),
+                            Some(&DeserializationType::FLOAT(0_f64))
+                        );
+
+                        assert_eq!(
+                            toolkit.max.get(&test_key),
+                            Some(&DeserializationType::FLOAT(1_f64))
+                        )
+                    }
+                    Err(e) => assert!(false, "{e}"),
+                }
+            }
+            Err(e) => assert!(false, "Could not initiate CsvToolkit!\n{e}"),
+        }
+    }
+
+    #[test]
+    pub fn test_fill_gaps_statistics() {
+        let mut toolkit = init_with_gaps().expect("Could not initiate CsvToolkit!");
+        let tmp_file = toolkit.tmp_file.clone();
+
+        assert_eq!(toolkit.gaps.rows().len(), 4);
+
+        let strategies = HashMap::from([
+            ("age".to_owned(), FillStrategy::Mean),
+            ("score".to_owned(), FillStrategy::Median),
+            ("group".to_owned(), FillStrategy::Mode),
+        ]);
+        let result = toolkit.fill_gaps(strategies);
+        let ages = column_values(&tmp_file, 1);
+        let scores = column_values(&tmp_file, 2);
+        let groups = column_values(&tmp_file, 3);
+
+        assert!(result.is_ok(), "{:?}", result.err());
+        assert!(toolkit.gaps.is_empty());
+        assert_eq!(ages, vec!["30", "40", "50", "40", "40"]);
+        assert_eq!(scores, vec!["1.5", "2.5", "3.5", "4.5", "5.5"]);
+        assert_eq!(groups, vec!["a", "b", "a", "a", "a"]);
+    }
+
+    #[test]
+    pub fn test_fill_gaps_directional() {
+        let mut toolkit = init_with_gaps().expect("Could not initiate CsvToolkit!");
+        let tmp_file = toolkit.tmp_file.clone();
+
+        let strategies = HashMap::from([
+            ("age".to_owned(), FillStrategy::ForwardFill),
+            ("score".to_owned(), FillStrategy::BackFill),
+            (
+                "group".to_owned(),
+                FillStrategy::Input(UserInput::VALUE("unknown".to_owned())),
+            ),
+        ]);
+        let result = toolkit.fill_gaps(strategies);
+        let ages = column_values(&tmp_file, 1);
+        let scores = column_values(&tmp_file, 2);
+        let groups = column_values(&tmp_file, 3);
+
+        assert!(result.is_ok(), "{:?}", result.err());
+        assert_eq!(ages, vec!["30", "30", "50", "40", "40"]);
+        assert_eq!(scores, vec!["1.5", "2.5", "4.5", "4.5", "5.5"]);
+        assert_eq!(groups, vec!["a", "b", "a", "unknown", "a"]);
+    }
+
+    #[test]
+    pub fn test_fill_gaps_unknown_column() {
+        let mut toolkit = init_with_gaps().expect("Could not initiate CsvToolkit!");
+        let strategies = HashMap::from([("weight".to_owned(), FillStrategy::Mean)]);
+
+        assert!(toolkit.fill_gaps(strategies).is_err());
+    }
+
+    #[test]
+    pub fn test_fill_gaps_expression() {
+        let mut toolkit = init_with_gaps().expect("Could not initiate CsvToolkit!");
+        let tmp_file = toolkit.tmp_file.clone();
+
+        let strategies = HashMap::from([(
+            "score".to_owned(),
+            FillStrategy::Input(UserInput::EXPR("age / 10".to_owned())),
+        )]);
+        let result = toolkit.fill_gaps(strategies);
+        let scores = column_values(&tmp_file, 2);
+
+        assert!(result.is_ok(), "{:?}", result.err());
+        assert_eq!(scores, vec!["1.5", "2.5", "5", "4.5", "5.5"]);
+    }
+
+    #[test]
+    pub fn test_derive_column() {
+        let mut toolkit = init().expect("Could not initiate CsvToolkit!");
+        let tmp_file = toolkit.tmp_file.clone();
+
+        let result = toolkit.derive_column(
+            "Risk Label".to_owned(),
+            UserInput::EXPR(r#"if([Heart Attack Risk] == 1, "high", "low")"#.to_owned()),
+        );
+        let labels = column_values(&tmp_file, 26);
+
+        assert!(result.is_ok(), "{:?}", result.err());
+        assert_eq!(toolkit.headers.last(), Some(&"Risk Label".to_owned()));
+        assert_eq!(labels[0], "low");
+        assert_eq!(labels[5], "high");
+    }
+
+    #[test]
+    pub fn test_outliers() {
+        let mut toolkit = init().expect("Could not initiate CsvToolkit!");
+        let detectors = HashMap::from([
+            (
+                "Income".to_owned(),
+                OutlierDetector::ZScore { threshold: 1.5 },
+            ),
+            ("Age".to_owned(), OutlierDetector::Iqr { k: 1.5 }),
+        ]);
+
+        toolkit.postprocessing(detectors).unwrap();
+
+        // Income: 25086 (row 8) and 29886 (row 12) are far below the mean
+        assert_eq!(toolkit.outliers.rows(), vec![8, 12]);
+        assert!(toolkit.outliers.contains(8, 17));
+
+        let outlier = toolkit.fences[&17].check(25086.).unwrap();
+        assert!(outlier.score < -1.5);
+        assert!(outlier.bound > DeserializationType::INTEGER(25086));
+        assert!(toolkit.gaps.is_empty());
+    }
+
+    #[test]
+    pub fn test_outliers_non_numeric() {
+        let mut toolkit = init().expect("Could not initiate CsvToolkit!");
+        let detectors = HashMap::from([("Sex".to_owned(), OutlierDetector::Iqr { k: 1.5 })]);
+
+        assert!(toolkit.postprocessing(detectors).is_err());
+    }
+
+    fn detect_income_outliers(toolkit: &mut CsvToolkit) {
+        let detectors = HashMap::from([(
+            "Income".to_owned(),
+            OutlierDetector::ZScore { threshold: 1.5 },
+        )]);
+        toolkit.postprocessing(detectors).unwrap();
+    }
+
+    #[test]
+    pub fn test_treat_outliers_clip() {
+        let mut toolkit = init().expect("Could not initiate CsvToolkit!");
+        detect_income_outliers(&mut toolkit);
+        let bound = toolkit.fences[&17].check(25086.).unwrap().bound;
+
+        let treatments = HashMap::from([("Income".to_owned(), OutlierTreatment::Clip)]);
+        let result = toolkit.treat_outliers(treatments);
+
+        assert!(result.is_ok(), "{:?}", result.err());
+        assert!(toolkit.outliers.is_empty());
+        assert_eq!(toolkit.min.get("Income"), Some(&bound));
+    }
+
+    #[test]
+    pub fn test_treat_outliers_winsorize() {
+        let mut toolkit = init().expect("Could not initiate CsvToolkit!");
+        let tmp_file = toolkit.tmp_file.clone();
+        detect_income_outliers(&mut toolkit);
+
+        let treatments = HashMap::from([(
+            "Income".to_owned(),
+            OutlierTreatment::Winsorize {
+                lower: 10_f64,
+                upper: 90_f64,
+            },
+        )]);
+        let result = toolkit.treat_outliers(treatments);
+        let incomes = column_values(&tmp_file, 17);
+
+        assert!(result.is_ok(), "{:?}", result.err());
+        assert_eq!(incomes[8], incomes[12]);
+        assert_eq!(incomes[0], "261404");
+    }
+
+    #[test]
+    pub fn test_treat_outliers_drop_and_gap() {
+        let mut toolkit = init().expect("Could not initiate CsvToolkit!");
+        detect_income_outliers(&mut toolkit);
+
+        let treatments = HashMap::from([("Income".to_owned(), OutlierTreatment::ToGap)]);
+        toolkit.treat_outliers(treatments).unwrap();
+
+        assert_eq!(toolkit.gaps.rows(), vec![8, 12]);
+
+        let mut toolkit = init().expect("Could not initiate CsvToolkit!");
+        let tmp_file = toolkit.tmp_file.clone();
+        detect_income_outliers(&mut toolkit);
+        let treatments = HashMap::from([("Income".to_owned(), OutlierTreatment::DropRow)]);
+        toolkit.treat_outliers(treatments).unwrap();
+        let rows = column_values(&tmp_file, 0);
+
+        assert_eq!(rows.len(), 12);
+        assert!(!rows.contains(&"XCQ5937".to_owned()));
+    }
+
+    #[test]
+    pub fn test_normalization_replay() {
+        let mut train = init().expect("Could not initiate CsvToolkit!");
+        let mut test = init().expect("Could not initiate CsvToolkit!");
+        let test_key = String::from("Income");
+        let scaler_file = test.tmp_file.with_extension("json");
+
+        let methods = HashMap::from([(test_key.clone(), NormalizationMethod::ZScore)]);
+        let applied = train
+            .normalizing(methods)
+            .and_then(|scaler| scaler.save(&scaler_file))
+            .and_then(|_| Scaler::load(&scaler_file))
+            .and_then(|scaler| test.apply_normalization(&scaler).map(|_| scaler));
+        let train_values = column_values(&train.tmp_file, 17);
+        let test_values = column_values(&test.tmp_file, 17);
+
+        let inverted = applied
+            .as_ref()
+            .map(|scaler| test.invert_normalization(scaler));
+        let original_values = column_values(&test.tmp_file, 17);
+
+        assert!(matches!(inverted, Ok(Ok(()))));
+        assert_eq!(train_values, test_values);
+        assert!(matches!(
+            applied.unwrap().get(&test_key),
+            Some(Normalization::ZScore { .. })
+        ));
+        assert_eq!(original_values[0], "261404");
+
+        let scratch_dir = test.scratch_dir.clone();
+        drop(test);
+        assert!(!scratch_dir.exists());
+    }
+
+    #[test]
+    pub fn test_encoding_replay() {
+        let mut train = init().expect("Could not initiate CsvToolkit!");
+        let mut test = init().expect("Could not initiate CsvToolkit!");
+        let encoder_file = test.tmp_file.with_extension("json");
+
+        let order = ["Unhealthy", "Average", "Healthy"]
+            .map(String::from)
+            .to_vec();
+        let methods = HashMap::from([
+            (
+                "Sex".to_owned(),
+                EncodingMethod::OneHot {
+                    drop_first: false,
+                    min_frequency: None,
+                },
+            ),
+            (
+                "Diet".to_owned(),
+                EncodingMethod::Ordinal { order: Some(order) },
+            ),
+            (
+                "Continent".to_owned(),
+                EncodingMethod::Target {
+                    label: "Heart Attack Risk".to_owned(),
+                    smoothing: 0.,
+                },
+            ),
+        ]);
+        let applied = train
+            .encoding(methods)
+            .and_then(|encoder| encoder.save(&encoder_file))
+            .and_then(|_| Encoder::load(&encoder_file))
+            .and_then(|encoder| test.apply_encoding(&encoder));
+
+        let train_data = fs::read_to_string(&train.tmp_file).unwrap();
+        let test_data = fs::read_to_string(&test.tmp_file).unwrap();
+
+        assert!(applied.is_ok());
+        assert_eq!(train_data, test_data);
+        assert_eq!(&train.headers[2..4], ["sex_female", "sex_male"]);
+
+        let male = train.column_statistics("sex_male").unwrap();
+        assert_eq!(male.mean(), Some(10. / 14.));
+        assert!(matches!(
+            train.types[train.headers.iter().position(|h| h == "Diet").unwrap()],
+            DeserializationType::INTEGER(_)
+        ));
+    }
+
+    #[test]
+    pub fn test_null_tokens() {
+        let path = Path::new("./tests/nulls.csv");
+        let toolkit = CsvToolkit::new(path, b',', None, false, None, None)
+            .expect("Could not initiate CsvToolkit!");
+
+        assert_eq!(toolkit.gaps.rows(), vec![1, 2, 3, 4]);
+        assert!(toolkit.types[1].is_same_type(&DeserializationType::INTEGER(0)));
+        assert_eq!(
+            toolkit.max.get("age"),
+            Some(&DeserializationType::INTEGER(50))
+        );
+
+        let mut options = ParseOptions::default();
+        options
+            .column_null_tokens
+            .insert("group".to_owned(), vec!["a".to_owned()]);
+        let toolkit = CsvToolkit::new_with_options(path, b',', None, false, None, None, options)
+            .expect("Could not initiate CsvToolkit!");
+
+        assert!(toolkit.gaps.contains(0, 3));
+        assert!(!toolkit.gaps.contains(3, 3));
+    }
+
+    #[test]
+    pub fn test_mixed_types() {
+        let path = Path::new("./tests/mixed.csv");
+        let toolkit = CsvToolkit::new(path, b',', None, false, None, None)
+            .expect("Mixed columns should not fail preprocessing!");
+
+        assert!(matches!(toolkit.types[1], DeserializationType::FLOAT(_)));
+        assert!(matches!(toolkit.types[2], DeserializationType::STRING(_)));
+        assert!(matches!(toolkit.types[3], DeserializationType::DATETIME(_)));
+
+        let report = toolkit.type_report("score").unwrap();
+        assert_eq!(report.counts["INTEGER"], 3);
+        assert_eq!(report.minority_rows().get("STRING"), Some(&vec![3]));
+        assert!(toolkit.type_report("age").unwrap().is_consistent());
+        assert_eq!(
+            toolkit.max.get("score"),
+            Some(&DeserializationType::INTEGER(9))
+        );
+    }
+
+    #[test]
+    pub fn test_schema() {
+        let schema = Schema::load("./tests/schema.toml").unwrap();
+        let toolkit = init().unwrap().with_schema(schema).unwrap();
+
+        let patient_id = toolkit
+            .headers
+            .iter()
+            .position(|h| h == "Patient ID")
+            .unwrap();
+        let diabetes = toolkit
+            .headers
+            .iter()
+            .position(|h| h == "Diabetes")
+            .unwrap();
+        let systolic = toolkit
+            .headers
+            .iter()
+            .position(|h| h == "Systolic")
+            .unwrap();
+
+        assert!(!toolkit.headers.contains(&"Blood Pressure".to_owned()));
+        assert_eq!(toolkit.headers[systolic + 1], "Diastolic");
+        assert!(matches!(
+            toolkit.types[patient_id],
+            DeserializationType::STRING(_)
+        ));
+        assert!(matches!(
+            toolkit.types[diabetes],
+            DeserializationType::BOOLEAN(_)
+        ));
+        assert!(matches!(
+            toolkit.types[systolic],
+            DeserializationType::INTEGER(_)
+        ));
+        assert_eq!(
+            toolkit.max.get("Systolic"),
+            Some(&DeserializationType::INTEGER(174))
+        );
+
+        // 4 Female rows are not allowed by the schema, 2 rows have a systolic pressure above 170
+        let categories = toolkit
+            .violations
+            .iter()
+            .filter(|v| matches!(v.kind, schema::ViolationKind::Category(_)))
+            .count();
+        let ranges: Vec<usize> = toolkit
+            .violations
+            .iter()
+            .filter(|v| v.column == "Systolic")
+            .map(|v| v.row)
+            .collect();
+        assert_eq!(categories, 4);
+        assert_eq!(ranges, vec![2, 5]);
+        assert_eq!(toolkit.violations.len(), 6);
+    }
+
+    #[test]
+    pub fn test_schema_blank_string() {
+        let schema = Schema::load("./tests/schema.toml").unwrap();
+        let path = Path::new("./tests/schema.csv");
+        let toolkit = CsvToolkit::new(path, b',', None, false, None, None)
+            .unwrap()
+            .with_schema(schema)
+            .unwrap();
+
+        // The blank id of a `nullable = false` string column is a gap and a violation
+        assert!(toolkit.gaps.contains(1, 0));
+        assert_eq!(toolkit.violations.len(), 1);
+        assert_eq!(toolkit.violations[0].row, 1);
+        assert_eq!(toolkit.violations[0].column, "Patient ID");
+        assert!(matches!(
+            toolkit.violations[0].kind,
+            schema::ViolationKind::Null
+        ));
+    }
+
+    #[test]
+    pub fn test_schema_declared_type() {
+        let schema = Schema::from_toml("[columns.score]\ntype = \"integer\"").unwrap();
+        let path = Path::new("./tests/mixed.csv");
+        let toolkit = CsvToolkit::new(path, b',', None, false, None, None)
+            .unwrap()
+            .with_schema(schema)
+            .unwrap();
+
+        assert!(matches!(toolkit.types[2], DeserializationType::INTEGER(_)));
+        assert_eq!(toolkit.violations.len(), 1);
+        assert_eq!(toolkit.violations[0].row, 3);
+    }
+
+    #[test]
+    pub fn test_schema_unknown_column() {
+        let schema = Schema::from_toml("[columns.Unknown]\ntype = \"integer\"").unwrap();
+        assert!(init().unwrap().with_schema(schema).is_err());
+    }
+
+    #[test]
+    pub fn test_column_statistics() {
+        let toolkit = init_with_gaps().unwrap();
+        let values: Vec<f64> = column_values(Path::new("./tests/gaps.csv"), 1)
+            .iter()
+            .filter_map(|v| v.parse().ok())
+            .collect();
+
+        let age = toolkit.column_statistics("age").unwrap();
+        let (mean, std) = statistics::mean_std(&values).unwrap();
+
+        assert_eq!(age.count(), values.len());
+        assert_eq!(age.count() + age.null_count(), values.len() + 2);
+        assert!((age.mean().unwrap() - mean).abs() < 1e-9);
+        assert!((age.std().unwrap() - std).abs() < 1e-9);
+
+        let group = toolkit.column_statistics("group").unwrap();
+        assert_eq!(group.mean(), None);
+        assert_eq!(group.distinct_count(), 2);
+        assert_eq!(group.top_k(1), vec![("a".to_owned(), 3)]);
+    }
+
+    #[test]
+    pub fn test_categories() {
+        let toolkit = init().unwrap();
+
+        let sex = toolkit.categories.get("Sex").unwrap();
+        assert_eq!(sex.cardinality(), 2);
+        assert_eq!(sex.count("Male"), 10);
+        assert_eq!(sex.rare(0.3), vec!["Female"]);
+        assert!(sex.near_duplicates().is_empty());
+
+        assert!(toolkit.categories.contains_key("Continent"));
+        assert!(!toolkit.categories.contains_key("Age"));
+    }
+
+    #[test]
+    pub fn test_split() {
+        let mut toolkit = init().unwrap();
+        let outputs = [
+            (toolkit.tmp_file.with_extension("train"), 0.5),
+            (toolkit.tmp_file.with_extension("test"), 0.5),
+        ];
+        let label = "Heart Attack Risk".to_owned();
+
+        let stratified = SplitStrategy::Stratified {
+            label: label.clone(),
+        };
+        let written = toolkit.split(&outputs, stratified.clone(), 42).unwrap();
+        let train = fs::read_to_string(&outputs[0].0).unwrap();
+        let risk: Vec<Vec<String>> = outputs
+            .iter()
+            .map(|(path, _)| column_values(path, 25))
+            .collect();
+
+        toolkit.split(&outputs, stratified, 42).unwrap();
+        let replay = fs::read_to_string(&outputs[0].0).unwrap();
+
+        let grouped = SplitStrategy::Grouped {
+            group: "Continent".to_owned(),
+        };
+        toolkit.split(&outputs, grouped, 1).unwrap();
+        let continents: Vec<Vec<String>> = outputs
+            .iter()
+            .map(|(path, _)| column_values(path, 23))
+            .collect();
+
+        let ordered = SplitStrategy::TimeOrdered {
+            column: "Age".to_owned(),
+        };
+        let ordered_sizes = toolkit.split(&outputs, ordered, 0).unwrap();
+        let ages: Vec<Vec<i64>> = outputs
+            .iter()
+            .map(|(path, _)| {
+                column_values(path, 1)
+                    .iter()
+                    .map(|age| age.parse().unwrap())
+                    .collect()
+            })
+            .collect();
+
+        assert_eq!(written, vec![7, 7]);
+        assert_eq!(train, replay);
+        let positives: Vec<usize> = risk
+            .iter()
+            .map(|values| values.iter().filter(|v| *v == "1").count())
+            .collect();
+        assert!(positives[0].abs_diff(positives[1]) <= 1);
+
+        assert!(continents[0].iter().all(|c| !continents[1].contains(c)));
+
+        assert_eq!(ordered_sizes, vec![7, 7]);
+        assert!(ages[0].iter().max() <= ages[1].iter().min());
+    }
+
+    #[test]
+    pub fn test_k_fold() {
+        let mut toolkit = init().unwrap();
+        let label = "Heart Attack Risk".to_owned();
+        let stratified = SplitStrategy::Stratified {
+            label: label.clone(),
+        };
+
+        let dir = toolkit.tmp_file.with_extension("folds");
+        let summary = toolkit
+            .k_fold(
+                3,
+                stratified.clone(),
+                7,
+                FoldOutput::Files(dir.clone()),
+                None,
+            )
+            .unwrap();
+        let train = column_values(&dir.join("fold_1_train.csv"), 0);
+        let val = column_values(&dir.join("fold_1_val.csv"), 0);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let column = FoldOutput::Column("fold".to_owned());
+        let replay = toolkit.k_fold(3, stratified, 7, column, None).unwrap();
+        let folds = column_values(&toolkit.tmp_file, 26);
+        let ids = column_values(&toolkit.tmp_file, 0);
+
+        assert_eq!(summary, replay);
+        assert_eq!(summary.sizes, vec![5, 5, 4]);
+        assert_eq!(train.len() + val.len(), 14);
+        assert!(val.iter().all(|id| !train.contains(id)));
+
+        let expected: Vec<&String> = ids
+            .iter()
+            .zip(folds.iter())
+            .filter(|(_, fold)| *fold == "1")
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(val.iter().collect::<Vec<&String>>(), expected);
+
+        let positives: Vec<usize> = (0..3).map(|fold| summary.label_counts[fold]["1"]).collect();
+        assert!(positives.iter().max().unwrap() - positives.iter().min().unwrap() <= 1);
+        assert!(summary.to_string().starts_with("fold 0: 5 rows"));
+        assert_eq!(toolkit.headers.last().unwrap(), "fold");
+    }
+
+    #[test]
+    pub fn test_balance() {
+        let label = "Heart Attack Risk";
+        let risk = |toolkit: &CsvToolkit| -> Vec<String> { column_values(&toolkit.tmp_file, 25) };
+
+        let mut under = init().unwrap();
+        let counts = under
+            .balance(label, ResamplingMethod::RandomUnder, 1., 5)
+            .unwrap();
+        let under_risk = risk(&under);
+
+        let mut over = init().unwrap();
+        over.balance(label, ResamplingMethod::RandomOver, 1., 5)
+            .unwrap();
+        let over_risk = risk(&over);
+
+        let mut smote = init().unwrap();
+        let smote_counts = smote
+            .balance(label, ResamplingMethod::Smote { k: 3 }, 0.8, 5)
+            .unwrap();
+        let smote_risk = risk(&smote);
+        let age = smote.column_statistics("Age").unwrap().clone();
+
+        assert_eq!(counts["0"], 5);
+        assert_eq!(under_risk.len(), 10);
+        assert_eq!(over_risk.iter().filter(|r| *r == "1").count(), 9);
+        assert_eq!(over_risk.len(), 18);
+
+        // round(0.8 * 9) positive rows
+        assert_eq!(smote_counts["1"], 7);
+        assert_eq!(smote_risk.len(), 16);
+        assert_eq!(age.count(), 16);
+        assert_eq!(age.numeric_count(), 16);
+    }
+
+    #[test]
+    pub fn test_find_duplicates() {
+        let path = Path::new("./tests/duplicates.csv");
+        let mut toolkit = CsvToolkit::new(path, b',', None, false, None, None).unwrap();
+
+        let report = toolkit.find_duplicates(Some("id")).unwrap();
+        let rows: Vec<usize> = report.rows.iter().map(|d| d.row).collect();
+        let keys: Vec<(usize, usize)> = report.keys.iter().map(|d| (d.row, d.first)).collect();
+
+        assert_eq!(rows, vec![2, 5]);
+        assert_eq!(keys, vec![(2, 0), (4, 1), (5, 1), (6, 3)]);
+        assert_eq!(report.conflicts.len(), 2);
+        assert_eq!(report.conflicts[0].key, "2");
+        assert_eq!(report.conflicts[0].rows, vec![1, 4, 5]);
+        assert_eq!(report.conflicts[0].columns, vec!["age"]);
+        assert_eq!(report.conflicts[1].columns, vec!["age", "city"]);
+
+        assert!(toolkit.find_duplicates(Some("unknown")).is_err());
+    }
+
+    #[test]
+    pub fn test_deduplicate() {
+        let path = Path::new("./tests/duplicates.csv");
+        let open = || CsvToolkit::new(path, b',', None, false, None, None).unwrap();
+
+        let mut rows = open();
+        let mut last = open();
+        let mut merge = open();
+
+        assert_eq!(rows.deduplicate(None, KeepPolicy::First).unwrap(), 2);
+        assert_eq!(last.deduplicate(Some("id"), KeepPolicy::Last).unwrap(), 4);
+        assert_eq!(merge.deduplicate(Some("id"), KeepPolicy::Merge).unwrap(), 4);
+
+        let last_ages = column_values(&last.tmp_file, 2);
+        let merged_ages = column_values(&merge.tmp_file, 2);
+        let merged_cities = column_values(&merge.tmp_file, 3);
+
+        assert_eq!(last_ages, vec!["30", "41", "26"]);
+        assert_eq!(merged_ages, vec!["30", "41", "25"]);
+        assert_eq!(merged_cities, vec!["Kyiv", "Lviv", "Odesa"]);
+    }
+
+    #[test]
+    pub fn test_save_as() {
+        let mut toolkit = init_with_gaps().unwrap();
+        let scratch_dir = toolkit.scratch_dir.clone();
+        let dir = std::env::temp_dir().join(format!("csv_toolkit_save_{}", std::process::id()));
+        let path = dir.join("nested").join("filled.csv");
+
+        let strategies = HashMap::from([("age".to_owned(), FillStrategy::Mean)]);
+        toolkit.fill_gaps(strategies).unwrap();
+        toolkit.save_as(&path, SaveOptions::default()).unwrap();
+
+        let no_overwrite = SaveOptions {
+            overwrite: false,
+            ..Default::default()
+        };
+        let existing = toolkit.save_as(&path, no_overwrite);
+
+        let semicolon = dir.join("semicolon.csv");
+        let options = SaveOptions {
+            delimiter: Some(b';'),
+            headers: false,
+            ..Default::default()
+        };
+        toolkit.save_as(&semicolon, options).unwrap();
+        let semicolon_data = fs::read_to_string(&semicolon).unwrap();
+
+        drop(toolkit);
+        let saved = CsvToolkit::new(&path, b',', None, false, None, None).unwrap();
+        let ages = column_values(&path, 1);
+        let partial_files = fs::read_dir(path.parent().unwrap()).unwrap().count();
+        drop(saved);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(existing.is_err());
+        assert!(!scratch_dir.exists());
+        assert_eq!(ages, vec!["30", "40", "50", "40", "40"]);
+        assert_eq!(partial_files, 1);
+        assert!(semicolon_data.starts_with("1;30;1.5;a\n"));
+    }
+
+    #[test]
+    pub fn test_dialect_round_trip() {
+        let path = Path::new("./tests/quoted.csv");
+        let mut toolkit = CsvToolkit::new(path, b',', None, true, None, None).unwrap();
+        let saved = toolkit.scratch_dir.join("saved.csv");
+
+        toolkit.save_as(&saved, SaveOptions::default()).unwrap();
+        assert_eq!(fs::read(&saved).unwrap(), fs::read(path).unwrap());
+        // Values are trimmed for the statistics only
+        assert_eq!(
+            toolkit.column_statistics("city").unwrap().top_k(1),
+            vec![("Kyiv".to_owned(), 2)]
+        );
+
+        toolkit
+            .derive_column("double".to_owned(), UserInput::EXPR("[id] * 2".to_owned()))
+            .unwrap();
+        toolkit.save_as(&saved, SaveOptions::default()).unwrap();
+
+        let mut reader = ReaderBuilder::new().from_path(&saved).unwrap();
+        let rows: Vec<csv::StringRecord> = reader.records().map(|r| r.unwrap()).collect();
+        assert_eq!(&rows[0][1], "South Africa, Cape");
+        assert_eq!(&rows[0][2], "said \"hi\"");
+        // Padding is a part of unedited fields
+        assert_eq!(&rows[1][1], " Kyiv ");
+        assert_eq!(&rows[1][2], "  padded, note  ");
+        assert_eq!(&rows[2][2], "multi\r\nline");
+        assert_eq!(&rows[2][3], "6");
+        assert!(fs::read_to_string(&saved)
+            .unwrap()
+            .ends_with("line\",6\r\n"));
+    }
+
+    #[test]
+    pub fn test_save_as_undoubled_quotes() {
+        let path = Path::new("./tests/unescaped.csv");
+        let mut toolkit = CsvToolkit::new(path, b',', None, false, None, None).unwrap();
+        let saved = toolkit.scratch_dir.join("saved.csv");
+
+        // The second rewrite reads back the quotes doubled by the first one
+        for (header, expr) in [("double", "[id] * 2"), ("triple", "[id] * 3")] {
+            toolkit
+                .derive_column(header.to_owned(), UserInput::EXPR(expr.to_owned()))
+                .unwrap();
+        }
+        toolkit.save_as(&saved, SaveOptions::default()).unwrap();
+
+        let mut reader = ReaderBuilder::new().from_path(&saved).unwrap();
+        let rows: Vec<csv::StringRecord> = reader.records().map(|r| r.unwrap()).collect();
+        assert_eq!(&rows[0][1], "a\"b");
+        assert_eq!(&rows[0][3], "3");
+        assert!(toolkit.dialect().double_quotes);
+    }
+
+    #[test]
+    pub fn test_open() {
+        let toolkit = CsvToolkit::open("./tests/test.csv").unwrap();
+        assert_eq!(toolkit.dialect().delimiter, b',');
+        assert!(toolkit.dialect().has_headers);
+        assert_eq!(toolkit.headers, init().unwrap().headers);
+
+        let mut toolkit = CsvToolkit::open("./tests/latin1.csv").unwrap();
+        let dialect = toolkit.dialect().clone();
+        assert_eq!(dialect.delimiter, b';');
+        assert_eq!(dialect.encoding, TextEncoding::Latin1);
+        assert_eq!(dialect.terminator, LineTerminator::CrLf);
+        assert!(!dialect.has_headers);
+        assert_eq!(toolkit.headers, vec!["column_1", "column_2", "column_3"]);
+
+        let saved = toolkit.scratch_dir.join("saved.csv");
+        toolkit.save_as(&saved, SaveOptions::default()).unwrap();
+        assert_eq!(
+            fs::read_to_string(&saved).unwrap(),
+            "column_1;column_2;column_3\r\n1;caf\u{e9};2,5\r\n2;th\u{e9};3,0\r\n3;\u{e9}t\u{e9};4,5\r\n"
+        );
+
+        // UTF-16 with byte order mark
+        let utf16 = toolkit.scratch_dir.join("utf16.csv");
+        let mut bytes = vec![0xFF, 0xFE];
+        for unit in "id\tname\n1\tcaf\u{e9}\n2\tx\n".encode_utf16() {
+            bytes.extend(unit.to_le_bytes());
+        }
+        fs::write(&utf16, bytes).unwrap();
+
+        let toolkit = CsvToolkit::open(&utf16).unwrap();
+        assert_eq!(toolkit.dialect().encoding, TextEncoding::Utf16Le);
+        assert!(toolkit.dialect().bom);
+        assert_eq!(toolkit.dialect().delimiter, b'\t');
+        assert_eq!(toolkit.headers, vec!["id", "name"]);
+        assert_eq!(toolkit.categories["name"].count("caf\u{e9}"), 1);
+    }
+
+    #[test]
+    pub fn test_error_location() {
+        let mut toolkit = init().unwrap();
+        let error = toolkit
+            .normalizing(HashMap::from([(
+                "Sex".to_owned(),
+                NormalizationMethod::MinMax,
+            )]))
+            .unwrap_err();
+
+        assert!(matches!(error, Error::TypeMismatch { .. }));
+        let location = error.location();
+        assert_eq!(location.row, Some(0));
+        assert_eq!(location.column.as_deref(), Some("Sex"));
+        assert_eq!(location.value.as_deref(), Some("Male"));
+
+        let Err(error) = CsvToolkit::open("./tests/missing.csv") else {
+            panic!("a missing file should not open");
+        };
+        assert!(matches!(error, Error::Io { .. }));
+        assert_eq!(
+            error.location().file.as_deref(),
+            Some(Path::new("./tests/missing.csv"))
+        );
+    }
+
+    fn assert_same_statistics(parallel: &CsvToolkit, sequential: &CsvToolkit) {
+        assert_eq!(parallel.types, sequential.types);
+        assert_eq!(parallel.type_reports, sequential.type_reports);
+        assert_eq!(parallel.min, sequential.min);
+        assert_eq!(parallel.max, sequential.max);
+        assert_eq!(parallel.categories, sequential.categories);
+        assert_eq!(parallel.statistics, sequential.statistics);
+        assert_eq!(parallel.gaps, sequential.gaps);
+        assert_eq!(parallel.violations, sequential.violations);
+    }
+
+    #[test]
+    pub fn test_parallel_preprocessing() {
+        // Chunks of a few rows, in waves of 3 threads
+        let chunked = Parallelism {
+            threads: 3,
+            chunk_bytes: 16,
+        };
+        let schema = Schema::load("./tests/schema.toml").unwrap();
+
+        let mut sequential = init().unwrap().with_schema(schema.clone()).unwrap();
+        let mut parallel = init().unwrap().with_schema(schema).unwrap();
+        sequential.set_parallelism(Parallelism::sequential());
+        sequential.preprocessing().unwrap();
+        parallel.set_parallelism(chunked);
+        parallel.preprocessing().unwrap();
+        assert!(!parallel.violations.is_empty());
+        assert_same_statistics(&parallel, &sequential);
+
+        for path in ["./tests/gaps.csv", "./tests/quoted.csv"] {
+            let sequential = CsvToolkit::new(path, b',', None, true, None, None).unwrap();
+            let mut parallel = CsvToolkit::new(path, b',', None, true, None, None).unwrap();
+            parallel.set_parallelism(chunked);
+            parallel.preprocessing().unwrap();
+            assert_same_statistics(&parallel, &sequential);
+        }
+
+        // Outliers and normalization of the rewritten data
+        let detectors = HashMap::from([
+            (
+                "Income".to_owned(),
+                OutlierDetector::ZScore { threshold: 1.5 },
+            ),
+            ("Age".to_owned(), OutlierDetector::Iqr { k: 1.5 }),
+        ]);
+        let methods = HashMap::from([
+            ("Income".to_owned(), NormalizationMethod::ZScore),
+            ("BMI".to_owned(), NormalizationMethod::MinMax),
+        ]);
+
+        sequential.postprocessing(detectors.clone()).unwrap();
+        parallel.postprocessing(detectors).unwrap();
+        assert_eq!(parallel.outliers, sequential.outliers);
+        assert_eq!(parallel.fences, sequential.fences);
+        assert_eq!(parallel.gaps, sequential.gaps);
+
+        let scaler = sequential.normalizing(methods.clone()).unwrap();
+        assert_eq!(parallel.normalizing(methods).unwrap(), scaler);
+        assert_same_statistics(&parallel, &sequential);
+        assert_eq!(
+            fs::read(&parallel.tmp_file).unwrap(),
+            fs::read(&sequential.tmp_file).unwrap()
+        );
+    }
+
+    #[test]
+    pub fn test_parallel_error_row() {
+        let dir = create_scratch_dir().unwrap();
+        let path = dir.join("invalid.csv");
+        let mut data = b"id,name\n".to_vec();
+        for row_id in 0..20 {
+            data.extend(format!("{row_id},name {row_id}\n").bytes());
+        }
+        fs::write(&path, &data).unwrap();
+        let mut toolkit = CsvToolkit::new(&path, b',', None, true, None, None).unwrap();
+
+        // Invalid UTF-8 in the last chunk
+        data.extend(b"20,\xff\n21,last\n");
+        fs::write(&path, data).unwrap();
+
+        for parallelism in [
+            Parallelism::sequential(),
+            Parallelism {
+                threads: 2,
+                chunk_bytes: 16,
+            },
+        ] {
+            toolkit.set_parallelism(parallelism);
+            let error = toolkit.preprocessing().unwrap_err();
+            assert_eq!(error.location().row, Some(20));
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    pub fn test_pipeline() {
+        let mut toolkit = init_with_gaps().unwrap();
+        let report = toolkit
+            .pipeline()
+            .fill_gaps(HashMap::from([("age".to_owned(), FillStrategy::Mean)]))
+            .normalize(HashMap::from([(
+                "score".to_owned(),
+                NormalizationMethod::MinMax,
+            )]))
+            .filter_rows("id > 1")
+            .drop_columns(vec!["group".to_owned()])
+            .run()
+            .unwrap();
+
+        // Both fits share the first pass, the second one writes the data
+        assert_eq!(report.passes, 2);
+        assert_eq!(report.rows, 4);
+        assert_eq!(report.scalers.len(), 1);
+        assert_eq!(toolkit.headers, ["id", "age", "score"]);
+
+        // Fitted before the filter: the mean age and the score range of every row
+        assert_eq!(
+            column_values(&toolkit.tmp_file, 1),
+            ["40", "50", "40", "40"]
+        );
+        assert_eq!(
+            column_values(&toolkit.tmp_file, 2),
+            ["0.25", "", "0.75", "1"]
+        );
+
+        // Statistics computed while writing are the ones of the written data
+        let types = toolkit.types.clone();
+        let statistics = toolkit.statistics.clone();
+        let gaps = toolkit.gaps.clone();
+        let min = toolkit.min.clone();
+        toolkit.preprocessing().unwrap();
+        assert_eq!(toolkit.types, types);
+        assert_eq!(toolkit.statistics, statistics);
+        assert_eq!(toolkit.gaps, gaps);
+        assert_eq!(toolkit.min, min);
+        assert!(toolkit.gaps.contains(1, 2));
+    }
+
+    #[test]
+    pub fn test_pipeline_dependent_fits() {
+        let mut fused = init_with_gaps().unwrap();
+        let mut eager = init_with_gaps().unwrap();
+        let strategies = HashMap::from([
+            ("age".to_owned(), FillStrategy::Median),
+            ("score".to_owned(), FillStrategy::ForwardFill),
+        ]);
+        let methods = HashMap::from([("age".to_owned(), NormalizationMethod::ZScore)]);
+
+        let report = fused
+            .pipeline()
+            .fill_gaps(strategies.clone())
+            .normalize(methods.clone())
+            .run()
+            .unwrap();
+
+        // The normalization is fitted on the filled ages
+        assert_eq!(report.passes, 3);
+
+        eager.fill_gaps(strategies).unwrap();
+        let scaler = eager.normalizing(methods).unwrap();
+        assert_eq!(report.scalers, vec![scaler]);
+        assert_eq!(
+            fs::read(&fused.tmp_file).unwrap(),
+            fs::read(&eager.tmp_file).unwrap()
+        );
+        assert_eq!(fused.statistics, eager.statistics);
+    }
+
+    #[test]
+    pub fn test_pipeline_encode() {
+        let mut toolkit = init_with_gaps().unwrap();
+        let report = toolkit
+            .pipeline()
+            .encode(HashMap::from([(
+                "group".to_owned(),
+                EncodingMethod::Ordinal { order: None },
+            )]))
+            .normalize(HashMap::from([(
+                "group".to_owned(),
+                NormalizationMethod::MinMaxRange { a: 0., b: 10. },
+            )]))
+            .run()
+            .unwrap();
+
+        // Codes are normalized once known
+        assert_eq!(report.passes, 3);
+        assert_eq!(report.encoders.len(), 1);
+        assert_eq!(
+            column_values(&toolkit.tmp_file, 3),
+            ["0", "10", "0", "", "0"]
+        );
+    }
+
+    #[test]
+    pub fn test_pipeline_errors() {
+        let mut toolkit = init_with_gaps().unwrap();
+        let gaps = toolkit.gaps.clone();
+
+        let error = toolkit
+            .pipeline()
+            .fill_gaps(HashMap::from([("age".to_owned(), FillStrategy::Mean)]))
+            .filter_rows("age + 1")
+            .run()
+            .unwrap_err();
+        assert!(matches!(error, Error::TypeMismatch { .. }));
+
+        let error = toolkit
+            .pipeline()
+            .drop_columns(vec!["Unknown".to_owned()])
+            .run()
+            .unwrap_err();
+        assert!(matches!(error, Error::InvalidColumn { .. }));
+
+        // The data is left untouched
+        assert_eq!(toolkit.headers, ["id", "age", "score", "group"]);
+        assert_eq!(toolkit.gaps, gaps);
+        assert_eq!(toolkit.data_file, Path::new("./tests/gaps.csv"));
     }
 }
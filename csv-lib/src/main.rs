@@ -1,6 +1,8 @@
 use csv_lib::error::Result;
+use csv_lib::normalization::NormalizationMethod;
 use csv_lib::CsvToolkit;
 
+use std::collections::HashMap;
 use std::path::Path;
 
 fn main() -> Result<()> {
@@ -8,6 +10,6 @@ fn main() -> Result<()> {
         CsvToolkit::new(Path::new("./tests/test.csv"), b',', None, false, None, None)?;
     let test_key = String::from("Exercise Hours Per Week");
 
-    toolkit.normalizing(vec![test_key.clone()])?;
+    toolkit.normalizing(HashMap::from([(test_key, NormalizationMethod::MinMax)]))?;
     Ok(())
 }
@@ -0,0 +1,294 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::statistics::{mean_std, quantile};
+
+/// Normalization method of a column used by `CsvToolkit::normalizing`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NormalizationMethod {
+    /// `(x - min) / (max - min)`, maps the column to `[0, 1]`.
+    MinMax,
+    /// Min-max scaling to a custom `[a, b]` range.
+    MinMaxRange { a: f64, b: f64 },
+    /// `(x - mean) / std`
+    ZScore,
+    /// `(x - median) / IQR`
+    Robust,
+    /// `x / max(|x|)`, maps the column to `[-1, 1]`.
+    MaxAbs,
+    /// `ln(1 + x)`, requires `x > -1`.
+    Log1p,
+}
+
+/// Normalization fitted on a column. Keeps everything needed to apply the same transform again.
+///
+/// Constant columns (zero spread) are mapped to the lower end of the target range (`0` or `a`)
+/// instead of dividing by zero.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum Normalization {
+    MinMax { min: f64, max: f64, a: f64, b: f64 },
+    ZScore { mean: f64, std: f64 },
+    Robust { median: f64, iqr: f64 },
+    MaxAbs { max_abs: f64 },
+    Log1p,
+}
+
+impl NormalizationMethod {
+    /// Fit the method on the values of a column. Sorts `values` in place.
+    ///
+    /// # Return
+    /// `None` if there are no values to fit.
+    pub fn fit(&self, values: &mut [f64]) -> Option<Normalization> {
+        if values.is_empty() {
+            return None;
+        }
+
+        values.sort_by(|a, b| a.total_cmp(b));
+        let (min, max) = (values[0], values[values.len() - 1]);
+
+        let fitted = match *self {
+            NormalizationMethod::MinMax => Normalization::MinMax {
+                min,
+                max,
+                a: 0_f64,
+                b: 1_f64,
+            },
+            NormalizationMethod::MinMaxRange { a, b } => Normalization::MinMax { min, max, a, b },
+            NormalizationMethod::ZScore => {
+                let (mean, std) = mean_std(values)?;
+                Normalization::ZScore { mean, std }
+            }
+            NormalizationMethod::Robust => Normalization::Robust {
+                median: quantile(values, 0.5)?,
+                iqr: quantile(values, 0.75)? - quantile(values, 0.25)?,
+            },
+            NormalizationMethod::MaxAbs => Normalization::MaxAbs {
+                max_abs: min.abs().max(max.abs()),
+            },
+            NormalizationMethod::Log1p => Normalization::Log1p,
+        };
+
+        Some(fitted)
+    }
+}
+
+impl Normalization {
+    /// Normalize a single value.
+    ///
+    /// # Errors
+    /// `Log1p` of a value less than or equal to `-1`;
+    pub fn transform(&self, x: f64) -> Result<f64> {
+        let y = match *self {
+            Normalization::MinMax { min, max, a, b } => {
+                if max == min {
+                    a
+                } else {
+                    a + (x - min) * (b - a) / (max - min)
+                }
+            }
+            Normalization::ZScore { mean, std } => {
+                if std == 0_f64 {
+                    0_f64
+                } else {
+                    (x - mean) / std
+                }
+            }
+            Normalization::Robust { median, iqr } => {
+                if iqr == 0_f64 {
+                    0_f64
+                } else {
+                    (x - median) / iqr
+                }
+            }
+            Normalization::MaxAbs { max_abs } => {
+                if max_abs == 0_f64 {
+                    0_f64
+                } else {
+                    x / max_abs
+                }
+            }
+            Normalization::Log1p => {
+                if x <= -1_f64 {
+                    return Err(Error::invalid_argument("log1p is defined above -1")
+                        .with_value(x.to_string()));
+                }
+                x.ln_1p()
+            }
+        };
+
+        Ok(y)
+    }
+
+    /// Restore the original value of a normalized one.
+    ///
+    /// Values of constant columns are restored to the only value of the column.
+    pub fn inverse(&self, y: f64) -> f64 {
+        match *self {
+            Normalization::MinMax { min, max, a, b } => {
+                if max == min || a == b {
+                    min
+                } else {
+                    min + (y - a) * (max - min) / (b - a)
+                }
+            }
+            Normalization::ZScore { mean, std } => mean + y * std,
+            Normalization::Robust { median, iqr } => median + y * iqr,
+            Normalization::MaxAbs { max_abs } => y * max_abs,
+            Normalization::Log1p => y.exp_m1(),
+        }
+    }
+}
+
+/// Fitted normalization of a dataset: method and parameters of every normalized column.
+///
+/// Produced by `CsvToolkit::normalizing`, it could be saved next to a model and applied to
+/// validation, test or inference data with `CsvToolkit::apply_normalization`
+/// (`CsvToolkit::invert_normalization` to restore the original values).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Scaler {
+    pub columns: BTreeMap<String, Normalization>,
+}
+
+impl Scaler {
+    pub fn new(columns: BTreeMap<String, Normalization>) -> Self {
+        Scaler { columns }
+    }
+
+    pub fn get(&self, column: &str) -> Option<&Normalization> {
+        self.columns.get(column)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Save the scaler as a JSON file.
+    ///
+    /// # Errors
+    /// fs, io, serialization;
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        if let Some(parent) = path.as_ref().parent() {
+            if !parent.as_os_str().is_empty() && !parent.exists() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+
+        fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+
+    /// Load a scaler saved by `Scaler::save`.
+    ///
+    /// # Errors
+    /// fs, io, deserialization;
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Normalization, NormalizationMethod, Scaler};
+
+    fn transform(method: NormalizationMethod, values: &[f64]) -> Vec<f64> {
+        let fitted = method.fit(&mut values.to_vec()).unwrap();
+        values
+            .iter()
+            .map(|x| fitted.transform(*x).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_methods() {
+        let values = [1., 2., 3., 4., 5.];
+
+        assert_eq!(
+            transform(NormalizationMethod::MinMax, &values),
+            vec![0., 0.25, 0.5, 0.75, 1.]
+        );
+        assert_eq!(
+            transform(NormalizationMethod::MinMaxRange { a: -1., b: 1. }, &values),
+            vec![-1., -0.5, 0., 0.5, 1.]
+        );
+        assert_eq!(
+            transform(NormalizationMethod::Robust, &values),
+            vec![-1., -0.5, 0., 0.5, 1.]
+        );
+        assert_eq!(
+            transform(NormalizationMethod::MaxAbs, &values),
+            vec![0.2, 0.4, 0.6, 0.8, 1.]
+        );
+
+        let z = transform(NormalizationMethod::ZScore, &values);
+        assert_eq!(z[2], 0.);
+        assert!((z[4] - 2_f64.sqrt()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_constant_column() {
+        for method in [
+            NormalizationMethod::MinMax,
+            NormalizationMethod::ZScore,
+            NormalizationMethod::Robust,
+        ] {
+            assert_eq!(transform(method, &[7., 7., 7.]), vec![0., 0., 0.]);
+        }
+
+        assert_eq!(
+            transform(NormalizationMethod::MaxAbs, &[0., 0.]),
+            vec![0., 0.]
+        );
+    }
+
+    #[test]
+    fn test_log1p() {
+        assert_eq!(Normalization::Log1p.transform(0.).unwrap(), 0.);
+        assert!(Normalization::Log1p.transform(-1.).is_err());
+    }
+
+    #[test]
+    fn test_inverse() {
+        let values = [1., 2., 3., 4., 5.];
+        for method in [
+            NormalizationMethod::MinMaxRange { a: -1., b: 1. },
+            NormalizationMethod::ZScore,
+            NormalizationMethod::Robust,
+            NormalizationMethod::MaxAbs,
+            NormalizationMethod::Log1p,
+        ] {
+            let fitted = method.fit(&mut values.to_vec()).unwrap();
+            for x in values {
+                assert!((fitted.inverse(fitted.transform(x).unwrap()) - x).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn test_scaler_json() {
+        let scaler = Scaler::new(
+            [
+                (
+                    "Age".to_owned(),
+                    Normalization::ZScore {
+                        mean: 50.,
+                        std: 10.,
+                    },
+                ),
+                ("BMI".to_owned(), Normalization::Log1p),
+            ]
+            .into_iter()
+            .collect(),
+        );
+
+        let json = scaler.to_json().unwrap();
+        assert!(json.contains(r#""method": "z_score""#));
+        assert_eq!(Scaler::from_json(&json).unwrap(), scaler);
+    }
+}
@@ -0,0 +1,203 @@
+use crate::deserialization::DeserializationType;
+use crate::statistics::{mean_std, median, quantile};
+
+/// Statistical outlier detector used by `CsvToolkit::postprocessing`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutlierDetector {
+    /// Tukey fences `[Q1 - k * IQR, Q3 + k * IQR]` (`k` is usually `1.5`). When more than half of
+    /// the values are equal (IQR of zero) the fences are `[Q1, Q3]`.
+    Iqr { k: f64 },
+    /// `|x - mean| / std > threshold` (usually `3`).
+    ZScore { threshold: f64 },
+    /// `0.6745 * |x - median| / MAD > threshold` (usually `3.5`).
+    ModifiedZScore { threshold: f64 },
+    /// Values outside the `[lower, upper]` percentiles (e.g. `1` and `99`).
+    Percentile { lower: f64, upper: f64 },
+}
+
+/// Treatment of detected outliers used by `CsvToolkit::treat_outliers`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutlierTreatment {
+    /// Clamp the value to the violated bound.
+    Clip,
+    /// Clamp the value to the `[lower, upper]` percentiles of the column (e.g. `5` and `95`).
+    Winsorize { lower: f64, upper: f64 },
+    /// Drop the whole row.
+    DropRow,
+    /// Turn the cell into a gap (`DeserializationType::EMPTY`) to be filled later.
+    ToGap,
+}
+
+/// Flagged value: the bound which was violated and the detector score of the value.
+///
+/// The score is signed (negative below the lower bound):
+/// * `Iqr` - distance to the fence in IQR units (mean absolute deviation units if the IQR is zero);
+/// * `ZScore` / `ModifiedZScore` - (modified) z-score of the value;
+/// * `Percentile` - distance to the percentile bound.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outlier {
+    pub bound: DeserializationType,
+    pub score: f64,
+}
+
+/// Bounds of a column fitted by an `OutlierDetector`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fences {
+    pub detector: OutlierDetector,
+    pub lower: f64,
+    pub upper: f64,
+    center: f64,
+    scale: f64,
+}
+
+impl OutlierDetector {
+    /// Fit the detector bounds on the values of a column. Sorts `values` in place.
+    ///
+    /// # Return
+    /// `None` if there are no values, or all the values are equal for the detectors scoring by the
+    /// spread of the column (nothing could be an outlier).
+    pub fn fit(&self, values: &mut [f64]) -> Option<Fences> {
+        values.sort_by(|a, b| a.total_cmp(b));
+
+        let (lower, upper, center, scale) = match *self {
+            OutlierDetector::Iqr { k } => {
+                let q1 = quantile(values, 0.25)?;
+                let q3 = quantile(values, 0.75)?;
+                let iqr = q3 - q1;
+                let scale = if iqr == 0_f64 {
+                    // Fallback to the mean absolute deviation, the fences stay at the quartiles
+                    values.iter().map(|x| (x - q1).abs()).sum::<f64>() / values.len() as f64
+                } else {
+                    iqr
+                };
+                (q1 - k * iqr, q3 + k * iqr, 0_f64, scale)
+            }
+            OutlierDetector::ZScore { threshold } => {
+                let (mean, std) = mean_std(values)?;
+                (mean - threshold * std, mean + threshold * std, mean, std)
+            }
+            OutlierDetector::ModifiedZScore { threshold } => {
+                let med = quantile(values, 0.5)?;
+                let mut deviations: Vec<f64> = values.iter().map(|x| (x - med).abs()).collect();
+                let mut mad = median(&mut deviations)?;
+                if mad == 0_f64 {
+                    // Fallback to the mean absolute deviation, scaled to be MAD consistent
+                    mad = 1.253314 * deviations.iter().sum::<f64>() / deviations.len() as f64;
+                }
+                let scale = mad / 0.6745;
+                (med - threshold * scale, med + threshold * scale, med, scale)
+            }
+            OutlierDetector::Percentile { lower, upper } => (
+                quantile(values, lower / 100_f64)?,
+                quantile(values, upper / 100_f64)?,
+                0_f64,
+                1_f64,
+            ),
+        };
+
+        if scale == 0_f64 || !scale.is_finite() {
+            return None;
+        }
+
+        Some(Fences {
+            detector: *self,
+            lower,
+            upper,
+            center,
+            scale,
+        })
+    }
+}
+
+impl Fences {
+    /// Check a single value against the fitted bounds.
+    pub fn check(&self, value: f64) -> Option<Outlier> {
+        let bound = if value < self.lower {
+            self.lower
+        } else if value > self.upper {
+            self.upper
+        } else {
+            return None;
+        };
+
+        let score = match self.detector {
+            OutlierDetector::Iqr { .. } => (value - bound) / self.scale,
+            OutlierDetector::ZScore { .. } | OutlierDetector::ModifiedZScore { .. } => {
+                (value - self.center) / self.scale
+            }
+            OutlierDetector::Percentile { .. } => value - bound,
+        };
+
+        Some(Outlier {
+            bound: DeserializationType::FLOAT(bound),
+            score,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::OutlierDetector;
+
+    fn values() -> Vec<f64> {
+        vec![10., 11., 12., 10., 11., 13., 12., 100., 11., 12.]
+    }
+
+    fn flagged(detector: OutlierDetector) -> Vec<f64> {
+        let fences = detector.fit(&mut values()).unwrap();
+        values()
+            .into_iter()
+            .filter(|x| fences.check(*x).is_some())
+            .collect()
+    }
+
+    #[test]
+    fn test_detectors() {
+        assert_eq!(flagged(OutlierDetector::Iqr { k: 1.5 }), vec![100.]);
+        assert_eq!(
+            flagged(OutlierDetector::ZScore { threshold: 2.5 }),
+            vec![100.]
+        );
+        assert_eq!(
+            flagged(OutlierDetector::ModifiedZScore { threshold: 3.5 }),
+            vec![100.]
+        );
+        assert_eq!(
+            flagged(OutlierDetector::Percentile {
+                lower: 5.,
+                upper: 95.
+            }),
+            vec![100.]
+        );
+    }
+
+    #[test]
+    fn test_degenerate_quartiles() {
+        let mut zero_inflated = [1., 1., 1., 1., 1., 1., 1., 1., 100.];
+        let fences = OutlierDetector::Iqr { k: 1.5 }
+            .fit(&mut zero_inflated)
+            .unwrap();
+
+        assert_eq!((fences.lower, fences.upper), (1., 1.));
+        assert!(fences.check(1.).is_none());
+        // 99 away from the fence, mean absolute deviation of 11
+        assert_eq!(fences.check(100.).unwrap().score, 9.);
+    }
+
+    #[test]
+    fn test_constant_column() {
+        assert_eq!(
+            OutlierDetector::ZScore { threshold: 3. }.fit(&mut [1., 1., 1.]),
+            None
+        );
+        assert_eq!(OutlierDetector::Iqr { k: 1.5 }.fit(&mut [1., 1., 1.]), None);
+    }
+
+    #[test]
+    fn test_score() {
+        let fences = OutlierDetector::Iqr { k: 1.5 }.fit(&mut values()).unwrap();
+        let outlier = fences.check(100.).unwrap();
+
+        assert_eq!(outlier.score, 86.5);
+    }
+}
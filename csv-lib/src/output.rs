@@ -0,0 +1,51 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::dialect::LineTerminator;
+
+/// Options of `CsvToolkit::save_as`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveOptions {
+    /// Field delimiter of the saved file, the delimiter of the source file if `None`
+    pub delimiter: Option<u8>,
+    /// Write the header line
+    pub headers: bool,
+    /// Replace the file if it already exists
+    pub overwrite: bool,
+}
+
+impl Default for SaveOptions {
+    fn default() -> Self {
+        SaveOptions {
+            delimiter: None,
+            headers: true,
+            overwrite: true,
+        }
+    }
+}
+
+/// Line terminator to write, so rewritten files keep the line endings of the source file.
+///
+/// # Arguments
+///
+/// * `src` - Source csv file.
+/// * `terminator` - Record terminator the file was opened with (`None` for any line ending).
+///
+pub(crate) fn line_terminator(
+    src: &Path,
+    terminator: Option<u8>,
+) -> std::io::Result<LineTerminator> {
+    if let Some(terminator) = terminator {
+        return Ok(LineTerminator::Any(terminator));
+    }
+
+    let mut first_line = Vec::new();
+    BufReader::new(File::open(src)?).read_until(b'\n', &mut first_line)?;
+
+    if first_line.ends_with(b"\r\n") {
+        Ok(LineTerminator::CrLf)
+    } else {
+        Ok(LineTerminator::Any(b'\n'))
+    }
+}
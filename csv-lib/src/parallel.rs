@@ -0,0 +1,355 @@
+//! Multi-threaded passes over the data: the file is split into byte ranges aligned to record
+//! boundaries, the ranges are parsed in parallel and the parsed rows are handed over in row order.
+//!
+//! Only reading and parsing (`ParseOptions::parse_cell`, the bulk of a pass) run on the workers.
+//! Statistics, column types, type reports and gap indexes are accumulated on the calling thread,
+//! deliberately: the P² quantiles and the Space-Saving top values of `ColumnStatistics` depend on
+//! the order of the values, and merged moments differ from the sequential ones in the last bits, so
+//! per-chunk accumulators merged afterwards could not give the results of a sequential pass.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::thread;
+
+use csv::{Position, StringRecord, Terminator};
+
+use crate::constants::PARALLEL_CHUNK_BYTES;
+use crate::deserialization::{DeserializationType, ParseOptions};
+use crate::dialect::Dialect;
+use crate::error::{Error, Result};
+
+/// Threads and chunk size of the passes over the data (see `CsvToolkit::set_parallelism`).
+///
+/// Results do not depend on the parallelism: rows are parsed in parallel, but accumulated in
+/// their original order. Parsed rows of at most two waves of `threads` chunks are kept in memory
+/// (the next wave is parsed while the current one is accumulated).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Parallelism {
+    /// Number of worker threads, `1` for a sequential pass
+    pub threads: usize,
+    /// Approximate size of the byte range parsed by a worker at once
+    pub chunk_bytes: usize,
+}
+
+impl Default for Parallelism {
+    /// One thread per available CPU. Files smaller than a chunk are read sequentially.
+    fn default() -> Self {
+        Parallelism {
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            chunk_bytes: PARALLEL_CHUNK_BYTES,
+        }
+    }
+}
+
+impl Parallelism {
+    pub fn sequential() -> Self {
+        Parallelism {
+            threads: 1,
+            ..Parallelism::default()
+        }
+    }
+}
+
+/// Data row with the values of the selected columns parsed by `ParseOptions::parse_cell`.
+struct ParsedRow {
+    record: StringRecord,
+    values: Vec<DeserializationType>,
+}
+
+/// Rows parsed by a worker, up to the first error (its row is set by the caller).
+struct Chunk {
+    rows: Vec<ParsedRow>,
+    error: Option<Error>,
+}
+
+/// Everything needed to read the data file independently of the toolkit reader.
+pub(crate) struct RowScanner {
+    pub(crate) path: PathBuf,
+    pub(crate) data_position: Position,
+    pub(crate) dialect: Dialect,
+    pub(crate) comment: Option<u8>,
+    pub(crate) terminator: Terminator,
+    pub(crate) headers: Vec<String>,
+    pub(crate) options: ParseOptions,
+    pub(crate) parallelism: Parallelism,
+}
+
+impl RowScanner {
+    /// Call `f` with every data row, in order.
+    ///
+    /// # Arguments
+    ///
+    /// * `selected` - Columns to parse, values of the other columns are `EMPTY`.
+    /// * `f` - Called with the index, the raw record and the parsed values of a row (one per header
+    ///   at most, fewer for a short row).
+    ///
+    /// # Errors
+    /// csv, io, parse errors (with the row), errors of `f`;
+    pub(crate) fn for_each<S, F>(&self, selected: S, mut f: F) -> Result<()>
+    where
+        S: Fn(usize) -> bool + Sync,
+        F: FnMut(usize, &StringRecord, &[DeserializationType]) -> Result<()>,
+    {
+        let ranges = match self.parallelism.threads {
+            0 | 1 => Vec::new(),
+            _ => self.chunk_ranges()?,
+        };
+
+        if ranges.len() <= 1 {
+            return self.for_each_sequential(&selected, f);
+        }
+
+        let selected = &selected;
+        thread::scope(|scope| {
+            let spawn = |wave: &[(u64, u64)]| {
+                wave.iter()
+                    .map(|&(start, end)| {
+                        scope.spawn(move || self.parse_range(start, end, selected))
+                    })
+                    .collect::<Vec<_>>()
+            };
+
+            // The next wave is parsed while the current one is handed over
+            let mut waves = ranges.chunks(self.parallelism.threads);
+            let mut pending = waves.next().map(spawn);
+            let mut row_base = 0;
+
+            while let Some(handles) = pending {
+                pending = waves.next().map(spawn);
+
+                for handle in handles {
+                    let chunk = handle
+                        .join()
+                        .map_err(|_| Error::io("A worker thread panicked"))?;
+
+                    for (i, row) in chunk.rows.iter().enumerate() {
+                        f(row_base + i, &row.record, &row.values)?;
+                    }
+                    // The row a worker stopped at, counted from the start of the file
+                    if let Some(error) = chunk.error {
+                        return Err(error.relocate_row(row_base + chunk.rows.len()));
+                    }
+                    row_base += chunk.rows.len();
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    fn for_each_sequential<S, F>(&self, selected: &S, mut f: F) -> Result<()>
+    where
+        S: Fn(usize) -> bool,
+        F: FnMut(usize, &StringRecord, &[DeserializationType]) -> Result<()>,
+    {
+        let mut reader =
+            crate::csv_reader(&self.path, &self.dialect, self.comment, self.terminator)?;
+        reader.seek(self.data_position.clone())?;
+
+        for (row_id, record) in reader.records().enumerate() {
+            let record = record?;
+            let values = self
+                .parse(&record, selected)
+                .map_err(|e| e.at_row(row_id))?;
+            f(row_id, &record, &values)?;
+        }
+
+        Ok(())
+    }
+
+    fn parse_range<S>(&self, start: u64, end: u64, selected: &S) -> Chunk
+    where
+        S: Fn(usize) -> bool,
+    {
+        let mut rows = Vec::new();
+        let error = self.parse_range_into(start, end, selected, &mut rows).err();
+
+        Chunk { rows, error }
+    }
+
+    fn parse_range_into<S>(
+        &self,
+        start: u64,
+        end: u64,
+        selected: &S,
+        rows: &mut Vec<ParsedRow>,
+    ) -> Result<()>
+    where
+        S: Fn(usize) -> bool,
+    {
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(start))?;
+
+        let mut reader = crate::reader_builder(&self.dialect, self.comment, self.terminator)
+            .has_headers(false)
+            .from_reader(file.take(end - start));
+
+        for record in reader.records() {
+            let record = record?;
+            let values = self.parse(&record, selected)?;
+            rows.push(ParsedRow { record, values });
+        }
+
+        Ok(())
+    }
+
+    fn parse<S>(&self, record: &StringRecord, selected: &S) -> Result<Vec<DeserializationType>>
+    where
+        S: Fn(usize) -> bool,
+    {
+        self.headers
+            .iter()
+            .zip(record.iter())
+            .enumerate()
+            .map(|(col_id, (header, value))| match selected(col_id) {
+                true => self.options.parse_cell(header, value),
+                false => Ok(DeserializationType::EMPTY),
+            })
+            .collect()
+    }
+
+    /// Split the data (after the header line) into byte ranges of about `chunk_bytes`, each one
+    /// starting at a record boundary.
+    ///
+    /// Boundaries are found by a single pass tracking the quoting state as the csv reader does, so
+    /// quoted fields with line breaks are never cut.
+    fn chunk_ranges(&self) -> Result<Vec<(u64, u64)>> {
+        let mut file = File::open(&self.path)?;
+        let start = self.data_position.byte();
+        let len = file.metadata()?.len();
+        if len.saturating_sub(start) <= self.parallelism.chunk_bytes as u64 {
+            return Ok(vec![(start, len)]);
+        }
+
+        file.seek(SeekFrom::Start(start))?;
+        let mut scanner = BoundaryScanner::new(&self.dialect, self.comment, self.terminator);
+
+        let mut ranges = Vec::new();
+        let mut chunk_start = start;
+        let mut offset = start;
+        let mut buf = vec![0; 1 << 20];
+
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+
+            for b in &buf[..n] {
+                offset += 1;
+                if scanner.push(*b) && offset - chunk_start >= self.parallelism.chunk_bytes as u64 {
+                    ranges.push((chunk_start, offset));
+                    chunk_start = offset;
+                }
+            }
+        }
+
+        if chunk_start < offset {
+            ranges.push((chunk_start, offset));
+        }
+
+        Ok(ranges)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScanState {
+    StartRecord,
+    StartField,
+    InField,
+    InQuoted,
+    /// After the escape character of a quoted field
+    Escaped,
+    /// After a quote of a quoted field: the end of the field or a doubled quote
+    QuoteInQuoted,
+    InComment,
+}
+
+/// Quoting state machine of the csv reader, reduced to finding where records end.
+struct BoundaryScanner {
+    state: ScanState,
+    delimiter: u8,
+    quote: u8,
+    double_quotes: bool,
+    escape: Option<u8>,
+    comment: Option<u8>,
+    terminator: Terminator,
+}
+
+impl BoundaryScanner {
+    fn new(dialect: &Dialect, comment: Option<u8>, terminator: Terminator) -> Self {
+        BoundaryScanner {
+            state: ScanState::StartRecord,
+            delimiter: dialect.delimiter,
+            quote: dialect.quote,
+            double_quotes: dialect.double_quotes,
+            escape: dialect.escape,
+            comment,
+            terminator,
+        }
+    }
+
+    fn is_terminator(&self, b: u8) -> bool {
+        match self.terminator {
+            Terminator::Any(t) => b == t,
+            // `CRLF` accepts `\r`, `\n` and `\r\n`
+            _ => b == b'\r' || b == b'\n',
+        }
+    }
+
+    /// # Return
+    /// Whether `b` ends a record.
+    fn push(&mut self, b: u8) -> bool {
+        use ScanState::*;
+
+        let state = match self.state {
+            // Blank lines are skipped by the reader, cutting there is harmless
+            StartRecord | StartField | InField | QuoteInQuoted if self.is_terminator(b) => {
+                self.state = StartRecord;
+                return true;
+            }
+            // Comments end at `\n` whatever the terminator
+            InComment if b == b'\n' => {
+                self.state = StartRecord;
+                return true;
+            }
+            StartRecord if Some(b) == self.comment => InComment,
+            StartRecord | StartField if b == self.quote => InQuoted,
+            StartRecord | StartField | InField | QuoteInQuoted if b == self.delimiter => StartField,
+            StartRecord | StartField | InField => InField,
+            InQuoted if Some(b) == self.escape => Escaped,
+            InQuoted if b == self.quote => QuoteInQuoted,
+            InQuoted | Escaped => InQuoted,
+            QuoteInQuoted if b == self.quote && self.double_quotes => InQuoted,
+            QuoteInQuoted => InField,
+            InComment => InComment,
+        };
+
+        self.state = state;
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BoundaryScanner;
+    use crate::dialect::Dialect;
+
+    #[test]
+    fn test_boundary_scanner() {
+        let data = b"1,\"a\nb\",\"say \"\"hi\"\"\"\n#c,\"\n2,x\"y,z\r\n3";
+        let mut scanner =
+            BoundaryScanner::new(&Dialect::default(), Some(b'#'), csv::Terminator::CRLF);
+
+        let ends: Vec<usize> = data
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| scanner.push(**b))
+            .map(|(i, _)| i + 1)
+            .collect();
+
+        // The quoted line break, the quote of the comment and the quote inside a field are skipped
+        assert_eq!(ends, vec![21, 26, 34, 35]);
+    }
+}
@@ -0,0 +1,907 @@
+//! Lazy pipeline of row operations, fused into as few passes over the data as possible.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::deserialization::{parse_headers, DeserializationType, ParseOptions};
+use crate::encoding::{Encoder, Encoding, EncodingMethod, LevelAccumulator};
+use crate::error::{Error, Result};
+use crate::expression::Expression;
+use crate::filling::{ColumnAccumulator, FillStrategy};
+use crate::normalization::{Normalization, NormalizationMethod, Scaler};
+use crate::outliers::{Fences, OutlierDetector};
+use crate::user_input::UserInput;
+use crate::{column_plan, CsvToolkit};
+
+/// Operations queued by `CsvToolkit::pipeline`, run by `run`.
+///
+/// Every row streams through the steps in the order they were queued, so a step sees the data as
+/// the previous steps left it. Steps which need fitted parameters (`fill_gaps` with statistics,
+/// `clip`, `normalize`, `encode`) are fitted by reading the data up to them; fits which do not
+/// depend on each other share the same pass. The data is written once, by the last pass, and
+/// statistics are computed while it is written.
+///
+/// ```no_run
+/// # use std::collections::HashMap;
+/// # use csv_lib::{filling::FillStrategy, normalization::NormalizationMethod};
+/// # use csv_lib::{outliers::OutlierDetector, CsvToolkit};
+/// # fn main() -> csv_lib::error::Result<()> {
+/// let mut toolkit = CsvToolkit::open("data.csv")?;
+/// let report = toolkit
+///     .pipeline()
+///     .fill_gaps(HashMap::from([("Age".to_owned(), FillStrategy::Median)]))
+///     .clip(HashMap::from([("Income".to_owned(), OutlierDetector::Iqr { k: 1.5 })]))
+///     .normalize(HashMap::from([("Income".to_owned(), NormalizationMethod::ZScore)]))
+///     .filter_rows("Age >= 18")
+///     .run()?;
+///
+/// // The fill and the clip are fitted by the first pass, the normalization of the clipped
+/// // values by the second one, the third one writes the data
+/// assert_eq!(report.passes, 3);
+/// # Ok(())
+/// # }
+/// ```
+pub struct Pipeline<'a> {
+    toolkit: &'a mut CsvToolkit,
+    steps: Vec<Step>,
+}
+
+/// Parameters fitted by a pipeline run, in the order of their steps.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PipelineReport {
+    /// Fences of each `clip` step, by column
+    pub fences: Vec<BTreeMap<String, Fences>>,
+    /// Scaler of each `normalize` step
+    pub scalers: Vec<Scaler>,
+    /// Encoder of each `encode` step
+    pub encoders: Vec<Encoder>,
+    /// Number of data rows written
+    pub rows: usize,
+    /// Number of reads of the data, the one writing the result included
+    pub passes: usize,
+}
+
+#[derive(Debug, Clone)]
+enum Step {
+    FillGaps(HashMap<String, FillStrategy>),
+    Clip(HashMap<String, OutlierDetector>),
+    Normalize(HashMap<String, NormalizationMethod>),
+    NormalizeWith(Scaler),
+    Encode(HashMap<String, EncodingMethod>),
+    EncodeWith(Encoder),
+    DropColumns(Vec<String>),
+    FilterRows(String),
+}
+
+impl<'a> Pipeline<'a> {
+    pub(crate) fn new(toolkit: &'a mut CsvToolkit) -> Self {
+        Pipeline {
+            toolkit,
+            steps: Vec::new(),
+        }
+    }
+
+    /// Fill gaps as `CsvToolkit::fill_gaps` does. Gaps are the empty cells (and null markers) of
+    /// the rows reaching the step.
+    pub fn fill_gaps(mut self, strategies: HashMap<String, FillStrategy>) -> Self {
+        self.steps.push(Step::FillGaps(strategies));
+        self
+    }
+
+    /// Replace the outliers found by the detector of a column with the nearest fence (see
+    /// `OutlierTreatment::Clip`).
+    pub fn clip(mut self, detectors: HashMap<String, OutlierDetector>) -> Self {
+        self.steps.push(Step::Clip(detectors));
+        self
+    }
+
+    /// Normalize numeric columns as `CsvToolkit::normalizing` does.
+    pub fn normalize(mut self, methods: HashMap<String, NormalizationMethod>) -> Self {
+        self.steps.push(Step::Normalize(methods));
+        self
+    }
+
+    /// Apply an already fitted `Scaler`, as `CsvToolkit::apply_normalization` does.
+    pub fn normalize_with(mut self, scaler: &Scaler) -> Self {
+        self.steps.push(Step::NormalizeWith(scaler.clone()));
+        self
+    }
+
+    /// Encode categorical columns as `CsvToolkit::encoding` does.
+    pub fn encode(mut self, methods: HashMap<String, EncodingMethod>) -> Self {
+        self.steps.push(Step::Encode(methods));
+        self
+    }
+
+    /// Apply an already fitted `Encoder`, as `CsvToolkit::apply_encoding` does.
+    pub fn encode_with(mut self, encoder: &Encoder) -> Self {
+        self.steps.push(Step::EncodeWith(encoder.clone()));
+        self
+    }
+
+    /// Remove columns (by header).
+    pub fn drop_columns(mut self, headers: Vec<String>) -> Self {
+        self.steps.push(Step::DropColumns(headers));
+        self
+    }
+
+    /// Keep the rows for which the expression (see `expression::Expression`) is `true`. Rows for
+    /// which it is `EMPTY` are dropped as well.
+    pub fn filter_rows(mut self, predicate: impl Into<String>) -> Self {
+        self.steps.push(Step::FilterRows(predicate.into()));
+        self
+    }
+
+    /// Run the queued steps and replace the data with their result.
+    ///
+    /// `outliers` and `fences` are cleared if rows are filtered or columns are moved, since their
+    /// indexes would be stale; statistics and `gaps` are recomputed.
+    ///
+    /// # Return
+    /// Fitted parameters of the steps and the number of passes over the data.
+    ///
+    /// # Errors
+    /// Unknown column, invalid expression, non numeric column for statistics, normalization or a
+    /// filter which is not a boolean, evaluation errors, io; the data is left untouched;
+    pub fn run(self) -> Result<PipelineReport> {
+        let Pipeline { toolkit, steps } = self;
+        let mut report = PipelineReport::default();
+        if steps.is_empty() {
+            return Ok(report);
+        }
+
+        let reindexes = steps.iter().any(|step| {
+            matches!(
+                step,
+                Step::FilterRows(_) | Step::DropColumns(_) | Step::Encode(_) | Step::EncodeWith(_)
+            )
+        });
+        let options = toolkit.parse_options.clone();
+        let mut stages: Vec<Stage> = steps.into_iter().map(Stage::new).collect();
+
+        // Fitting passes: rows go through the stages up to the ones fitted by the pass
+        while stages.iter().any(|stage| stage.op.is_none()) {
+            let (actions, _) = schedule(&mut stages, &toolkit.headers)?;
+
+            toolkit.row_scanner().for_each(
+                |_| false,
+                |_, record, _| {
+                    let row = record.iter().map(|s| s.to_owned()).collect();
+                    feed(&mut stages, &actions, &options, row)?;
+                    Ok(())
+                },
+            )?;
+
+            for stage in stages.iter_mut() {
+                stage.finish_fit()?;
+            }
+            report.passes += 1;
+        }
+
+        // Writing pass: every stage is fitted
+        let (actions, headers) = schedule(&mut stages, &toolkit.headers)?;
+        let mut rows = 0;
+        toolkit.expand_records(headers, |_, row| {
+            let row = feed(&mut stages, &actions, &options, row)?;
+            rows += usize::from(row.is_some());
+            Ok(row.into_iter().collect())
+        })?;
+        report.passes += 1;
+        report.rows = rows;
+
+        if reindexes {
+            toolkit.outliers.clear();
+            toolkit.fences.clear();
+        }
+
+        for stage in stages {
+            stage.report(&mut report);
+        }
+
+        Ok(report)
+    }
+}
+
+/// What a stage does with the rows of a pass.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Apply,
+    Fit,
+    /// Pass rows through untouched: the stage is fitted by a later pass
+    Skip,
+}
+
+/// Step with its columns resolved against the headers of the rows reaching it.
+struct Stage {
+    step: Step,
+    /// Headers of the rows reaching the stage, known once the previous stages are resolved
+    input: Option<Vec<String>>,
+    fit: Option<Fit>,
+    op: Option<Op>,
+    /// Index of the next row reaching the stage in the current pass
+    rows: usize,
+}
+
+/// Accumulators of a stage during its fitting pass.
+enum Fit {
+    Fill {
+        plan: HashMap<usize, FillStrategy>,
+        accumulators: HashMap<usize, ColumnAccumulator>,
+        back_fill: HashMap<usize, HashMap<usize, String>>,
+    },
+    Clip {
+        plan: HashMap<usize, OutlierDetector>,
+        samples: HashMap<usize, Vec<f64>>,
+    },
+    Normalize {
+        plan: HashMap<usize, NormalizationMethod>,
+        samples: HashMap<usize, Vec<f64>>,
+    },
+    Encode {
+        plan: HashMap<usize, EncodingMethod>,
+        /// Label column of target encoded columns
+        labels: HashMap<usize, usize>,
+        levels: HashMap<usize, LevelAccumulator>,
+    },
+}
+
+/// Fitted operation of a stage.
+enum Op {
+    Fill {
+        plan: HashMap<usize, FillStrategy>,
+        constants: HashMap<usize, String>,
+        expressions: HashMap<usize, Expression>,
+        back_fill: HashMap<usize, HashMap<usize, String>>,
+        last_seen: HashMap<usize, String>,
+    },
+    Clip(HashMap<usize, Fences>),
+    Normalize(HashMap<usize, Normalization>),
+    Encode(HashMap<usize, Encoding>),
+    Drop(HashSet<usize>),
+    Filter(Expression),
+}
+
+impl Stage {
+    fn new(step: Step) -> Self {
+        Stage {
+            step,
+            input: None,
+            fit: None,
+            op: None,
+            rows: 0,
+        }
+    }
+
+    fn input(&self) -> &[String] {
+        self.input.as_deref().unwrap_or_default()
+    }
+
+    /// Set the headers of the rows reaching the stage and build the operation if it needs no fit.
+    ///
+    /// # Errors
+    /// Unknown column, invalid expression;
+    fn resolve(&mut self, headers: &[String]) -> Result<()> {
+        if self.input.is_some() {
+            return Ok(());
+        }
+
+        let op = match &self.step {
+            Step::FillGaps(strategies) => {
+                let plan = column_plan(headers, strategies.clone())?;
+                match plan.values().any(|s| s.needs_statistics()) {
+                    true => None,
+                    false => Some(fill_op(plan, HashMap::new(), HashMap::new(), headers)?),
+                }
+            }
+            Step::Clip(detectors) => {
+                column_plan(headers, detectors.clone())?;
+                None
+            }
+            Step::Normalize(methods) => {
+                column_plan(headers, methods.clone())?;
+                None
+            }
+            Step::Encode(methods) => {
+                labels(headers, &column_plan(headers, methods.clone())?)?;
+                None
+            }
+            Step::NormalizeWith(scaler) => {
+                Some(Op::Normalize(column_plan(headers, scaler.columns.clone())?))
+            }
+            Step::EncodeWith(encoder) => {
+                Some(Op::Encode(column_plan(headers, encoder.columns.clone())?))
+            }
+            Step::DropColumns(dropped) => Some(Op::Drop(
+                column_plan(headers, dropped.iter().map(|h| (h.clone(), ())))?
+                    .into_keys()
+                    .collect(),
+            )),
+            Step::FilterRows(predicate) => Some(Op::Filter(Expression::parse(predicate, headers)?)),
+        };
+
+        self.input = Some(headers.to_vec());
+        self.op = op;
+        Ok(())
+    }
+
+    /// Columns whose values the stage reads, to be fitted or applied.
+    fn reads(&self) -> Result<Vec<usize>> {
+        let input = self.input();
+        let mut columns = match &self.step {
+            Step::FillGaps(strategies) => {
+                let mut columns = Vec::new();
+                for (col_id, strategy) in column_plan(input, strategies.clone())? {
+                    columns.push(col_id);
+                    if let FillStrategy::Input(UserInput::EXPR(expr)) = strategy {
+                        columns.extend(Expression::parse(&expr, input)?.columns());
+                    }
+                }
+                columns
+            }
+            Step::Encode(methods) => {
+                let plan = column_plan(input, methods.clone())?;
+                let labels = labels(input, &plan)?;
+                plan.into_keys().chain(labels.into_values()).collect()
+            }
+            Step::EncodeWith(encoder) => column_plan(input, encoder.columns.clone())?
+                .into_keys()
+                .collect(),
+            Step::FilterRows(predicate) => Expression::parse(predicate, input)?.columns(),
+            Step::DropColumns(_) => Vec::new(),
+            Step::Clip(_) | Step::Normalize(_) | Step::NormalizeWith(_) => self.writes()?,
+        };
+
+        columns.sort_unstable();
+        columns.dedup();
+        Ok(columns)
+    }
+
+    /// Columns whose values the stage rewrites in place.
+    fn writes(&self) -> Result<Vec<usize>> {
+        let headers: Vec<&String> = match &self.step {
+            Step::FillGaps(strategies) => strategies.keys().collect(),
+            Step::Clip(detectors) => detectors.keys().collect(),
+            Step::Normalize(methods) => methods.keys().collect(),
+            Step::NormalizeWith(scaler) => scaler.columns.keys().collect(),
+            _ => Vec::new(),
+        };
+
+        let plan = column_plan(self.input(), headers.into_iter().map(|h| (h.clone(), ())))?;
+        Ok(plan.into_keys().collect())
+    }
+
+    /// Headers of the rows leaving the stage, with the index of the input column of each one.
+    ///
+    /// # Return
+    /// `None` while an `encode` stage is not fitted.
+    fn output(&self) -> Option<(Vec<String>, Vec<usize>)> {
+        let input = self.input();
+        let mut headers = Vec::with_capacity(input.len());
+        let mut sources = Vec::with_capacity(input.len());
+
+        for (col_id, header) in input.iter().enumerate() {
+            let output = match (&self.op, &self.step) {
+                (None, Step::Encode(_)) => return None,
+                (Some(Op::Drop(dropped)), _) if dropped.contains(&col_id) => Vec::new(),
+                (Some(Op::Encode(plan)), _) => match plan.get(&col_id) {
+                    Some(encoding) => encoding.headers(header),
+                    None => vec![header.clone()],
+                },
+                _ => vec![header.clone()],
+            };
+
+            sources.extend(output.iter().map(|_| col_id));
+            headers.extend(output);
+        }
+
+        Some((headers, sources))
+    }
+
+    fn start_fit(&mut self) -> Result<()> {
+        let input = self.input();
+        let fit = match &self.step {
+            Step::FillGaps(strategies) => Fit::Fill {
+                plan: column_plan(input, strategies.clone())?,
+                accumulators: HashMap::new(),
+                back_fill: HashMap::new(),
+            },
+            Step::Clip(detectors) => Fit::Clip {
+                plan: column_plan(input, detectors.clone())?,
+                samples: HashMap::new(),
+            },
+            Step::Normalize(methods) => Fit::Normalize {
+                plan: column_plan(input, methods.clone())?,
+                samples: HashMap::new(),
+            },
+            Step::Encode(methods) => {
+                let plan = column_plan(input, methods.clone())?;
+                Fit::Encode {
+                    labels: labels(input, &plan)?,
+                    levels: plan
+                        .keys()
+                        .map(|col_id| (*col_id, LevelAccumulator::default()))
+                        .collect(),
+                    plan,
+                }
+            }
+            _ => return Ok(()),
+        };
+
+        self.fit = Some(fit);
+        Ok(())
+    }
+
+    /// Register a row reaching the stage in its fitting pass.
+    ///
+    /// # Errors
+    /// Non numeric value for statistics, normalization or as a target label;
+    fn push(&mut self, row_id: usize, row: &[String], options: &ParseOptions) -> Result<()> {
+        let input = self.input.as_deref().unwrap_or_default();
+
+        match &mut self.fit {
+            Some(Fit::Fill {
+                plan,
+                accumulators,
+                back_fill,
+            }) => {
+                for (col_id, raw) in row.iter().enumerate() {
+                    let Some(strategy) = plan.get(&col_id).filter(|s| s.needs_statistics()) else {
+                        continue;
+                    };
+                    let raw = raw.trim();
+
+                    let value = options.parse_cell(&input[col_id], raw)?;
+                    let rows = accumulators.entry(col_id).or_default().push(
+                        strategy,
+                        &input[col_id],
+                        row_id,
+                        raw,
+                        &value,
+                    )?;
+
+                    for gap_row in rows {
+                        back_fill
+                            .entry(gap_row)
+                            .or_default()
+                            .insert(col_id, raw.to_owned());
+                    }
+                }
+            }
+            Some(Fit::Clip { plan, samples }) => {
+                push_samples(samples, plan.keys(), input, row_id, row, options)?;
+            }
+            Some(Fit::Normalize { plan, samples }) => {
+                push_samples(samples, plan.keys(), input, row_id, row, options)?;
+            }
+            Some(Fit::Encode { labels, levels, .. }) => {
+                for (col_id, accumulator) in levels.iter_mut() {
+                    let Some(value) = row.get(*col_id) else {
+                        continue;
+                    };
+                    if options.is_null(&input[*col_id], value) {
+                        continue;
+                    }
+
+                    let label = match labels.get(col_id) {
+                        Some(label_id) => {
+                            let raw = row.get(*label_id).map(String::as_str).unwrap_or_default();
+                            match options.parse_cell(&input[*label_id], raw)? {
+                                DeserializationType::EMPTY => None,
+                                y if y.is_numeric() => y.as_f64(),
+                                other => {
+                                    return Err(Error::type_mismatch("number", other.type_name())
+                                        .at_row(row_id)
+                                        .in_column(input[*label_id].as_str())
+                                        .with_value(raw))
+                                }
+                            }
+                        }
+                        None => None,
+                    };
+
+                    accumulator.push(value.trim(), label);
+                }
+            }
+            None => {}
+        }
+
+        Ok(())
+    }
+
+    /// Build the operation of a stage fitted by the pass which just ended.
+    ///
+    /// # Errors
+    /// A category missing from an explicit ordinal order;
+    fn finish_fit(&mut self) -> Result<()> {
+        let Some(fit) = self.fit.take() else {
+            return Ok(());
+        };
+        let input = self.input();
+
+        let op = match fit {
+            Fit::Fill {
+                plan,
+                mut accumulators,
+                back_fill,
+            } => {
+                let mut constants = HashMap::new();
+                for (col_id, accumulator) in accumulators.iter_mut() {
+                    if let Some(value) = accumulator.fill_value(&plan[col_id]) {
+                        constants.insert(*col_id, value);
+                    }
+                }
+
+                fill_op(plan, constants, back_fill, input)?
+            }
+            Fit::Clip { plan, samples } => Op::Clip(
+                samples
+                    .into_iter()
+                    .filter_map(|(col_id, mut values)| {
+                        plan[&col_id].fit(&mut values).map(|f| (col_id, f))
+                    })
+                    .collect(),
+            ),
+            Fit::Normalize { plan, samples } => Op::Normalize(
+                samples
+                    .into_iter()
+                    .filter_map(|(col_id, mut values)| {
+                        plan[&col_id].fit(&mut values).map(|n| (col_id, n))
+                    })
+                    .collect(),
+            ),
+            Fit::Encode { plan, levels, .. } => {
+                // Generated headers should not collide with the existing ones
+                let mut taken: HashSet<String> = parse_headers(input.to_vec())
+                    .into_iter()
+                    .chain(input.iter().cloned())
+                    .collect();
+
+                let mut col_ids: Vec<&usize> = plan.keys().collect();
+                col_ids.sort();
+
+                let mut encodings = HashMap::with_capacity(plan.len());
+                for col_id in col_ids {
+                    let encoding =
+                        plan[col_id].fit(&input[*col_id], &levels[col_id], &mut taken)?;
+                    encodings.insert(*col_id, encoding);
+                }
+
+                Op::Encode(encodings)
+            }
+        };
+
+        self.op = Some(op);
+        Ok(())
+    }
+
+    /// Add the fitted parameters of the stage to the `report`.
+    fn report(self, report: &mut PipelineReport) {
+        let input = self.input.unwrap_or_default();
+        match (self.step, self.op) {
+            (Step::Clip(_), Some(Op::Clip(fences))) => report.fences.push(
+                fences
+                    .into_iter()
+                    .map(|(col_id, f)| (input[col_id].clone(), f))
+                    .collect(),
+            ),
+            (Step::Normalize(_), Some(Op::Normalize(normalizations))) => {
+                report.scalers.push(Scaler::new(
+                    normalizations
+                        .into_iter()
+                        .map(|(col_id, n)| (input[col_id].clone(), n))
+                        .collect(),
+                ))
+            }
+            (Step::Encode(_), Some(Op::Encode(encodings))) => report.encoders.push(Encoder::new(
+                encodings
+                    .into_iter()
+                    .map(|(col_id, e)| (input[col_id].clone(), e))
+                    .collect(),
+            )),
+            _ => {}
+        }
+    }
+}
+
+impl Op {
+    /// # Arguments
+    ///
+    /// * `row_id` - Index of the row among the rows reaching the stage.
+    /// * `row` - Values of the row.
+    /// * `input` - Headers of the rows reaching the stage.
+    ///
+    /// # Return
+    /// The row to pass to the next stage or `None` to drop the row.
+    fn apply(
+        &mut self,
+        row_id: usize,
+        mut row: Vec<String>,
+        input: &[String],
+        options: &ParseOptions,
+    ) -> Result<Option<Vec<String>>> {
+        match self {
+            Op::Fill {
+                plan,
+                constants,
+                expressions,
+                back_fill,
+                last_seen,
+            } => {
+                let mut gaps = HashSet::new();
+                for (col_id, cell) in row.iter().enumerate() {
+                    if plan.contains_key(&col_id)
+                        && options.parse_cell(&input[col_id], cell)? == DeserializationType::EMPTY
+                    {
+                        gaps.insert(col_id);
+                    }
+                }
+
+                // Expressions see the row as it was before filling
+                let parsed = match expressions.keys().any(|col_id| gaps.contains(col_id)) {
+                    true => Some(options.parse_row(input, &row)?),
+                    false => None,
+                };
+
+                for (col_id, cell) in row.iter_mut().enumerate() {
+                    let Some(strategy) = plan.get(&col_id) else {
+                        continue;
+                    };
+
+                    if !gaps.contains(&col_id) {
+                        if *strategy == FillStrategy::ForwardFill {
+                            last_seen.insert(col_id, cell.trim().to_owned());
+                        }
+                        continue;
+                    }
+
+                    let fill = match strategy {
+                        FillStrategy::ForwardFill => last_seen.get(&col_id).cloned(),
+                        FillStrategy::BackFill => {
+                            back_fill.get(&row_id).and_then(|r| r.get(&col_id)).cloned()
+                        }
+                        FillStrategy::Input(UserInput::EXPR(_)) => {
+                            match (expressions.get(&col_id), parsed.as_ref()) {
+                                (Some(expr), Some(values)) => {
+                                    Some(expr.evaluate(values)?.to_cell_string())
+                                }
+                                _ => None,
+                            }
+                        }
+                        _ => constants.get(&col_id).cloned(),
+                    };
+
+                    if let Some(value) = fill {
+                        *cell = value;
+                    }
+                }
+            }
+            Op::Clip(fences) => {
+                for (col_id, fences) in fences.iter() {
+                    let Some(cell) = row.get_mut(*col_id) else {
+                        continue;
+                    };
+
+                    let outlier = options
+                        .parse_cell(&input[*col_id], cell)?
+                        .as_f64()
+                        .and_then(|x| fences.check(x));
+                    if let Some(outlier) = outlier {
+                        *cell = outlier.bound.to_cell_string();
+                    }
+                }
+            }
+            Op::Normalize(normalizations) => {
+                for (col_id, normalization) in normalizations.iter() {
+                    let Some(cell) = row.get_mut(*col_id) else {
+                        continue;
+                    };
+
+                    if let Some(x) = options.parse_cell(&input[*col_id], cell)?.as_f64() {
+                        *cell = normalization.transform(x)?.to_string();
+                    }
+                }
+            }
+            Op::Encode(encodings) => {
+                let mut encoded = Vec::with_capacity(row.len());
+                for (col_id, value) in row.into_iter().enumerate() {
+                    match encodings.get(&col_id) {
+                        Some(encoding) => {
+                            let category =
+                                (!options.is_null(&input[col_id], &value)).then_some(value.trim());
+                            encoded.extend(encoding.encode(category));
+                        }
+                        None => encoded.push(value),
+                    }
+                }
+                row = encoded;
+            }
+            Op::Drop(dropped) => {
+                row = row
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(col_id, _)| !dropped.contains(col_id))
+                    .map(|(_, value)| value)
+                    .collect();
+            }
+            Op::Filter(predicate) => match predicate.evaluate(&options.parse_row(input, &row)?)? {
+                DeserializationType::BOOLEAN(true) => {}
+                DeserializationType::BOOLEAN(false) | DeserializationType::EMPTY => {
+                    return Ok(None)
+                }
+                other => {
+                    return Err(Error::type_mismatch("boolean", other.type_name()).at_row(row_id))
+                }
+            },
+        }
+
+        Ok(Some(row))
+    }
+}
+
+/// Fill operation, with the constants of the statistics (if any) and of the user input.
+fn fill_op(
+    plan: HashMap<usize, FillStrategy>,
+    mut constants: HashMap<usize, String>,
+    back_fill: HashMap<usize, HashMap<usize, String>>,
+    headers: &[String],
+) -> Result<Op> {
+    let mut expressions = HashMap::new();
+    for (col_id, strategy) in plan.iter() {
+        match strategy {
+            FillStrategy::Input(UserInput::VALUE(value)) => {
+                constants.insert(*col_id, value.clone());
+            }
+            FillStrategy::Input(UserInput::EXPR(expr)) => {
+                expressions.insert(*col_id, Expression::parse(expr, headers)?);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(Op::Fill {
+        plan,
+        constants,
+        expressions,
+        back_fill,
+        last_seen: HashMap::new(),
+    })
+}
+
+/// Label column of every target encoded column.
+fn labels(
+    headers: &[String],
+    plan: &HashMap<usize, EncodingMethod>,
+) -> Result<HashMap<usize, usize>> {
+    let mut labels = HashMap::new();
+    for (col_id, method) in plan {
+        if let EncodingMethod::Target { label, .. } = method {
+            let label_plan = column_plan(headers, HashMap::from([(label.clone(), ())]))?;
+            labels.extend(label_plan.into_keys().map(|label_id| (*col_id, label_id)));
+        }
+    }
+
+    Ok(labels)
+}
+
+/// Add the non empty values of the `columns` of a row to their samples.
+///
+/// # Errors
+/// Non numeric value;
+fn push_samples<'c>(
+    samples: &mut HashMap<usize, Vec<f64>>,
+    columns: impl Iterator<Item = &'c usize>,
+    input: &[String],
+    row_id: usize,
+    row: &[String],
+    options: &ParseOptions,
+) -> Result<()> {
+    for col_id in columns {
+        let Some(raw) = row.get(*col_id) else {
+            continue;
+        };
+
+        match options.parse_cell(&input[*col_id], raw)? {
+            DeserializationType::EMPTY => continue,
+            value if value.is_numeric() => samples
+                .entry(*col_id)
+                .or_default()
+                .push(value.as_f64().unwrap_or_default()),
+            other => {
+                return Err(Error::type_mismatch("number", other.type_name())
+                    .at_row(row_id)
+                    .in_column(input[*col_id].as_str())
+                    .with_value(raw.as_str()))
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Prepare the stages for a pass over the data.
+///
+/// A stage is fitted as soon as the values it reads are final at its position in the pass: the
+/// stages before it are fitted, or rewrite other columns only.
+///
+/// # Arguments
+///
+/// * `stages` - Every stage of the pipeline.
+/// * `source` - Headers of the data.
+///
+/// # Return
+/// The action of every stage reached by the rows of the pass (rows stop after the last one), and
+/// the headers of the rows leaving it.
+fn schedule(stages: &mut [Stage], source: &[String]) -> Result<(Vec<Action>, Vec<String>)> {
+    let mut headers = source.to_vec();
+    // Columns whose values are not final at this point of the pass
+    let mut dirty = vec![false; headers.len()];
+    let mut actions = Vec::with_capacity(stages.len());
+
+    for stage in stages.iter_mut() {
+        stage.resolve(&headers)?;
+        stage.rows = 0;
+        if let Some(Op::Fill { last_seen, .. }) = &mut stage.op {
+            last_seen.clear();
+        }
+
+        let clean = stage.reads()?.iter().all(|col_id| !dirty[*col_id]);
+        let action = match (&stage.op, clean) {
+            // Rows kept by the filter are not known yet
+            (Some(Op::Filter(_)), false) => break,
+            (Some(Op::Fill { .. } | Op::Clip(_) | Op::Normalize(_)), false) => Action::Skip,
+            // Columns are moved (and encoded) whatever their values
+            (Some(_), _) => Action::Apply,
+            (None, true) => {
+                stage.start_fit()?;
+                Action::Fit
+            }
+            (None, false) => Action::Skip,
+        };
+        actions.push(action);
+
+        // Columns rewritten by a stage which is not applied are not final
+        let writes = match action {
+            Action::Apply => Vec::new(),
+            Action::Fit | Action::Skip => stage.writes()?,
+        };
+        let Some((output, sources)) = stage.output() else {
+            break;
+        };
+        dirty = sources
+            .iter()
+            .map(|col_id| dirty[*col_id] || writes.contains(col_id))
+            .collect();
+        headers = output;
+    }
+
+    Ok((actions, headers))
+}
+
+/// Pass a row through the stages of a pass.
+///
+/// # Return
+/// The row leaving the last stage, `None` if it was dropped.
+fn feed(
+    stages: &mut [Stage],
+    actions: &[Action],
+    options: &ParseOptions,
+    mut row: Vec<String>,
+) -> Result<Option<Vec<String>>> {
+    for (stage, action) in stages.iter_mut().zip(actions) {
+        let row_id = stage.rows;
+        stage.rows += 1;
+
+        match (action, &mut stage.op) {
+            (Action::Apply, Some(op)) => {
+                let input = stage.input.as_deref().unwrap_or_default();
+                match op.apply(row_id, row, input, options)? {
+                    Some(applied) => row = applied,
+                    None => return Ok(None),
+                }
+            }
+            (Action::Fit, _) => stage.push(row_id, &row, options)?,
+            _ => {}
+        }
+    }
+
+    Ok(Some(row))
+}
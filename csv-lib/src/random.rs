@@ -0,0 +1,58 @@
+/// Small seeded pseudo random generator (SplitMix64), so splits and resampling are reproducible
+/// across platforms and crate versions. Not suitable for cryptography.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        SplitMix64 { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1_u64 << 53) as f64
+    }
+
+    /// Uniform value in `[0, n)`. `n` should be positive.
+    pub fn below(&mut self, n: usize) -> usize {
+        ((self.next_u64() as u128 * n as u128) >> 64) as usize
+    }
+
+    /// Fisher-Yates shuffle.
+    pub fn shuffle<T>(&mut self, values: &mut [T]) {
+        for i in (1..values.len()).rev() {
+            values.swap(i, self.below(i + 1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SplitMix64;
+
+    #[test]
+    fn test_reproducible() {
+        let mut a = SplitMix64::new(42);
+        let mut b = SplitMix64::new(42);
+
+        let mut values: Vec<usize> = (0..10).collect();
+        a.shuffle(&mut values);
+        let mut other: Vec<usize> = (0..10).collect();
+        b.shuffle(&mut other);
+
+        assert_eq!(values, other);
+        assert_ne!(values, (0..10).collect::<Vec<usize>>());
+        assert!((0..1000).all(|_| a.below(7) < 7 && a.next_f64() < 1.));
+    }
+}
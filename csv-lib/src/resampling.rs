@@ -0,0 +1,176 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::error::{Error, Result};
+use crate::random::SplitMix64;
+
+/// How `CsvToolkit::balance` changes the class distribution of a label column.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResamplingMethod {
+    /// Drop random rows of the larger classes.
+    RandomUnder,
+    /// Duplicate random rows (with replacement) of the smaller classes.
+    RandomOver,
+    /// Synthesize rows of the smaller classes by interpolating the numeric columns between a row
+    /// and one of its `k` nearest neighbours of the same class (SMOTE, Chawla et al., 2002).
+    Smote { k: usize },
+}
+
+impl ResamplingMethod {
+    /// Number of rows of every class after resampling.
+    ///
+    /// # Arguments
+    ///
+    /// * `counts` - Number of rows per class.
+    /// * `ratio` - Target ratio of the smallest to the largest class, in `(0, 1]` (`1` for equal classes).
+    ///
+    /// # Errors
+    /// A ratio out of range.
+    pub fn targets(
+        &self,
+        counts: &BTreeMap<String, usize>,
+        ratio: f64,
+    ) -> Result<BTreeMap<String, usize>> {
+        if !(ratio > 0. && ratio <= 1.) {
+            return Err(Error::invalid_argument(format!(
+                "Resampling ratio should be in (0, 1], got {ratio}"
+            )));
+        }
+
+        let smallest = counts.values().copied().min().unwrap_or_default();
+        let largest = counts.values().copied().max().unwrap_or_default();
+
+        Ok(counts
+            .iter()
+            .map(|(class, count)| {
+                let target = match self {
+                    ResamplingMethod::RandomUnder => {
+                        (*count).min((smallest as f64 / ratio).ceil() as usize)
+                    }
+                    _ => (*count).max((largest as f64 * ratio).round() as usize),
+                };
+                (class.clone(), target)
+            })
+            .collect())
+    }
+}
+
+/// Numeric features of a row, `None` for gaps.
+pub(crate) type Features = Vec<Option<f64>>;
+
+/// Euclidean distance over the features present in both rows, each one divided by its `scales`.
+fn distance(a: &Features, b: &Features, scales: &[f64]) -> f64 {
+    a.iter()
+        .zip(b.iter())
+        .zip(scales.iter())
+        .filter_map(|((x, y), scale)| Some(((x.as_ref()? - y.as_ref()?) / scale).powi(2)))
+        .sum::<f64>()
+        .sqrt()
+}
+
+/// Indexes of the `k` nearest rows of every row (brute force, the rows of a single class).
+pub(crate) fn nearest_neighbours(rows: &[Features], scales: &[f64], k: usize) -> Vec<Vec<usize>> {
+    (0..rows.len())
+        .map(|i| {
+            let mut others: Vec<(f64, usize)> = (0..rows.len())
+                .filter(|j| *j != i)
+                .map(|j| (distance(&rows[i], &rows[j], scales), j))
+                .collect();
+
+            others.sort_by(|(a, a_id), (b, b_id)| a.total_cmp(b).then(a_id.cmp(b_id)));
+            others.into_iter().take(k).map(|(_, j)| j).collect()
+        })
+        .collect()
+}
+
+/// New SMOTE rows of a single class, grouped by the index of the row they are based on.
+///
+/// A row without neighbours (a class of a single row) is duplicated. Gaps of the base row stay gaps,
+/// features missing from the neighbour keep the value of the base row.
+pub(crate) fn smote(
+    rows: &[Features],
+    scales: &[f64],
+    k: usize,
+    n: usize,
+    rng: &mut SplitMix64,
+) -> HashMap<usize, Vec<Features>> {
+    let mut synthetic: HashMap<usize, Vec<Features>> = HashMap::new();
+    // Neighbours are quadratic in the number of rows, skip classes which need no new rows
+    if rows.is_empty() || n == 0 {
+        return synthetic;
+    }
+
+    let neighbours = nearest_neighbours(rows, scales, k);
+    for _ in 0..n {
+        let base = rng.below(rows.len());
+        let features = match neighbours[base].len() {
+            0 => rows[base].clone(),
+            len => {
+                let other = &rows[neighbours[base][rng.below(len)]];
+                let gap = rng.next_f64();
+
+                rows[base]
+                    .iter()
+                    .zip(other.iter())
+                    .map(|(x, y)| match (x, y) {
+                        (Some(x), Some(y)) => Some(x + gap * (y - x)),
+                        (x, _) => *x,
+                    })
+                    .collect()
+            }
+        };
+
+        synthetic.entry(base).or_default().push(features);
+    }
+
+    synthetic
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use super::{nearest_neighbours, smote, ResamplingMethod};
+    use crate::random::SplitMix64;
+
+    #[test]
+    fn test_targets() {
+        let counts = BTreeMap::from([("0".to_owned(), 90), ("1".to_owned(), 10)]);
+
+        let under = ResamplingMethod::RandomUnder.targets(&counts, 0.5).unwrap();
+        assert_eq!(under["0"], 20);
+        assert_eq!(under["1"], 10);
+
+        let over = ResamplingMethod::RandomOver.targets(&counts, 1.).unwrap();
+        assert_eq!(over["1"], 90);
+        assert!(ResamplingMethod::RandomOver.targets(&counts, 0.).is_err());
+    }
+
+    #[test]
+    fn test_smote() {
+        let rows = vec![
+            vec![Some(0.), Some(0.)],
+            vec![Some(1.), Some(0.)],
+            vec![Some(10.), None],
+        ];
+        let scales = [1., 1.];
+
+        assert_eq!(
+            nearest_neighbours(&rows, &scales, 1),
+            vec![vec![1], vec![0], vec![1]]
+        );
+
+        assert!(smote(&rows, &scales, 1, 0, &mut SplitMix64::new(3)).is_empty());
+
+        let synthetic = smote(&rows, &scales, 1, 20, &mut SplitMix64::new(3));
+        assert_eq!(synthetic.values().map(Vec::len).sum::<usize>(), 20);
+
+        for features in synthetic.get(&0).into_iter().flatten() {
+            let x = features[0].unwrap();
+            assert!((0. ..=1.).contains(&x));
+            assert_eq!(features[1], Some(0.));
+        }
+        for features in synthetic.get(&2).into_iter().flatten() {
+            assert_eq!(features[1], None);
+        }
+    }
+}
@@ -0,0 +1,331 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::deserialization::DeserializationType;
+use crate::error::Result;
+
+/// Declared type of a column. Overrides the inferred one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    Integer,
+    Float,
+    /// `true`/`false` as well as `1`/`0`
+    Boolean,
+    Date,
+    Datetime,
+    /// Any value is kept as is, e.g. IDs which look like numbers
+    String,
+}
+
+impl ColumnType {
+    /// Convert an inferred value to the declared type when it is lossless.
+    ///
+    /// # Return
+    /// `value` as is if it could not be converted (`accepts` tells whether it conforms).
+    pub fn coerce(&self, raw: &str, value: DeserializationType) -> DeserializationType {
+        match (self, value) {
+            (_, DeserializationType::EMPTY) => DeserializationType::EMPTY,
+            (ColumnType::Integer, DeserializationType::FLOAT(x))
+                if x.fract() == 0. && x.abs() < i64::MAX as f64 =>
+            {
+                DeserializationType::INTEGER(x as i64)
+            }
+            (ColumnType::Float, DeserializationType::INTEGER(x)) => {
+                DeserializationType::FLOAT(x as f64)
+            }
+            (ColumnType::Boolean, DeserializationType::INTEGER(x @ (0 | 1))) => {
+                DeserializationType::BOOLEAN(x == 1)
+            }
+            (ColumnType::Datetime, DeserializationType::DATE(x)) => {
+                DeserializationType::DATETIME(x.and_hms_opt(0, 0, 0).unwrap_or_default())
+            }
+            (ColumnType::String, _) => DeserializationType::STRING(raw.trim().to_owned()),
+            (_, value) => value,
+        }
+    }
+
+    /// Whether a (coerced) value conforms to the type. `EMPTY` always does.
+    pub fn accepts(&self, value: &DeserializationType) -> bool {
+        matches!(
+            (self, value),
+            (_, DeserializationType::EMPTY)
+                | (ColumnType::Integer, DeserializationType::INTEGER(_))
+                | (ColumnType::Float, DeserializationType::FLOAT(_))
+                | (ColumnType::Boolean, DeserializationType::BOOLEAN(_))
+                | (ColumnType::Date, DeserializationType::DATE(_))
+                | (ColumnType::Datetime, DeserializationType::DATETIME(_))
+                | (ColumnType::String, DeserializationType::STRING(_))
+        )
+    }
+
+    /// Representative value of the type (stored in `CsvToolkit::types`).
+    pub fn placeholder(&self) -> DeserializationType {
+        match self {
+            ColumnType::Integer => DeserializationType::INTEGER(i64::default()),
+            ColumnType::Float => DeserializationType::FLOAT(f64::default()),
+            ColumnType::Boolean => DeserializationType::BOOLEAN(bool::default()),
+            ColumnType::Date => DeserializationType::DATE(Default::default()),
+            ColumnType::Datetime => DeserializationType::DATETIME(Default::default()),
+            ColumnType::String => DeserializationType::STRING(String::default()),
+        }
+    }
+}
+
+/// Split a column into several new ones, e.g. `120/80` into systolic and diastolic pressure.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Split {
+    pub separator: String,
+    /// Headers of the new columns (declare their schema as for any other column)
+    pub into: Vec<String>,
+}
+
+/// Constraints of a single column. Every field is optional.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColumnSchema {
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub column_type: Option<ColumnType>,
+    #[serde(default = "nullable_default")]
+    pub nullable: bool,
+    /// Inclusive lower bound of numeric values
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    /// Inclusive upper bound of numeric values
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+    /// Allowed raw values of the column
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub categories: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub split: Option<Split>,
+}
+
+fn nullable_default() -> bool {
+    true
+}
+
+impl Default for ColumnSchema {
+    fn default() -> Self {
+        ColumnSchema {
+            column_type: None,
+            nullable: nullable_default(),
+            min: None,
+            max: None,
+            categories: None,
+            split: None,
+        }
+    }
+}
+
+/// Why a value does not conform to its `ColumnSchema`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ViolationKind {
+    Type {
+        expected: ColumnType,
+        found: DeserializationType,
+    },
+    Null,
+    OutOfRange(f64),
+    Category(String),
+}
+
+/// A value which does not conform to the schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    /// Index of the data row
+    pub row: usize,
+    pub column: String,
+    pub kind: ViolationKind,
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Row {}, column '{}': ", self.row, self.column)?;
+        match &self.kind {
+            ViolationKind::Type { expected, found } => {
+                write!(f, "expected {expected:?}, found {}", found.type_name())
+            }
+            ViolationKind::Null => write!(f, "value is missing"),
+            ViolationKind::OutOfRange(x) => write!(f, "{x} is out of range"),
+            ViolationKind::Category(value) => write!(f, "'{value}' is not an allowed category"),
+        }
+    }
+}
+
+/// Declared columns of a csv file, loaded from a TOML or JSON file:
+///
+/// ```toml
+/// [columns."Patient ID"]
+/// type = "string"
+/// nullable = false
+///
+/// [columns."Blood Pressure"]
+/// split = { separator = "/", into = ["Systolic", "Diastolic"] }
+///
+/// [columns.Systolic]
+/// type = "integer"
+/// min = 0
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Schema {
+    #[serde(default)]
+    pub columns: BTreeMap<String, ColumnSchema>,
+}
+
+impl Schema {
+    pub fn new(columns: BTreeMap<String, ColumnSchema>) -> Self {
+        Schema { columns }
+    }
+
+    pub fn get(&self, column: &str) -> Option<&ColumnSchema> {
+        self.columns.get(column)
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Self> {
+        Ok(toml::from_str(toml)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Load a schema file. Files with the `json` extension are read as JSON, any other as TOML.
+    ///
+    /// # Errors
+    /// fs, io, deserialization;
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let content = fs::read_to_string(path.as_ref())?;
+
+        match path.as_ref().extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Self::from_json(&content),
+            _ => Self::from_toml(&content),
+        }
+    }
+
+    /// Check a single (coerced) value of the `column`.
+    ///
+    /// # Arguments
+    ///
+    /// * `row` - The index of the data row.
+    /// * `column` - Header of the column.
+    /// * `raw` - Raw csv value.
+    /// * `value` - Value parsed by `ParseOptions::parse_cell`.
+    ///
+    pub fn validate(
+        &self,
+        row: usize,
+        column: &str,
+        raw: &str,
+        value: &DeserializationType,
+    ) -> Vec<Violation> {
+        let Some(spec) = self.columns.get(column) else {
+            return Vec::new();
+        };
+
+        let mut kinds = Vec::new();
+
+        if *value == DeserializationType::EMPTY {
+            if !spec.nullable {
+                kinds.push(ViolationKind::Null);
+            }
+        } else {
+            if let Some(expected) = spec.column_type {
+                if !expected.accepts(value) {
+                    kinds.push(ViolationKind::Type {
+                        expected,
+                        found: value.clone(),
+                    });
+                }
+            }
+
+            if let Some(x) = value.as_f64() {
+                if spec.min.is_some_and(|min| x < min) || spec.max.is_some_and(|max| x > max) {
+                    kinds.push(ViolationKind::OutOfRange(x));
+                }
+            }
+
+            if let Some(categories) = &spec.categories {
+                let raw = raw.trim();
+                if !categories.iter().any(|category| category == raw) {
+                    kinds.push(ViolationKind::Category(raw.to_owned()));
+                }
+            }
+        }
+
+        kinds
+            .into_iter()
+            .map(|kind| Violation {
+                row,
+                column: column.to_owned(),
+                kind,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ColumnType, Schema, ViolationKind};
+    use crate::deserialization::DeserializationType;
+
+    #[test]
+    fn test_schema_file() {
+        let schema = Schema::from_toml(
+            r#"
+            [columns."Patient ID"]
+            type = "string"
+            nullable = false
+
+            [columns.Sex]
+            categories = ["Male", "Female"]
+
+            [columns.Age]
+            type = "integer"
+            min = 0
+            max = 120
+            "#,
+        )
+        .unwrap();
+
+        let age = schema.get("Age").unwrap();
+        assert_eq!(age.column_type, Some(ColumnType::Integer));
+        assert!(age.nullable);
+        assert_eq!(
+            schema.get("Patient ID").unwrap().column_type,
+            Some(ColumnType::String)
+        );
+
+        let json = serde_json::to_string(&schema).unwrap();
+        assert_eq!(Schema::from_json(&json).unwrap(), schema);
+
+        let violations = schema.validate(3, "Age", "130", &DeserializationType::INTEGER(130));
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].kind, ViolationKind::OutOfRange(130.));
+        assert_eq!(
+            violations[0].to_string(),
+            "Row 3, column 'Age': 130 is out of range"
+        );
+
+        let violations = schema.validate(0, "Patient ID", "", &DeserializationType::EMPTY);
+        assert_eq!(violations[0].kind, ViolationKind::Null);
+
+        let violations = schema.validate(0, "Sex", "M", &DeserializationType::STRING("M".into()));
+        assert_eq!(violations[0].kind, ViolationKind::Category("M".to_owned()));
+    }
+
+    #[test]
+    fn test_coercion() {
+        let boolean = ColumnType::Boolean.coerce("1", DeserializationType::INTEGER(1));
+        assert_eq!(boolean, DeserializationType::BOOLEAN(true));
+        assert!(!ColumnType::Boolean
+            .accepts(&ColumnType::Boolean.coerce("2", DeserializationType::INTEGER(2))));
+
+        let id = ColumnType::String.coerce(" 0042 ", DeserializationType::INTEGER(42));
+        assert_eq!(id, DeserializationType::STRING("0042".to_owned()));
+        assert!(ColumnType::Float
+            .accepts(&ColumnType::Float.coerce("3", DeserializationType::INTEGER(3))));
+    }
+}
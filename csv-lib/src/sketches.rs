@@ -0,0 +1,360 @@
+//! Constant memory summaries of a stream of values, used by `ColumnStatistics`.
+
+use std::cmp::Reverse;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+use crate::constants::{EXACT_DISTINCT_LIMIT, HLL_PRECISION};
+use crate::statistics::quantile;
+
+/// Running central moments (Welford/Pébay update), numerically stable in a single pass.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Moments {
+    n: f64,
+    mean: f64,
+    m2: f64,
+    m3: f64,
+    m4: f64,
+}
+
+impl Moments {
+    pub fn push(&mut self, x: f64) {
+        let n1 = self.n;
+        self.n += 1.;
+        let n = self.n;
+
+        let delta = x - self.mean;
+        let delta_n = delta / n;
+        let delta_n2 = delta_n * delta_n;
+        let term = delta * delta_n * n1;
+
+        self.mean += delta_n;
+        self.m4 += term * delta_n2 * (n * n - 3. * n + 3.) + 6. * delta_n2 * self.m2
+            - 4. * delta_n * self.m3;
+        self.m3 += term * delta_n * (n - 2.) - 3. * delta_n * self.m2;
+        self.m2 += term;
+    }
+
+    pub fn count(&self) -> usize {
+        self.n as usize
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        (self.n > 0.).then_some(self.mean)
+    }
+
+    /// Population variance.
+    pub fn variance(&self) -> Option<f64> {
+        (self.n > 0.).then(|| self.m2 / self.n)
+    }
+
+    /// Sample skewness (`None` for constant values).
+    pub fn skewness(&self) -> Option<f64> {
+        (self.m2 > 0.).then(|| self.n.sqrt() * self.m3 / self.m2.powf(1.5))
+    }
+
+    /// Excess kurtosis, `0` for the normal distribution (`None` for constant values).
+    pub fn kurtosis(&self) -> Option<f64> {
+        (self.m2 > 0.).then(|| self.n * self.m4 / (self.m2 * self.m2) - 3.)
+    }
+}
+
+/// Streaming quantile estimate with the P² algorithm (Jain & Chlamtac, 1985).
+///
+/// Keeps only five markers. The result is exact for up to five values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct P2Quantile {
+    q: f64,
+    count: usize,
+    heights: [f64; 5],
+    positions: [f64; 5],
+    desired: [f64; 5],
+    increments: [f64; 5],
+}
+
+impl P2Quantile {
+    pub fn new(q: f64) -> Self {
+        let q = q.clamp(0., 1.);
+
+        P2Quantile {
+            q,
+            count: 0,
+            heights: [0.; 5],
+            positions: [0., 1., 2., 3., 4.],
+            desired: [0., 2. * q, 4. * q, 2. + 2. * q, 4.],
+            increments: [0., q / 2., q, (1. + q) / 2., 1.],
+        }
+    }
+
+    pub fn push(&mut self, x: f64) {
+        if self.count < 5 {
+            self.heights[self.count] = x;
+            self.count += 1;
+            if self.count == 5 {
+                self.heights.sort_by(|a, b| a.total_cmp(b));
+            }
+            return;
+        }
+        self.count += 1;
+
+        let cell = if x < self.heights[0] {
+            self.heights[0] = x;
+            0
+        } else if x >= self.heights[4] {
+            self.heights[4] = x;
+            3
+        } else {
+            (0..4)
+                .find(|&i| self.heights[i] <= x && x < self.heights[i + 1])
+                .unwrap_or(3)
+        };
+
+        for position in self.positions.iter_mut().skip(cell + 1) {
+            *position += 1.;
+        }
+        for (desired, increment) in self.desired.iter_mut().zip(self.increments) {
+            *desired += increment;
+        }
+
+        for i in 1..4 {
+            let d = self.desired[i] - self.positions[i];
+            let right = self.positions[i + 1] - self.positions[i];
+            let left = self.positions[i - 1] - self.positions[i];
+
+            if (d >= 1. && right > 1.) || (d <= -1. && left < -1.) {
+                let d = d.signum();
+                let parabolic = self.parabolic(i, d);
+
+                self.heights[i] =
+                    if self.heights[i - 1] < parabolic && parabolic < self.heights[i + 1] {
+                        parabolic
+                    } else {
+                        self.linear(i, d)
+                    };
+                self.positions[i] += d;
+            }
+        }
+    }
+
+    pub fn estimate(&self) -> Option<f64> {
+        if self.count > 5 {
+            return Some(self.heights[2]);
+        }
+
+        let mut values = self.heights[..self.count].to_vec();
+        values.sort_by(|a, b| a.total_cmp(b));
+        quantile(&values, self.q)
+    }
+
+    fn parabolic(&self, i: usize, d: f64) -> f64 {
+        let (q, n) = (&self.heights, &self.positions);
+
+        q[i] + d / (n[i + 1] - n[i - 1])
+            * ((n[i] - n[i - 1] + d) * (q[i + 1] - q[i]) / (n[i + 1] - n[i])
+                + (n[i + 1] - n[i] - d) * (q[i] - q[i - 1]) / (n[i] - n[i - 1]))
+    }
+
+    fn linear(&self, i: usize, d: f64) -> f64 {
+        let j = if d > 0. { i + 1 } else { i - 1 };
+        self.heights[i]
+            + d * (self.heights[j] - self.heights[i]) / (self.positions[j] - self.positions[i])
+    }
+}
+
+/// Distinct values counter: exact up to `EXACT_DISTINCT_LIMIT` values, HyperLogLog estimate above.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DistinctCounter {
+    exact: HashSet<u64>,
+    registers: Vec<u8>,
+}
+
+impl DistinctCounter {
+    pub fn push(&mut self, value: &str) {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        if self.registers.is_empty() {
+            self.exact.insert(hash);
+            if self.exact.len() <= EXACT_DISTINCT_LIMIT {
+                return;
+            }
+
+            self.registers = vec![0; 1 << HLL_PRECISION];
+            for hash in std::mem::take(&mut self.exact) {
+                self.register(hash);
+            }
+        } else {
+            self.register(hash);
+        }
+    }
+
+    /// Whether `count` is exact.
+    pub fn is_exact(&self) -> bool {
+        self.registers.is_empty()
+    }
+
+    pub fn count(&self) -> usize {
+        if self.is_exact() {
+            return self.exact.len();
+        }
+
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1. + 1.079 / m);
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|r| 2_f64.powi(-(*r as i32)))
+            .sum();
+        let estimate = alpha * m * m / sum;
+
+        let zeros = self.registers.iter().filter(|r| **r == 0).count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            // Linear counting is more precise for small cardinalities
+            return (m * (m / zeros as f64).ln()).round() as usize;
+        }
+
+        estimate.round() as usize
+    }
+
+    fn register(&mut self, hash: u64) {
+        let index = (hash >> (64 - HLL_PRECISION)) as usize;
+        let rank = ((hash << HLL_PRECISION).leading_zeros() + 1).min(64 - HLL_PRECISION + 1) as u8;
+
+        self.registers[index] = self.registers[index].max(rank);
+    }
+}
+
+/// Most frequent values with the Space-Saving algorithm (Metwally et al., 2005).
+///
+/// Counts are exact while the number of distinct values does not exceed the capacity. Otherwise a
+/// value which replaced an evicted one inherits its count, so counts could be overestimated.
+///
+/// The evicted value is the last one of `top`: the least frequent, the greatest value among ties.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TopK {
+    capacity: usize,
+    counters: HashMap<String, usize>,
+    /// Counters ordered by eviction priority, the next evicted one first
+    order: BTreeSet<(usize, Reverse<String>)>,
+}
+
+impl TopK {
+    pub fn new(capacity: usize) -> Self {
+        TopK {
+            capacity,
+            counters: HashMap::with_capacity(capacity),
+            order: BTreeSet::new(),
+        }
+    }
+
+    pub fn push(&mut self, value: &str) {
+        if let Some(count) = self.counters.get_mut(value) {
+            self.order.remove(&(*count, Reverse(value.to_owned())));
+            *count += 1;
+            self.order.insert((*count, Reverse(value.to_owned())));
+            return;
+        }
+
+        let count = if self.counters.len() < self.capacity {
+            1
+        } else {
+            match self.order.pop_first() {
+                Some((min, Reverse(evicted))) => {
+                    self.counters.remove(&evicted);
+                    min + 1
+                }
+                None => return,
+            }
+        };
+
+        self.counters.insert(value.to_owned(), count);
+        self.order.insert((count, Reverse(value.to_owned())));
+    }
+
+    /// `k` most frequent values with their counts, the most frequent first.
+    pub fn top(&self, k: usize) -> Vec<(String, usize)> {
+        let mut top: Vec<(String, usize)> = self
+            .counters
+            .iter()
+            .map(|(value, count)| (value.clone(), *count))
+            .collect();
+
+        top.sort_by(|(a_value, a_count), (b_value, b_count)| {
+            b_count.cmp(a_count).then_with(|| a_value.cmp(b_value))
+        });
+        top.truncate(k);
+        top
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DistinctCounter, Moments, P2Quantile, TopK};
+
+    #[test]
+    fn test_moments() {
+        let mut moments = Moments::default();
+        for x in [2., 4., 4., 4., 5., 5., 7., 9.] {
+            moments.push(x);
+        }
+
+        assert_eq!(moments.mean(), Some(5.));
+        assert!((moments.variance().unwrap() - 4.).abs() < 1e-12);
+        assert!((moments.skewness().unwrap() - 0.65625).abs() < 1e-12);
+        assert!((moments.kurtosis().unwrap() + 0.21875).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_p2_quantile() {
+        let mut median = P2Quantile::new(0.5);
+        for x in [3., 1., 2.] {
+            median.push(x);
+        }
+        assert_eq!(median.estimate(), Some(2.));
+
+        let mut quartile = P2Quantile::new(0.75);
+        // Deterministic permutation of 0..1000
+        for i in 0..1000 {
+            quartile.push(((i * 379) % 1000) as f64);
+        }
+        assert!((quartile.estimate().unwrap() - 749.25).abs() < 10.);
+    }
+
+    #[test]
+    fn test_distinct_counter() {
+        let mut small = DistinctCounter::default();
+        for value in ["a", "b", "a"] {
+            small.push(value);
+        }
+        assert_eq!(small.count(), 2);
+        assert!(small.is_exact());
+
+        let mut large = DistinctCounter::default();
+        for i in 0..50_000 {
+            large.push(&(i % 40_000).to_string());
+        }
+        assert!(!large.is_exact());
+        assert!((large.count() as f64 - 40_000.).abs() < 40_000. * 0.05);
+    }
+
+    #[test]
+    fn test_top_k() {
+        let mut top = TopK::new(2);
+        for value in ["a", "b", "a", "c", "a"] {
+            top.push(value);
+        }
+
+        assert_eq!(top.top(1), vec![("a".to_owned(), 3)]);
+
+        // Ties are evicted by value, whatever the order of the values
+        for values in [["a", "b", "c"], ["b", "a", "c"]] {
+            let mut top = TopK::new(2);
+            for value in values {
+                top.push(value);
+            }
+            assert_eq!(top.top(2), vec![("c".to_owned(), 2), ("a".to_owned(), 1)]);
+        }
+    }
+}
@@ -0,0 +1,358 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+use crate::deserialization::DeserializationType;
+use crate::error::{Error, Result};
+use crate::random::SplitMix64;
+
+/// How `CsvToolkit::split` assigns rows to the outputs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SplitStrategy {
+    /// Uniformly random rows.
+    Random,
+    /// Every value of the `label` column keeps (almost) the same proportion in each output.
+    Stratified { label: String },
+    /// All rows with the same value of the `group` column (e.g. a patient ID) go to the same
+    /// output. Output sizes follow the ratios as close as the group sizes allow.
+    Grouped { group: String },
+    /// Earliest rows by the `column` (date, datetime or number) go to the first output, the latest
+    /// to the last one. No shuffling, the seed is ignored.
+    TimeOrdered { column: String },
+}
+
+/// Where `CsvToolkit::k_fold` puts the fold assignment.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FoldOutput {
+    /// A column with the fold index appended to the data.
+    Column(String),
+    /// `fold_{i}_train.csv` and `fold_{i}_val.csv` file pairs in the directory.
+    Files(PathBuf),
+}
+
+/// Rows and label distribution of every fold, to verify a fold assignment.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FoldSummary {
+    pub sizes: Vec<usize>,
+    /// Rows per label value in every fold (empty maps without a label column)
+    pub label_counts: Vec<BTreeMap<String, usize>>,
+}
+
+impl FoldSummary {
+    pub fn new(k: usize) -> Self {
+        FoldSummary {
+            sizes: vec![0; k],
+            label_counts: vec![BTreeMap::new(); k],
+        }
+    }
+
+    pub fn push(&mut self, fold: usize, label: Option<&str>) {
+        self.sizes[fold] += 1;
+        if let Some(label) = label {
+            *self.label_counts[fold].entry(label.to_owned()).or_default() += 1;
+        }
+    }
+
+    /// Share of the label value among the rows of the fold.
+    pub fn proportion(&self, fold: usize, label: &str) -> f64 {
+        match self.sizes.get(fold) {
+            Some(size) if *size > 0 => {
+                self.label_counts[fold]
+                    .get(label)
+                    .copied()
+                    .unwrap_or_default() as f64
+                    / *size as f64
+            }
+            _ => 0.,
+        }
+    }
+}
+
+impl std::fmt::Display for FoldSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (fold, size) in self.sizes.iter().enumerate() {
+            write!(f, "fold {fold}: {size} rows")?;
+            for (label, count) in self.label_counts[fold].iter() {
+                let share = 100. * self.proportion(fold, label);
+                write!(f, ", '{label}': {count} ({share:.1}%)")?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Number of rows of each output: `n` split by `ratios` with the largest remainder method.
+///
+/// # Errors
+/// No ratio, a negative ratio or only zero ratios.
+pub fn allocate(n: usize, ratios: &[f64]) -> Result<Vec<usize>> {
+    let sum: f64 = ratios.iter().sum();
+    if ratios.is_empty() || ratios.iter().any(|r| *r < 0. || !r.is_finite()) || sum <= 0. {
+        return Err(Error::invalid_argument(format!(
+            "Invalid split ratios {ratios:?}"
+        )));
+    }
+
+    let exact: Vec<f64> = ratios.iter().map(|r| n as f64 * r / sum).collect();
+    let mut sizes: Vec<usize> = exact.iter().map(|x| x.floor() as usize).collect();
+
+    let mut by_remainder: Vec<usize> = (0..ratios.len()).collect();
+    by_remainder.sort_by(|a, b| {
+        (exact[*b] - exact[*b].floor())
+            .total_cmp(&(exact[*a] - exact[*a].floor()))
+            .then(a.cmp(b))
+    });
+
+    let missing = n - sizes.iter().sum::<usize>();
+    for i in by_remainder.into_iter().take(missing) {
+        sizes[i] += 1;
+    }
+
+    Ok(sizes)
+}
+
+/// Output sizes of several strata (e.g. label values), so each stratum is split by `ratios` and
+/// the total output sizes match `allocate` over all rows.
+///
+/// Strata are floored first, then the missing rows of each stratum go to the outputs with the
+/// largest remainders which are still below their total size.
+///
+/// # Errors
+/// Invalid ratios (see `allocate`).
+pub fn allocate_strata(counts: &[usize], ratios: &[f64]) -> Result<Vec<Vec<usize>>> {
+    let mut deficits = allocate(counts.iter().sum(), ratios)?;
+    let sum: f64 = ratios.iter().sum();
+
+    let exact: Vec<Vec<f64>> = counts
+        .iter()
+        .map(|n| ratios.iter().map(|r| *n as f64 * r / sum).collect())
+        .collect();
+    let mut sizes: Vec<Vec<usize>> = exact
+        .iter()
+        .map(|shares| shares.iter().map(|x| x.floor() as usize).collect())
+        .collect();
+
+    for stratum in sizes.iter() {
+        for (deficit, size) in deficits.iter_mut().zip(stratum) {
+            *deficit -= size;
+        }
+    }
+
+    for (n, (shares, stratum)) in counts.iter().zip(exact.iter().zip(sizes.iter_mut())) {
+        let mut by_remainder: Vec<usize> = (0..ratios.len()).collect();
+        by_remainder.sort_by(|a, b| {
+            (shares[*b] - shares[*b].floor())
+                .total_cmp(&(shares[*a] - shares[*a].floor()))
+                .then(a.cmp(b))
+        });
+
+        // Less missing rows than outputs: every output gets at most one of them
+        for _ in 0..n - stratum.iter().sum::<usize>() {
+            let index = by_remainder
+                .iter()
+                .position(|part| deficits[*part] > 0)
+                .unwrap_or_default();
+            let part = by_remainder.remove(index);
+
+            stratum[part] += 1;
+            deficits[part] = deficits[part].saturating_sub(1);
+        }
+    }
+
+    Ok(sizes)
+}
+
+/// Streaming random assignment with exact output sizes: every next row goes to an output with the
+/// probability proportional to the number of rows it still misses.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SequentialSampler {
+    remaining: Vec<usize>,
+}
+
+impl SequentialSampler {
+    pub fn new(sizes: Vec<usize>) -> Self {
+        SequentialSampler { remaining: sizes }
+    }
+
+    pub fn next(&mut self, rng: &mut SplitMix64) -> Option<usize> {
+        let total: usize = self.remaining.iter().sum();
+        if total == 0 {
+            return None;
+        }
+
+        let mut pick = rng.below(total);
+        for (part, remaining) in self.remaining.iter_mut().enumerate() {
+            if pick < *remaining {
+                *remaining -= 1;
+                return Some(part);
+            }
+            pick -= *remaining;
+        }
+
+        None
+    }
+}
+
+/// Output of every row, prepared from the first pass over the data.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Assignment {
+    /// Sampler per key (a single key for `Random`, a label value for `Stratified`)
+    Sampled(HashMap<String, SequentialSampler>),
+    /// Output per group
+    Grouped(HashMap<String, usize>),
+    /// Output per row
+    Ordered(Vec<usize>),
+}
+
+impl Assignment {
+    /// # Arguments
+    ///
+    /// * `counts` - Number of rows per key (label value or group).
+    /// * `ratios` - Relative sizes of the outputs.
+    /// * `grouped` - Keep every key in a single output instead of sampling each one separately.
+    /// * `rng` - Generator which shuffles the groups.
+    ///
+    pub fn by_keys(
+        counts: HashMap<String, usize>,
+        ratios: &[f64],
+        grouped: bool,
+        rng: &mut SplitMix64,
+    ) -> Result<Self> {
+        if !grouped {
+            let mut strata: Vec<(String, usize)> = counts.into_iter().collect();
+            strata.sort();
+
+            let counts: Vec<usize> = strata.iter().map(|(_, count)| *count).collect();
+            let samplers = strata
+                .into_iter()
+                .map(|(key, _)| key)
+                .zip(allocate_strata(&counts, ratios)?)
+                .map(|(key, sizes)| (key, SequentialSampler::new(sizes)))
+                .collect();
+
+            return Ok(Assignment::Sampled(samplers));
+        }
+
+        let total = counts.values().sum();
+        let targets = allocate(total, ratios)?;
+
+        let mut groups: Vec<(String, usize)> = counts.into_iter().collect();
+        groups.sort();
+        rng.shuffle(&mut groups);
+
+        // Fill the outputs one by one, a group goes to the output whose cumulative target is not
+        // reached yet
+        let mut assignment = HashMap::with_capacity(groups.len());
+        let (mut part, mut assigned, mut target) = (0, 0, targets[0]);
+        for (group, count) in groups {
+            while assigned >= target && part + 1 < targets.len() {
+                part += 1;
+                target += targets[part];
+            }
+            assigned += count;
+            assignment.insert(group, part);
+        }
+
+        Ok(Assignment::Grouped(assignment))
+    }
+
+    /// # Arguments
+    ///
+    /// * `keys` - Ordering value of every row, by row index.
+    /// * `ratios` - Relative sizes of the outputs.
+    ///
+    /// # Errors
+    /// Gaps or values which could not be compared.
+    pub fn by_order(keys: Vec<DeserializationType>, ratios: &[f64]) -> Result<Self> {
+        let sizes = allocate(keys.len(), ratios)?;
+
+        let mut order: Vec<usize> = (0..keys.len()).collect();
+        let mut incomparable = false;
+        order.sort_by(|a, b| {
+            keys[*a].partial_cmp(&keys[*b]).unwrap_or_else(|| {
+                incomparable = true;
+                std::cmp::Ordering::Equal
+            })
+        });
+
+        if incomparable || keys.contains(&DeserializationType::EMPTY) {
+            return Err(Error::type_mismatch(
+                "values of a single comparable type without gaps",
+                "gaps or values of different types",
+            ));
+        }
+
+        let mut parts = vec![0; keys.len()];
+        let mut rank = 0;
+        for (part, size) in sizes.into_iter().enumerate() {
+            for row_id in &order[rank..rank + size] {
+                parts[*row_id] = part;
+            }
+            rank += size;
+        }
+
+        Ok(Assignment::Ordered(parts))
+    }
+
+    /// Output of the row.
+    pub fn part(&mut self, row_id: usize, key: &str, rng: &mut SplitMix64) -> Option<usize> {
+        match self {
+            Assignment::Sampled(samplers) => samplers.get_mut(key)?.next(rng),
+            Assignment::Grouped(groups) => groups.get(key).copied(),
+            Assignment::Ordered(parts) => parts.get(row_id).copied(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::{allocate, allocate_strata, Assignment};
+    use crate::deserialization::DeserializationType;
+    use crate::random::SplitMix64;
+
+    #[test]
+    fn test_allocate() {
+        assert_eq!(allocate(10, &[0.7, 0.15, 0.15]).unwrap(), vec![7, 2, 1]);
+        assert_eq!(allocate(3, &[1., 1.]).unwrap(), vec![2, 1]);
+        assert!(allocate(3, &[-1., 2.]).is_err());
+        assert!(allocate(3, &[]).is_err());
+
+        let strata = allocate_strata(&[9, 5], &[0.5, 0.5]).unwrap();
+        assert_eq!(strata, vec![vec![5, 4], vec![2, 3]]);
+    }
+
+    #[test]
+    fn test_grouped_assignment() {
+        let counts = HashMap::from([
+            ("a".to_owned(), 4),
+            ("b".to_owned(), 3),
+            ("c".to_owned(), 2),
+            ("d".to_owned(), 1),
+        ]);
+        let mut rng = SplitMix64::new(7);
+        let mut assignment = Assignment::by_keys(counts, &[0.5, 0.5], true, &mut rng).unwrap();
+
+        let parts: Vec<Option<usize>> = ["a", "b", "c", "d"]
+            .iter()
+            .map(|group| assignment.part(0, group, &mut rng))
+            .collect();
+        assert!(parts.iter().all(|part| part.is_some()));
+        assert!(parts.contains(&Some(0)) && parts.contains(&Some(1)));
+    }
+
+    #[test]
+    fn test_ordered_assignment() {
+        let keys = [3, 1, 4, 2].map(DeserializationType::INTEGER).to_vec();
+        let mut assignment = Assignment::by_order(keys, &[0.5, 0.5]).unwrap();
+        let mut rng = SplitMix64::new(0);
+
+        let parts: Vec<Option<usize>> = (0..4).map(|i| assignment.part(i, "", &mut rng)).collect();
+        assert_eq!(parts, vec![Some(1), Some(0), Some(1), Some(0)]);
+
+        let gaps = vec![DeserializationType::INTEGER(1), DeserializationType::EMPTY];
+        assert!(Assignment::by_order(gaps, &[0.5, 0.5]).is_err());
+    }
+}
@@ -0,0 +1,186 @@
+use crate::constants::TOP_K_CAPACITY;
+use crate::deserialization::DeserializationType;
+use crate::sketches::{DistinctCounter, Moments, P2Quantile, TopK};
+
+/// Median of the passed values. Sorts `values` in place.
+pub fn median(values: &mut [f64]) -> Option<f64> {
+    values.sort_by(|a, b| a.total_cmp(b));
+    quantile(values, 0.5)
+}
+
+/// Quantile of already sorted values with linear interpolation between the closest ranks.
+///
+/// # Arguments
+///
+/// * `sorted` - Values sorted in ascending order.
+/// * `q` - Quantile in `[0, 1]` range (e.g. `0.25` for the first quartile).
+///
+pub fn quantile(sorted: &[f64], q: f64) -> Option<f64> {
+    if sorted.is_empty() {
+        return None;
+    }
+
+    let pos = q.clamp(0_f64, 1_f64) * (sorted.len() - 1) as f64;
+    let (lo, hi) = (pos.floor() as usize, pos.ceil() as usize);
+
+    Some(sorted[lo] + (sorted[hi] - sorted[lo]) * (pos - lo as f64))
+}
+
+/// Arithmetic mean and population standard deviation.
+pub fn mean_std(values: &[f64]) -> Option<(f64, f64)> {
+    if values.is_empty() {
+        return None;
+    }
+
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let variance = values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n;
+
+    Some((mean, variance.sqrt()))
+}
+
+/// Descriptive statistics of a single column, updated value by value in constant memory.
+///
+/// Numeric statistics (mean, quartiles, ...) use the numeric values only; count, distinct count
+/// and the most frequent values use every non empty raw value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnStatistics {
+    count: usize,
+    null_count: usize,
+    moments: Moments,
+    quartiles: [P2Quantile; 3],
+    distinct: DistinctCounter,
+    frequent: TopK,
+}
+
+impl Default for ColumnStatistics {
+    fn default() -> Self {
+        ColumnStatistics {
+            count: 0,
+            null_count: 0,
+            moments: Moments::default(),
+            quartiles: [
+                P2Quantile::new(0.25),
+                P2Quantile::new(0.5),
+                P2Quantile::new(0.75),
+            ],
+            distinct: DistinctCounter::default(),
+            frequent: TopK::new(TOP_K_CAPACITY),
+        }
+    }
+}
+
+impl ColumnStatistics {
+    /// Register a value of the column.
+    ///
+    /// # Arguments
+    ///
+    /// * `raw` - Raw csv value.
+    /// * `value` - Parsed value.
+    ///
+    pub fn push(&mut self, raw: &str, value: &DeserializationType) {
+        if *value == DeserializationType::EMPTY {
+            self.null_count += 1;
+            return;
+        }
+
+        let raw = raw.trim();
+        self.count += 1;
+        self.distinct.push(raw);
+        self.frequent.push(raw);
+
+        if let Some(x) = value.as_f64() {
+            self.moments.push(x);
+            for quartile in self.quartiles.iter_mut() {
+                quartile.push(x);
+            }
+        }
+    }
+
+    /// Number of non empty values.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn null_count(&self) -> usize {
+        self.null_count
+    }
+
+    /// Number of numeric values.
+    pub fn numeric_count(&self) -> usize {
+        self.moments.count()
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        self.moments.mean()
+    }
+
+    /// Population variance.
+    pub fn variance(&self) -> Option<f64> {
+        self.moments.variance()
+    }
+
+    /// Population standard deviation.
+    pub fn std(&self) -> Option<f64> {
+        self.variance().map(f64::sqrt)
+    }
+
+    pub fn skewness(&self) -> Option<f64> {
+        self.moments.skewness()
+    }
+
+    /// Excess kurtosis.
+    pub fn kurtosis(&self) -> Option<f64> {
+        self.moments.kurtosis()
+    }
+
+    /// Estimated median (exact for less than five values).
+    pub fn median(&self) -> Option<f64> {
+        self.quartiles[1].estimate()
+    }
+
+    /// Estimated first, second and third quartiles.
+    pub fn quartiles(&self) -> Option<(f64, f64, f64)> {
+        Some((
+            self.quartiles[0].estimate()?,
+            self.quartiles[1].estimate()?,
+            self.quartiles[2].estimate()?,
+        ))
+    }
+
+    /// Number of distinct non empty values (estimated for large columns).
+    pub fn distinct_count(&self) -> usize {
+        self.distinct.count()
+    }
+
+    /// `k` most frequent values with their counts (`k` is at most `TOP_K_CAPACITY`).
+    pub fn top_k(&self, k: usize) -> Vec<(String, usize)> {
+        self.frequent.top(k)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ColumnStatistics;
+    use crate::deserialization::DeserializationType;
+
+    #[test]
+    fn test_column_statistics() {
+        let mut statistics = ColumnStatistics::default();
+        for raw in ["1", "", "2", "2", "3", "4"] {
+            let value = match raw.parse() {
+                Ok(x) => DeserializationType::INTEGER(x),
+                Err(_) => DeserializationType::EMPTY,
+            };
+            statistics.push(raw, &value);
+        }
+
+        assert_eq!(statistics.count(), 5);
+        assert_eq!(statistics.null_count(), 1);
+        assert_eq!(statistics.mean(), Some(2.4));
+        assert_eq!(statistics.median(), Some(2.));
+        assert_eq!(statistics.quartiles(), Some((2., 2., 3.)));
+        assert_eq!(statistics.distinct_count(), 4);
+        assert_eq!(statistics.top_k(1), vec![("2".to_owned(), 2)]);
+    }
+}
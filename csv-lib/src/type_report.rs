@@ -0,0 +1,100 @@
+use std::collections::BTreeMap;
+
+use crate::constants::MAX_REPORTED_ROWS;
+use crate::deserialization::DeserializationType;
+
+/// Types seen in a single column during `preprocessing`.
+///
+/// Helps to find cells which prevent a column from having a specific type, e.g. a few
+/// `STRING` values in an otherwise numeric column.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TypeReport {
+    /// Number of values of each type (see `DeserializationType::type_name`)
+    pub counts: BTreeMap<&'static str, usize>,
+    /// First `MAX_REPORTED_ROWS` rows of each type
+    rows: BTreeMap<&'static str, Vec<usize>>,
+}
+
+/// Types which could be stored in the same column without falling back to STRING.
+fn family(type_name: &'static str) -> &'static str {
+    match type_name {
+        "INTEGER" | "FLOAT" => "NUMBER",
+        "DATE" | "DATETIME" => "DATE",
+        other => other,
+    }
+}
+
+impl TypeReport {
+    pub fn push(&mut self, row_id: usize, value: &DeserializationType) {
+        let type_name = value.type_name();
+
+        *self.counts.entry(type_name).or_default() += 1;
+
+        let rows = self.rows.entry(type_name).or_default();
+        if rows.len() < MAX_REPORTED_ROWS {
+            rows.push(row_id);
+        }
+    }
+
+    /// Most frequent group of compatible non EMPTY types: `NUMBER` (INTEGER and FLOAT),
+    /// `DATE` (DATE and DATETIME), `BOOLEAN` or `STRING`.
+    pub fn majority(&self) -> Option<&'static str> {
+        let mut families: BTreeMap<&'static str, usize> = BTreeMap::new();
+        for (type_name, count) in self.counts.iter() {
+            if *type_name != "EMPTY" {
+                *families.entry(family(type_name)).or_default() += count;
+            }
+        }
+
+        families
+            .into_iter()
+            .max_by_key(|(_, count)| *count)
+            .map(|(family, _)| family)
+    }
+
+    /// Rows with values incompatible with the `majority` types, grouped by type.
+    ///
+    /// Only the first `MAX_REPORTED_ROWS` rows of each type are listed.
+    pub fn minority_rows(&self) -> BTreeMap<&'static str, Vec<usize>> {
+        let Some(majority) = self.majority() else {
+            return BTreeMap::new();
+        };
+
+        self.rows
+            .iter()
+            .filter(|(type_name, _)| **type_name != "EMPTY" && family(type_name) != majority)
+            .map(|(type_name, rows)| (*type_name, rows.clone()))
+            .collect()
+    }
+
+    /// Whether all non EMPTY values of the column have compatible types.
+    pub fn is_consistent(&self) -> bool {
+        self.minority_rows().is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TypeReport;
+    use crate::deserialization::DeserializationType;
+
+    #[test]
+    fn test_minority_rows() {
+        let mut report = TypeReport::default();
+        let values = [
+            DeserializationType::INTEGER(1),
+            DeserializationType::EMPTY,
+            DeserializationType::FLOAT(2.5),
+            DeserializationType::STRING("n/d".to_owned()),
+            DeserializationType::INTEGER(3),
+        ];
+        for (row_id, value) in values.iter().enumerate() {
+            report.push(row_id, value);
+        }
+
+        assert_eq!(report.counts["INTEGER"], 2);
+        assert_eq!(report.majority(), Some("NUMBER"));
+        assert_eq!(report.minority_rows().get("STRING"), Some(&vec![3]));
+        assert!(!report.is_consistent());
+    }
+}